@@ -0,0 +1,196 @@
+//! Measures the CPU the worker thread burns while idle & the message throughput of the worker loop.
+//!
+//! Run with `cargo bench -p bevy_networking --bench worker`. It only uses APIs which predate the blocking worker, so
+//! to compare against an older worker copy this file onto that revision & run it there as well.
+//!
+//! CPU time is read from `/proc/self/stat` & the `schedstat` of each thread, so it is only reported on Linux.
+//!
+//! Three runs each on one machine, the idle numbers covering both the server's & the client's worker:
+//!
+//! | worker                         | throughput          | idle (memory) | idle (laminar) |
+//! |--------------------------------|---------------------|---------------|----------------|
+//! | fixed 1ms sleep                | 658k - 675k msg/s   | 2.2% - 2.5%   | 2.4% - 2.7%    |
+//! | condvar wake up & 4ms backoff  | 584k - 751k msg/s   | 1.1% - 1.4%   | 1.1% - 1.6%    |
+use bevy::prelude::*;
+
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+use bevy_networking::{
+    events::NetworkMessage, AddNetworkMessage, ChannelId, LaminarConfig, MemoryConfig, MemoryNetwork, NetworkDelivery,
+    NetworkResource, NetworkingPlugin, Transport,
+};
+
+const SERVER: &str = "127.0.0.1:12451";
+const CLIENT: &str = "127.0.0.1:12450";
+
+const IDLE_DURATION: Duration = Duration::from_secs(3);
+const MESSAGES: usize = 100_000;
+const MESSAGES_PER_FRAME: usize = 1_000;
+
+/// Clock ticks per second used by `/proc/self/stat`, which is 100 on practically every Linux system.
+const CLOCK_TICKS_PER_SEC: f64 = 100.0;
+
+
+#[derive(Default)]
+struct Received(usize);
+
+fn main() {
+    throughput();
+    idle("memory", |network| Transport::Memory(MemoryConfig::new(network)));
+    idle("laminar", |_| Transport::Laminar(LaminarConfig::default()));
+}
+
+/// Two bound sockets without any traffic. Only the threads started here are measured, since older workers may keep
+/// running after their app is dropped.
+fn idle(name: &str, transport: impl Fn(&MemoryNetwork) -> Transport) {
+    let network = MemoryNetwork::new();
+    let existing = threads();
+
+    let mut server = build_app().app;
+    let mut client = build_app().app;
+
+    bind(&mut server, SERVER, transport(&network));
+    bind(&mut client, CLIENT, transport(&network));
+
+    // Past the first stats update & any other start up work
+    std::thread::sleep(Duration::from_secs(2));
+
+    let started: Vec<String> = threads().into_iter().filter(|thread| !existing.contains(thread)).collect();
+    let cpu_start = threads_cpu_time(&started);
+    let start = Instant::now();
+
+    std::thread::sleep(IDLE_DURATION);
+
+    report_cpu(&format!("idle ({})", name), cpu_start, threads_cpu_time(&started), start.elapsed());
+}
+
+/// Sends `MESSAGES` small messages from the client to the server over a `MemoryNetwork` as fast as possible.
+fn throughput() {
+    let network = MemoryNetwork::new();
+
+    let mut server = build_app();
+    server.add_resource(Received::default()).add_system(count_messages.system());
+
+    let mut server = server.app;
+    let mut client = build_app().app;
+
+    bind(&mut server, SERVER, Transport::Memory(MemoryConfig::new(&network)));
+    bind(&mut client, CLIENT, Transport::Memory(MemoryConfig::new(&network)));
+
+    let server_addr = SERVER.parse().unwrap();
+    let mut sent = 0;
+    let mut send_errors = 0;
+
+    let cpu_start = cpu_time();
+    let start = Instant::now();
+
+    while server.resources.get::<Received>().unwrap().0 < MESSAGES - send_errors {
+        if start.elapsed() > Duration::from_secs(60) {
+            println!("throughput: timed out");
+            break;
+        }
+
+        {
+            let net = client.resources.get::<NetworkResource>().unwrap();
+
+            for _ in 0..MESSAGES_PER_FRAME.min(MESSAGES - sent) {
+                if net.send(server_addr, &sent).is_err() {
+                    send_errors += 1;
+                }
+
+                sent += 1;
+            }
+        }
+
+        client.update();
+        server.update();
+    }
+
+    let elapsed = start.elapsed();
+    let received = server.resources.get::<Received>().unwrap().0;
+
+    println!(
+        "throughput: {} messages in {:.3?}, {:.0} messages/s, {} send errors",
+        received,
+        elapsed,
+        received as f64 / elapsed.as_secs_f64(),
+        send_errors
+    );
+    report_cpu("throughput", cpu_start, cpu_time(), elapsed);
+}
+
+
+fn build_app() -> AppBuilder {
+    let mut app = App::build();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(NetworkingPlugin)
+        .add_network_message::<usize>(ChannelId(0), NetworkDelivery::ReliableOrdered(None));
+
+    app
+}
+
+fn bind(app: &mut App, addr: &str, transport: Transport) {
+    let mut net = app.resources.get_mut::<NetworkResource>().unwrap();
+    net.bind_with_transport(addr, transport).unwrap();
+}
+
+fn count_messages(
+    mut reader: Local<EventReader<NetworkMessage<usize>>>,
+    events: Res<Events<NetworkMessage<usize>>>,
+    mut received: ResMut<Received>,
+) {
+    received.0 += reader.iter(&events).count();
+}
+
+fn report_cpu(name: &str, cpu_start: Option<Duration>, cpu_end: Option<Duration>, elapsed: Duration) {
+    match (cpu_start, cpu_end) {
+        (Some(cpu_start), Some(cpu_end)) => {
+            let cpu = cpu_end - cpu_start;
+            println!(
+                "{}: {:.3?} CPU over {:.3?}, {:.1}% of a core",
+                name,
+                cpu,
+                elapsed,
+                cpu.as_secs_f64() / elapsed.as_secs_f64() * 100.0
+            );
+        }
+        _ => println!("{}: CPU time is unavailable on this platform", name),
+    }
+}
+
+/// User + system CPU time of the whole process.
+fn cpu_time() -> Option<Duration> {
+    let stat = fs::read_to_string("/proc/self/stat").ok()?;
+
+    // The command name may contain spaces, so count fields from the closing parenthesis
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    let user: f64 = fields.get(11)?.parse().ok()?;
+    let system: f64 = fields.get(12)?.parse().ok()?;
+
+    Some(Duration::from_secs_f64((user + system) / CLOCK_TICKS_PER_SEC))
+}
+
+/// Ids of the threads of this process.
+fn threads() -> Vec<String> {
+    match fs::read_dir("/proc/self/task") {
+        Ok(tasks) => tasks.filter_map(|task| Some(task.ok()?.file_name().to_string_lossy().into_owned())).collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// CPU time of some threads of this process, in nanoseconds rather than the clock ticks of `cpu_time`, since an idle
+/// worker only runs for a few milliseconds.
+fn threads_cpu_time(threads: &[String]) -> Option<Duration> {
+    let mut total = Duration::default();
+
+    for thread in threads {
+        let schedstat = fs::read_to_string(format!("/proc/self/task/{}/schedstat", thread)).ok()?;
+        let nanos: u64 = schedstat.split_whitespace().next()?.parse().ok()?;
+        total += Duration::from_nanos(nanos);
+    }
+
+    Some(total)
+}
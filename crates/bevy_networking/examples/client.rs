@@ -41,7 +41,7 @@ fn send_messages(
         println!("---> {:?}", msg);
 
         let delivery = NetworkDelivery::ReliableSequenced(Some(1));
-        net.send(server, msg.as_bytes(), delivery).unwrap();
+        net.send_bytes(server, msg.as_bytes(), delivery).unwrap();
 
         state.message_timer.reset();
     }
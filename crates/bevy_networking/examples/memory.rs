@@ -0,0 +1,81 @@
+//! Runs a host & a client `App` in the same process, connected through a `MemoryNetwork` instead of UDP
+//! sockets. This is the setup to use for integration tests.
+use bevy::prelude::*;
+
+use std::time::{Duration, Instant};
+
+use bevy_networking::{
+    events::NetworkMessage, AddNetworkMessage, ChannelId, MemoryConfig, MemoryNetwork, NetworkDelivery,
+    NetworkResource, NetworkingPlugin, Transport,
+};
+
+const SERVER: &str = "127.0.0.1:12351";
+const CLIENT: &str = "127.0.0.1:12350";
+
+fn main() {
+    let network = MemoryNetwork::new();
+
+    let mut server = build_app();
+    server.add_system(echo_messages.system());
+
+    let mut client = build_app();
+    client.add_system(print_messages.system());
+
+    let mut server = server.app;
+    let mut client = client.app;
+
+    bind(&mut server, &network, SERVER);
+    bind(&mut client, &network, CLIENT);
+
+    {
+        let net = client.resources.get::<NetworkResource>().unwrap();
+        net.send(SERVER.parse().unwrap(), &"Hello!".to_string()).unwrap();
+    }
+
+    let start = Instant::now();
+
+    while start.elapsed() < Duration::from_secs(1) {
+        server.update();
+        client.update();
+
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+fn build_app() -> AppBuilder {
+    let mut app = App::build();
+    // Task pools for the schedule, there is no window or renderer to run
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(NetworkingPlugin)
+        .add_network_message::<String>(ChannelId(0), NetworkDelivery::ReliableOrdered(None));
+
+    app
+}
+
+fn bind(app: &mut App, network: &MemoryNetwork, addr: &str) {
+    let mut net = app.resources.get_mut::<NetworkResource>().unwrap();
+
+    let config = MemoryConfig {
+        latency: Duration::from_millis(50),
+        ..MemoryConfig::new(network)
+    };
+
+    net.bind_with_transport(addr, Transport::Memory(config)).unwrap();
+}
+
+fn echo_messages(
+    mut reader: Local<EventReader<NetworkMessage<String>>>,
+    events: Res<Events<NetworkMessage<String>>>,
+    net: Res<NetworkResource>,
+) {
+    for NetworkMessage { conn, message } in reader.iter(&events) {
+        println!("Server <--- {:?} from {}", message, conn);
+        net.send(conn.addr, &format!("Echo: {}", message)).unwrap();
+    }
+}
+
+fn print_messages(mut reader: Local<EventReader<NetworkMessage<String>>>, events: Res<Events<NetworkMessage<String>>>) {
+    for NetworkMessage { conn, message } in reader.iter(&events) {
+        println!("Client <--- {:?} from {}", message, conn);
+    }
+}
@@ -5,7 +5,8 @@ use std::net::SocketAddr;
 use std::time::Duration;
 
 use bevy_networking::{
-    NetworkDelivery, NetworkEvent, NetworkResource, NetworkingPlugin,
+    events::{ClientConnected, ClientDisconnected, MessageReceived, SendError},
+    NetworkDelivery, NetworkResource, NetworkingPlugin,
 };
 
 const SERVER: &str = "127.0.0.1:12351";
@@ -34,20 +35,34 @@ fn main() {
 
 #[derive(Default)]
 struct NetworkEventReader {
-    network_events: EventReader<NetworkEvent>,
+    messages: EventReader<MessageReceived>,
+    connected: EventReader<ClientConnected>,
+    disconnected: EventReader<ClientDisconnected>,
+    errors: EventReader<SendError>,
 }
 
-fn print_network_events(mut state: ResMut<NetworkEventReader>, events: Res<Events<NetworkEvent>>) {
-    for event in state.network_events.iter(&events) {
-        match event {
-            NetworkEvent::Message(conn, data) => {
-                let msg = String::from_utf8_lossy(&*data);
-                println!("<--- {:?} from {}", msg, conn);
-            }
-            NetworkEvent::Connected(conn) => println!("\tConnected: {}", conn),
-            NetworkEvent::Disconnected(conn) => println!("\tDisconnected: {}", conn),
-            NetworkEvent::SendError(err) => println!("\tSend Error: {}", err),
-        }
+fn print_network_events(
+    mut state: ResMut<NetworkEventReader>,
+    messages: Res<Events<MessageReceived>>,
+    connected: Res<Events<ClientConnected>>,
+    disconnected: Res<Events<ClientDisconnected>>,
+    errors: Res<Events<SendError>>,
+) {
+    for MessageReceived(conn, data) in state.messages.iter(&messages) {
+        let msg = String::from_utf8_lossy(&*data);
+        println!("<--- {:?} from {}", msg, conn);
+    }
+
+    for ClientConnected(conn) in state.connected.iter(&connected) {
+        println!("\tConnected: {}", conn);
+    }
+
+    for ClientDisconnected(conn) in state.disconnected.iter(&disconnected) {
+        println!("\tDisconnected: {}", conn);
+    }
+
+    for err in state.errors.iter(&errors) {
+        println!("\tSend Error: {}", err.error);
     }
 }
 
@@ -97,15 +112,15 @@ fn send_messages(
 
         println!("---> {:?}", msg);
         if ci.is_server() {
-            net.broadcast(msg.as_bytes(), NetworkDelivery::ReliableSequenced(Some(1)))
-                .unwrap()
+            net.broadcast_bytes(msg.as_bytes(), NetworkDelivery::ReliableSequenced(Some(1)))
+                .unwrap();
         } else {
-            net.send(
+            net.send_bytes(
                 server,
                 msg.as_bytes(),
                 NetworkDelivery::ReliableSequenced(Some(1)),
             )
-                .unwrap()
+                .unwrap();
         }
 
         state.message_timer.reset();
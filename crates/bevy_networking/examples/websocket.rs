@@ -0,0 +1,73 @@
+//! Runs a WebSocket echo server & a client talking to it in the same process. Point `SERVER` at a reverse proxy
+//! forwarding to the echo server to try the game's proxy setup.
+use bevy::prelude::*;
+
+use std::time::{Duration, Instant};
+
+use bevy_networking::{
+    events::NetworkMessage, AddNetworkMessage, ChannelId, NetworkDelivery, NetworkResource, NetworkingPlugin,
+    Transport, WebSocketConfig,
+};
+
+const SERVER: &str = "127.0.0.1:12351";
+const CLIENT: &str = "127.0.0.1:12350";
+
+fn main() {
+    let mut server = build_app();
+    server.add_system(echo_messages.system());
+
+    let mut client = build_app();
+    client.add_system(print_messages.system());
+
+    let mut server = server.app;
+    let mut client = client.app;
+
+    bind(&mut server, SERVER);
+    bind(&mut client, CLIENT);
+
+    {
+        let net = client.resources.get::<NetworkResource>().unwrap();
+        net.send(SERVER.parse().unwrap(), &"Hello!".to_string()).unwrap();
+    }
+
+    let start = Instant::now();
+
+    while start.elapsed() < Duration::from_secs(2) {
+        server.update();
+        client.update();
+
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+fn build_app() -> AppBuilder {
+    let mut app = App::build();
+    app.add_plugin(NetworkingPlugin)
+        .add_network_message::<String>(ChannelId(0), NetworkDelivery::ReliableOrdered(None));
+
+    app
+}
+
+fn bind(app: &mut App, addr: &str) {
+    let mut net = app.resources.get_mut::<NetworkResource>().unwrap();
+
+    net.bind_with_transport(addr, Transport::WebSocket(WebSocketConfig::default()))
+        .unwrap();
+}
+
+fn echo_messages(
+    mut reader: Local<EventReader<NetworkMessage<String>>>,
+    events: Res<Events<NetworkMessage<String>>>,
+    net: Res<NetworkResource>,
+) {
+    for NetworkMessage { conn, message } in reader.iter(&events) {
+        println!("Server <--- {:?} from {}", message, conn);
+        net.send(conn.addr, &format!("Echo: {}", message)).unwrap();
+    }
+}
+
+fn print_messages(mut reader: Local<EventReader<NetworkMessage<String>>>, events: Res<Events<NetworkMessage<String>>>) {
+    for NetworkMessage { conn, message } in reader.iter(&events) {
+        println!("Client <--- {:?} from {}", message, conn);
+    }
+}
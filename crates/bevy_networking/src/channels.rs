@@ -0,0 +1,76 @@
+use bevy::app::{AppBuilder, EventReader, Events};
+use bevy::ecs::prelude::*;
+use bevy::prelude::stage;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    error::NetworkError,
+    events::{MessageDecodeError, MessageReceived, NetworkMessage},
+    resources::NetworkResource,
+    types::{ChannelId, NetworkDelivery},
+};
+
+
+/// Types which can be sent over a typed channel.
+pub trait NetworkMessageType: Serialize + DeserializeOwned + Send + Sync + 'static {}
+impl<T> NetworkMessageType for T where T: Serialize + DeserializeOwned + Send + Sync + 'static {}
+
+
+/// An AppBuilder extension which binds a message type to a channel & delivery mode, and delivers
+/// received messages of that type as `NetworkMessage<T>` events.
+///
+/// The `NetworkingPlugin` must be added first.
+pub trait AddNetworkMessage {
+    fn add_network_message<T>(&mut self, channel: ChannelId, delivery: NetworkDelivery) -> &mut Self
+        where T: NetworkMessageType;
+}
+
+impl AddNetworkMessage for AppBuilder {
+    fn add_network_message<T>(&mut self, channel: ChannelId, delivery: NetworkDelivery) -> &mut Self
+        where T: NetworkMessageType
+    {
+        {
+            let mut net = self
+                .resources_mut()
+                .get_mut::<NetworkResource>()
+                .expect("The NetworkingPlugin must be added before registering network messages");
+
+            net.register_channel::<T>(channel, delivery);
+        }
+
+        self.add_event::<NetworkMessage<T>>()
+            .add_system_to_stage(stage::EVENT, decode_channel_messages::<T>.system())
+    }
+}
+
+
+pub(crate) fn encode<T: NetworkMessageType>(channel: ChannelId, message: &T) -> Result<Vec<u8>, NetworkError> {
+    let mut data = vec![channel.0];
+    bincode::serialize_into(&mut data, message)?;
+
+    Ok(data)
+}
+
+fn decode_channel_messages<T: NetworkMessageType>(
+    mut reader: Local<EventReader<MessageReceived>>,
+    events: Res<Events<MessageReceived>>,
+    net: Res<NetworkResource>,
+    mut message_events: ResMut<Events<NetworkMessage<T>>>,
+    mut error_events: ResMut<Events<MessageDecodeError>>,
+) {
+    let channel = match net.channel_for::<T>() {
+        Ok(config) => config.id,
+        Err(_) => return,
+    };
+
+    for MessageReceived(conn, data) in reader.iter(&events) {
+        if data.first() != Some(&channel.0) {
+            continue;
+        }
+
+        match bincode::deserialize::<T>(&data[1..]) {
+            Ok(message) => message_events.send(NetworkMessage { conn: *conn, message }),
+            Err(err) => error_events.send(MessageDecodeError(*conn, channel, err.into())),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crossbeam_channel::SendError;
+use crossbeam_channel::{SendError, SendTimeoutError, TrySendError};
 use laminar::ErrorKind as LaminarError;
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
@@ -13,6 +13,10 @@ use super::types::{SocketHandle, WorkerInstructions, MessageWithDestination};
 pub enum NetworkError {
     NoSocket(SocketHandle),
     NoDefaultSocket,
+    UnregisteredMessage(&'static str),
+    SerializationError(bincode::Error),
+    AuthenticationError(&'static str),
+    SendQueueFull,
     InternalError(InternalErrorKind),
     IOError(io::Error),
 }
@@ -43,6 +47,18 @@ impl<T> From<PoisonError<MutexGuard<'_, T>>> for NetworkError {
     }
 }
 
+impl From<io::Error> for NetworkError {
+    fn from(err: io::Error) -> Self {
+        IOError(err)
+    }
+}
+
+impl From<bincode::Error> for NetworkError {
+    fn from(err: bincode::Error) -> Self {
+        SerializationError(err)
+    }
+}
+
 impl From<SendError<WorkerInstructions>> for NetworkError {
     fn from(err: SendError<WorkerInstructions>) -> Self {
         InternalError(InternalErrorKind::SendWorkerInstructionsError(
@@ -57,6 +73,24 @@ impl From<SendError<MessageWithDestination>> for NetworkError {
     }
 }
 
+impl From<TrySendError<MessageWithDestination>> for NetworkError {
+    fn from(err: TrySendError<MessageWithDestination>) -> Self {
+        match err {
+            TrySendError::Full(_) => SendQueueFull,
+            TrySendError::Disconnected(_) => InternalError(InternalErrorKind::SendMessageError(err.to_string())),
+        }
+    }
+}
+
+impl From<SendTimeoutError<MessageWithDestination>> for NetworkError {
+    fn from(err: SendTimeoutError<MessageWithDestination>) -> Self {
+        match err {
+            SendTimeoutError::Timeout(_) => SendQueueFull,
+            SendTimeoutError::Disconnected(_) => InternalError(InternalErrorKind::SendMessageError(err.to_string())),
+        }
+    }
+}
+
 impl Display for NetworkError {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> fmt::Result {
         match self {
@@ -66,6 +100,14 @@ impl Display for NetworkError {
                 handle
             ),
             NoDefaultSocket => write!(fmt, "No default socket is bound."),
+            UnregisteredMessage(type_name) => write!(
+                fmt,
+                "No channel is registered for the message type {}",
+                type_name
+            ),
+            SerializationError(e) => write!(fmt, "A message could not be (de)serialized: {}", e),
+            AuthenticationError(reason) => write!(fmt, "A session could not be authenticated: {}", reason),
+            SendQueueFull => write!(fmt, "The send queue is full, the network worker is falling behind."),
             IOError(e) => write!(fmt, "An IO error occurred: {}", e),
             InternalError(e) => write!(fmt, "An internal error occurred: {}", e),
         }
@@ -1,5 +1,11 @@
 use bytes::Bytes;
-use super::{error::NetworkError, types::Connection};
+use std::net::SocketAddr;
+
+use super::{
+    error::NetworkError,
+    stats::ConnectionStats,
+    types::{ChannelId, Connection, MessageId, MessageWithDestination, SocketHandle, Violation, ViolationPolicy},
+};
 
 
 #[derive(Debug)]
@@ -11,8 +17,83 @@ pub struct ClientDisconnected(pub Connection);
 #[derive(Debug)]
 pub struct MessageReceived(pub Connection, pub Bytes);
 
+/// Something went wrong in the worker, usually while sending.
+#[derive(Debug)]
+pub struct SendError {
+    /// Messages lost because of the error, as returned by `NetworkResource::send`. Empty when the error can't be
+    /// traced to a message, e.g. when accepting a connection failed.
+    pub messages: Vec<MessageId>,
+    /// The peer the lost messages were for, if known.
+    pub addr: Option<SocketAddr>,
+    pub error: NetworkError,
+}
+
+impl SendError {
+    pub(crate) fn new(error: NetworkError) -> Self {
+        SendError {
+            messages: vec![],
+            addr: None,
+            error,
+        }
+    }
+
+    pub(crate) fn for_addr(addr: SocketAddr, error: NetworkError) -> Self {
+        SendError {
+            messages: vec![],
+            addr: Some(addr),
+            error,
+        }
+    }
+
+    pub(crate) fn for_message(message: &MessageWithDestination, error: NetworkError) -> Self {
+        Self::for_messages(message.destination, std::iter::once(message), error)
+    }
+
+    /// For errors losing every message queued for a peer, like a failed connection attempt.
+    pub(crate) fn for_messages<'a>(
+        addr: SocketAddr,
+        messages: impl IntoIterator<Item = &'a MessageWithDestination>,
+        error: NetworkError,
+    ) -> Self {
+        SendError {
+            messages: messages.into_iter().filter_map(|message| message.id).collect(),
+            addr: Some(addr),
+            error,
+        }
+    }
+}
+
+/// Published for every connection about once a second.
+#[derive(Debug)]
+pub struct ConnectionStatsUpdated(pub Connection, pub ConnectionStats);
+
+/// A packet from the connection was not sealed with the session key, e.g. because the peer used another room
+/// password. The packet is dropped, & when it ends the handshake a `SendError` reports the messages queued for it.
+#[derive(Debug)]
+pub struct AuthenticationFailed(pub Connection);
+
+/// A peer broke the `RateLimitConfig` of the socket it sent to & `action` was taken. Reported on the first violation
+/// & then at most once a second while the peer keeps at it.
+#[derive(Debug)]
+pub struct RateLimitViolated {
+    pub conn: Connection,
+    /// The latest violation.
+    pub violation: Violation,
+    /// Packets dropped since the last report, including the ones that triggered this one.
+    pub dropped: u32,
+    pub action: ViolationPolicy,
+}
+
+/// A packet arrived on a registered channel but could not be decoded into the channel's message type.
+#[derive(Debug)]
+pub struct MessageDecodeError(pub Connection, pub ChannelId, pub NetworkError);
+
+/// A typed message received on the channel registered for `T`.
 #[derive(Debug)]
-pub struct SendError(pub NetworkError);
+pub struct NetworkMessage<T> {
+    pub conn: Connection,
+    pub message: T,
+}
 
 
 #[derive(Debug)]
@@ -20,5 +101,9 @@ pub enum NetworkEvent {
     Connected(Connection),
     Disconnected(Connection),
     Message(Connection, Bytes),
-    SendError(NetworkError),
-}
\ No newline at end of file
+    SendError(SendError),
+    SocketClosed(SocketHandle),
+    Stats(Connection, ConnectionStats),
+    AuthenticationFailed(Connection),
+    RateLimitViolated(RateLimitViolated),
+}
@@ -3,29 +3,44 @@ use bevy::ecs::prelude::*;
 use bevy::prelude::stage;
 
 mod types;
+mod channels;
 mod error;
 mod resources;
+mod stats;
 pub mod events;
+mod transport;
 mod worker;
 
 
 use events::NetworkEvent;
 pub use error::NetworkError;
-pub use types::{Connection, SendConfig, SocketHandle, NetworkDelivery};
+pub use types::{
+    BackpressurePolicy, ChannelId, Connection, EncryptionConfig, LaminarConfig, LinkConditionerConfig, MemoryConfig,
+    MemoryOrdering, MessageId, NetworkDelivery, RateLimitConfig, SendConfig, SocketHandle, TcpConfig, Transport,
+    Violation, ViolationPolicy, WebSocketConfig, WorkerConfig,
+};
+pub use transport::MemoryNetwork;
+pub use channels::{AddNetworkMessage, NetworkMessageType};
 pub use resources::NetworkResource;
+pub use stats::ConnectionStats;
 pub use bytes::Bytes;
 
 
 pub struct NetworkingPlugin;
 impl Plugin for NetworkingPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        let network_resource = worker::start_worker_thread();
+        let config = app.resources().get::<WorkerConfig>().map(|config| (*config).clone()).unwrap_or_default();
+        let network_resource = worker::start_worker_thread(config);
 
         app
             .add_event::<events::ClientConnected>()
             .add_event::<events::ClientDisconnected>()
             .add_event::<events::MessageReceived>()
             .add_event::<events::SendError>()
+            .add_event::<events::MessageDecodeError>()
+            .add_event::<events::ConnectionStatsUpdated>()
+            .add_event::<events::AuthenticationFailed>()
+            .add_event::<events::RateLimitViolated>()
             .add_resource(network_resource)
             .add_system_to_stage(stage::EVENT, process_network_events.system());
     }
@@ -39,9 +54,13 @@ fn process_network_events(
     mut disconnected_events: ResMut<Events<events::ClientDisconnected>>,
     mut message_events: ResMut<Events<events::MessageReceived>>,
     mut error_events: ResMut<Events<events::SendError>>,
+    mut stats_events: ResMut<Events<events::ConnectionStatsUpdated>>,
+    mut auth_events: ResMut<Events<events::AuthenticationFailed>>,
+    mut violation_events: ResMut<Events<events::RateLimitViolated>>,
 ) {
     let mut added_connections: Vec<Connection> = Vec::new();
     let mut removed_connections: Vec<Connection> = Vec::new();
+    let mut updated_stats: Vec<(Connection, ConnectionStats)> = Vec::new();
 
     {
         let locked = match net.event_rx.lock() {
@@ -56,6 +75,11 @@ fn process_network_events(
         while let Ok(event) = locked.try_recv() {
             match event {
                 NetworkEvent::Connected(conn) => {
+                    // Ignore connections reported by a socket closed since the event was queued
+                    if !net.bound_sockets.contains(&conn.socket) {
+                        continue;
+                    }
+
                     if !net.has_connection(conn) && !added_connections.contains(&conn) {
                         added_connections.push(conn);
                     }
@@ -69,7 +93,25 @@ fn process_network_events(
                     message_events.send(events::MessageReceived(conn, bytes));
                 }
                 NetworkEvent::SendError(error) => {
-                    error_events.send(events::SendError(error));
+                    error_events.send(error);
+                }
+                NetworkEvent::Stats(conn, stats) => {
+                    updated_stats.push((conn, stats));
+                }
+                NetworkEvent::AuthenticationFailed(conn) => {
+                    auth_events.send(events::AuthenticationFailed(conn));
+                }
+                NetworkEvent::RateLimitViolated(violation) => {
+                    violation_events.send(violation);
+                }
+                NetworkEvent::SocketClosed(handle) => {
+                    added_connections.retain(|conn| conn.socket != handle);
+
+                    for conn in net.connections_for_socket(handle) {
+                        if !removed_connections.contains(&conn) {
+                            removed_connections.push(conn);
+                        }
+                    }
                 }
             }
         }
@@ -84,4 +126,11 @@ fn process_network_events(
         net.remove_connection(conn);
         disconnected_events.send(events::ClientDisconnected(conn));
     }
+
+    for (conn, stats) in updated_stats {
+        if net.has_connection(conn) {
+            net.stats.insert(conn, stats.clone());
+            stats_events.send(events::ConnectionStatsUpdated(conn, stats));
+        }
+    }
 }
\ No newline at end of file
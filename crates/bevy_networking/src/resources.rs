@@ -1,15 +1,32 @@
 use bytes::Bytes;
 use crossbeam_channel::{Receiver, Sender};
 use laminar::Socket;
+use log::warn;
 use std::{
+    any::{type_name, TypeId},
+    collections::HashMap,
+    io,
     net::{SocketAddr, ToSocketAddrs},
-    sync::Mutex
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
 };
 
 use super::{
+    channels::{encode, NetworkMessageType},
     error::NetworkError,
     events::NetworkEvent,
-    types::{Connection, SendConfig, SocketHandle, MessageWithDestination, WorkerInstructions, NetworkDelivery, LaminarConfig, Transport},
+    stats::ConnectionStats,
+    transport::{
+        ConditionedSocket, LaminarSocket, RateLimitedSocket, SecureSocket, TcpSocket, WebSocketSocket, WorkerSocket,
+    },
+    worker::Waker,
+    types::{
+        BackpressurePolicy, ChannelConfig, ChannelId, Connection, EncryptionConfig, LaminarConfig,
+        LinkConditionerConfig, MemoryConfig, MessageId, MessageWithDestination, NetworkDelivery, RateLimitConfig,
+        SendConfig, SocketHandle, TcpConfig, Transport, WebSocketConfig, WorkerInstructions,
+    },
 };
 
 
@@ -18,6 +35,11 @@ pub struct NetworkResource {
 
     pub(crate) bound_sockets: Vec<SocketHandle>,
     pub(crate) connections: Vec<Connection>,
+    pub(crate) stats: HashMap<Connection, ConnectionStats>,
+    pub(crate) channels: HashMap<TypeId, ChannelConfig>,
+    pub(crate) next_message_id: AtomicU64,
+    pub(crate) backpressure: BackpressurePolicy,
+    pub(crate) waker: Waker,
     pub(crate) event_rx: Mutex<Receiver<NetworkEvent>>,
     pub(crate) message_tx: Mutex<Sender<MessageWithDestination>>,
     pub(crate) instruction_tx: Mutex<Sender<WorkerInstructions>>,
@@ -36,9 +58,14 @@ impl NetworkResource {
             .collect()
     }
 
+    /// The latest stats measured for a connection. Connections are measured about once a second.
+    pub fn stats(&self, connection: Connection) -> Option<&ConnectionStats> {
+        self.stats.get(&connection)
+    }
+
     pub fn add_connection(&mut self, connection: Connection) {
         if self.has_connection(connection) {
-            println!("Warning: attempted to add a connection that already exists");
+            warn!("Attempted to add a connection that already exists");
             return;
         }
 
@@ -51,9 +78,10 @@ impl NetworkResource {
         match conn {
             Some(idx) => {
                 self.connections.remove(idx);
+                self.stats.remove(&connection);
             }
             None => {
-                println!("Warning: attempted to remove connection that doesn't exist");
+                warn!("Attempted to remove a connection that doesn't exist");
             }
         }
     }
@@ -73,6 +101,9 @@ impl NetworkResource {
     ) -> Result<SocketHandle, NetworkError> {
         match transport {
             Transport::Laminar(config) => self.bind_with_laminar(addr, config),
+            Transport::Memory(config) => self.bind_with_memory(addr, config),
+            Transport::Tcp(config) => self.bind_with_tcp(addr, config),
+            Transport::WebSocket(config) => self.bind_with_websocket(addr, config),
         }
     }
 
@@ -81,17 +112,90 @@ impl NetworkResource {
         addr: A,
         config: LaminarConfig,
     ) -> Result<SocketHandle, NetworkError> {
+        let link_conditioner = config.link_conditioner.clone();
+        let encryption = config.encryption.clone();
+        let rate_limit = config.rate_limit.clone();
         let cfg = config.into();
 
-        let handle = SocketHandle::new();
         let socket = Socket::bind_with_config(addr, cfg)?;
 
+        self.add_socket(Box::new(LaminarSocket::new(socket)), link_conditioner, encryption, rate_limit)
+    }
+
+    fn bind_with_memory<A: ToSocketAddrs>(
+        &mut self,
+        addr: A,
+        config: MemoryConfig,
+    ) -> Result<SocketHandle, NetworkError> {
+        let addr = first_addr(addr)?;
+
+        let socket = config.network.bind(addr, config.clone(), self.waker.clone())?;
+
+        self.add_socket(Box::new(socket), config.link_conditioner, config.encryption, config.rate_limit)
+    }
+
+    fn bind_with_tcp<A: ToSocketAddrs>(
+        &mut self,
+        addr: A,
+        config: TcpConfig,
+    ) -> Result<SocketHandle, NetworkError> {
+        let addr = first_addr(addr)?;
+        let link_conditioner = config.link_conditioner.clone();
+        let encryption = config.encryption.clone();
+        let rate_limit = config.rate_limit.clone();
+        let socket = TcpSocket::bind(addr, config, self.waker.clone())?;
+
+        self.add_socket(Box::new(socket), link_conditioner, encryption, rate_limit)
+    }
+
+    fn bind_with_websocket<A: ToSocketAddrs>(
+        &mut self,
+        addr: A,
+        config: WebSocketConfig,
+    ) -> Result<SocketHandle, NetworkError> {
+        let addr = first_addr(addr)?;
+        let link_conditioner = config.link_conditioner.clone();
+        let encryption = config.encryption.clone();
+        let rate_limit = config.rate_limit.clone();
+        let socket = WebSocketSocket::bind(addr, config, self.waker.clone())?;
+
+        self.add_socket(Box::new(socket), link_conditioner, encryption, rate_limit)
+    }
+
+    fn add_socket(
+        &mut self,
+        socket: Box<dyn WorkerSocket>,
+        link_conditioner: Option<LinkConditionerConfig>,
+        encryption: Option<EncryptionConfig>,
+        rate_limit: Option<RateLimitConfig>,
+    ) -> Result<SocketHandle, NetworkError> {
+        let handle = SocketHandle::new();
+
+        // Limit right above the transport, so floods are dropped before any decryption work is spent on them
+        let socket: Box<dyn WorkerSocket> = match rate_limit {
+            Some(config) => Box::new(RateLimitedSocket::new(socket, config)),
+            None => socket,
+        };
+
+        let socket: Box<dyn WorkerSocket> = match link_conditioner {
+            Some(config) => Box::new(ConditionedSocket::new(socket, config)),
+            None => socket,
+        };
+
+        // Encrypt above the conditioner, so simulated loss & reordering hit sealed packets like a real link would
+        let socket: Box<dyn WorkerSocket> = match encryption {
+            Some(config) => Box::new(SecureSocket::new(socket, config)),
+            None => socket,
+        };
+
         let instruction = WorkerInstructions::AddSocket(handle, socket);
         {
             let locked = self.instruction_tx.lock()?;
             locked.send(instruction)?;
         }
 
+        self.waker.wake();
+
         self.bound_sockets.push(handle);
 
         if self.default_socket.is_none() {
@@ -101,64 +205,160 @@ impl NetworkResource {
         Ok(handle)
     }
 
-    pub fn send(
+    /// Closes a bound socket & unbinds its address. A `ClientDisconnected` event is sent for each of the
+    /// socket's connections once the worker has closed it.
+    ///
+    /// If the socket was the default socket, the oldest remaining socket becomes the default.
+    pub fn close(&mut self, handle: SocketHandle) -> Result<(), NetworkError> {
+        let idx = self
+            .bound_sockets
+            .iter()
+            .position(|h| *h == handle)
+            .ok_or(NetworkError::NoSocket(handle))?;
+
+        {
+            let locked = self.instruction_tx.lock()?;
+            locked.send(WorkerInstructions::CloseSocket(handle))?;
+        }
+
+        self.waker.wake();
+
+        self.bound_sockets.remove(idx);
+
+        if self.default_socket == Some(handle) {
+            self.default_socket = self.bound_sockets.first().copied();
+        }
+
+        Ok(())
+    }
+
+    pub fn default_socket(&self) -> Option<SocketHandle> {
+        self.default_socket
+    }
+
+    pub fn bound_sockets(&self) -> &Vec<SocketHandle> {
+        &self.bound_sockets
+    }
+
+    /// Sends a typed message on the channel registered for `T` with `AddNetworkMessage`.
+    ///
+    /// Errors found while queueing the message are returned. Errors found later by the worker, like a peer refusing
+    /// the connection, are published as `SendError` events listing the returned `MessageId`.
+    pub fn send<T: NetworkMessageType>(&self, addr: SocketAddr, message: &T) -> Result<MessageId, NetworkError> {
+        let channel = self.channel_for::<T>()?;
+        let data = encode(channel.id, message)?;
+
+        self.send_bytes(addr, &data, channel.delivery)
+    }
+
+    /// Sends a typed message to every connection of the default socket. Every copy shares the returned id.
+    pub fn broadcast<T: NetworkMessageType>(&self, message: &T) -> Result<MessageId, NetworkError> {
+        let channel = self.channel_for::<T>()?;
+        let data = encode(channel.id, message)?;
+
+        self.broadcast_bytes(&data, channel.delivery)
+    }
+
+    pub fn send_bytes(
         &self,
         addr: SocketAddr,
         message: &[u8],
         delivery: NetworkDelivery,
-    ) -> Result<(), NetworkError> {
-        self.send_with_config(addr, message, delivery, SendConfig::default())
+    ) -> Result<MessageId, NetworkError> {
+        self.send_bytes_with_config(addr, message, delivery, SendConfig::default())
     }
 
-    pub fn broadcast(&self, message: &[u8], delivery: NetworkDelivery) -> Result<(), NetworkError> {
-        self.broadcast_with_config(message, delivery, SendConfig::default())
+    pub fn broadcast_bytes(&self, message: &[u8], delivery: NetworkDelivery) -> Result<MessageId, NetworkError> {
+        self.broadcast_bytes_with_config(message, delivery, SendConfig::default())
     }
 
-    pub fn send_with_config(
+    pub fn send_bytes_with_config(
         &self,
         addr: SocketAddr,
         message: &[u8],
         delivery: NetworkDelivery,
         config: SendConfig,
-    ) -> Result<(), NetworkError> {
+    ) -> Result<MessageId, NetworkError> {
         let socket = self.get_socket_or_default(config.socket)?;
+        let id = self.next_message_id();
 
         let msg = MessageWithDestination {
+            id: Some(id),
             destination: addr,
             delivery,
             socket_handle: socket,
             message: Bytes::copy_from_slice(message),
         };
 
-        self.message_tx.lock()?.send(msg)?;
+        self.queue_message(msg)?;
 
-        Ok(())
+        Ok(id)
     }
 
-    pub fn broadcast_with_config(
+    pub fn broadcast_bytes_with_config(
         &self,
         message: &[u8],
         delivery: NetworkDelivery,
         config: SendConfig,
-    ) -> Result<(), NetworkError> {
+    ) -> Result<MessageId, NetworkError> {
         let socket = self.get_socket_or_default(config.socket)?;
+        let id = self.next_message_id();
 
         let broadcast_to = self.connections_for_socket(socket);
 
         for conn in broadcast_to {
             let msg = MessageWithDestination {
+                id: Some(id),
                 destination: conn.addr,
                 delivery,
                 socket_handle: socket,
                 message: Bytes::copy_from_slice(message),
             };
 
-            self.message_tx.lock()?.send(msg)?;
+            self.queue_message(msg)?;
+        }
+
+        Ok(id)
+    }
+
+    pub(crate) fn register_channel<T: NetworkMessageType>(&mut self, channel: ChannelId, delivery: NetworkDelivery) {
+        if let Some(existing) = self.channels.values().find(|config| config.id == channel) {
+            panic!("{:?} is already registered for {}", channel, existing.type_name);
+        }
+
+        let config = ChannelConfig {
+            id: channel,
+            delivery,
+            type_name: type_name::<T>(),
+        };
+
+        self.channels.insert(TypeId::of::<T>(), config);
+    }
+
+    pub(crate) fn channel_for<T: NetworkMessageType>(&self) -> Result<ChannelConfig, NetworkError> {
+        self.channels
+            .get(&TypeId::of::<T>())
+            .cloned()
+            .ok_or(NetworkError::UnregisteredMessage(type_name::<T>()))
+    }
+
+    /// Hands a message to the worker, applying the `BackpressurePolicy` while its queue is full.
+    fn queue_message(&self, message: MessageWithDestination) -> Result<(), NetworkError> {
+        let message_tx = self.message_tx.lock()?;
+
+        match self.backpressure {
+            BackpressurePolicy::Block(timeout) => message_tx.send_timeout(message, timeout)?,
+            BackpressurePolicy::Reject => message_tx.try_send(message)?,
         }
 
+        self.waker.wake();
         Ok(())
     }
 
+    fn next_message_id(&self) -> MessageId {
+        MessageId(self.next_message_id.fetch_add(1, Ordering::Relaxed))
+    }
+
     fn get_socket_or_default(
         &self,
         socket: Option<SocketHandle>,
@@ -176,8 +376,20 @@ impl NetworkResource {
 
 impl Drop for NetworkResource {
     fn drop(&mut self) {
-        let locked = self.instruction_tx.lock().unwrap();
-        locked.send(WorkerInstructions::Terminate).unwrap();
+        // The worker may have stopped already, in which case there is nothing left to terminate
+        if let Ok(locked) = self.instruction_tx.lock() {
+            let _ = locked.send(WorkerInstructions::Terminate);
+        }
+
+        self.waker.wake();
     }
 }
 
+fn first_addr<A: ToSocketAddrs>(addr: A) -> Result<SocketAddr, NetworkError> {
+    let addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "No address to bind to"))?;
+
+    Ok(addr)
+}
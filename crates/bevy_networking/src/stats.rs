@@ -0,0 +1,388 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use log::warn;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use super::{
+    events::NetworkEvent,
+    types::{Connection, MessageWithDestination, NetworkDelivery},
+};
+
+/// Every payload the worker sends starts with one of these, so pings never reach the app.
+const PAYLOAD_DATA: u8 = 0;
+const PAYLOAD_PING: u8 = 1;
+const PAYLOAD_PONG: u8 = 2;
+
+/// How often each connection is pinged & its stats are published.
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Pings unanswered for this long are counted as lost.
+const PING_TIMEOUT: Duration = Duration::from_secs(3);
+
+
+/// Link quality of a single connection, measured by the worker.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionStats {
+    /// Smoothed round trip time, `None` until the first ping is answered.
+    pub rtt: Option<Duration>,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+    /// Estimated from pings, which are sent unreliably & so are lost like any other packet.
+    pub packets_lost: u64,
+    /// Share of the pings answered or timed out so far which were lost, between 0 and 1.
+    pub packet_loss: f32,
+    pub bytes_sent_per_sec: f32,
+    pub bytes_received_per_sec: f32,
+}
+
+#[derive(Default)]
+struct ConnectionTracker {
+    stats: ConnectionStats,
+    next_ping: u32,
+    pings: VecDeque<(u32, Instant)>,
+    pings_answered: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+/// Worker side bookkeeping for `ConnectionStats`.
+pub(crate) struct StatsTracker {
+    connections: HashMap<Connection, ConnectionTracker>,
+    last_update: Instant,
+}
+
+impl StatsTracker {
+    pub(crate) fn new() -> Self {
+        StatsTracker {
+            connections: HashMap::new(),
+            last_update: Instant::now(),
+        }
+    }
+
+    /// Prefixes an outgoing app message & counts it.
+    pub(crate) fn on_send(&mut self, mut message: MessageWithDestination) -> MessageWithDestination {
+        message.message = frame(PAYLOAD_DATA, &message.message);
+        self.count_sent(&message);
+
+        message
+    }
+
+    /// Counts & unwraps an incoming event. Returns the event to pass on to the app, if any, and a pong to send
+    /// back when the event was a ping.
+    pub(crate) fn on_event(
+        &mut self,
+        event: NetworkEvent,
+        now: Instant,
+    ) -> (Option<NetworkEvent>, Option<MessageWithDestination>) {
+        let (conn, data) = match event {
+            NetworkEvent::Message(conn, data) => (conn, data),
+            NetworkEvent::Connected(conn) => {
+                self.connections.entry(conn).or_default();
+                return (Some(event), None);
+            }
+            NetworkEvent::Disconnected(conn) => {
+                self.connections.remove(&conn);
+                return (Some(event), None);
+            }
+            NetworkEvent::SocketClosed(handle) => {
+                self.connections.retain(|conn, _| conn.socket != handle);
+                return (Some(event), None);
+            }
+            event => return (Some(event), None),
+        };
+
+        if let Some(tracker) = self.connections.get_mut(&conn) {
+            tracker.stats.packets_received += 1;
+            tracker.bytes_received += data.len() as u64;
+        }
+
+        match data.first() {
+            Some(&PAYLOAD_DATA) => (Some(NetworkEvent::Message(conn, data.slice(1..))), None),
+            Some(&PAYLOAD_PING) => {
+                let pong = control_message(conn, frame(PAYLOAD_PONG, &data[1..]));
+                self.count_sent(&pong);
+
+                (None, Some(pong))
+            }
+            Some(&PAYLOAD_PONG) => {
+                if let Some(tracker) = self.connections.get_mut(&conn) {
+                    tracker.on_pong(&data[1..], now);
+                }
+
+                (None, None)
+            }
+            _ => {
+                warn!("Dropping malformed packet from {}", conn);
+                (None, None)
+            }
+        }
+    }
+
+    pub(crate) fn next_update(&self) -> Instant {
+        self.last_update + STATS_INTERVAL
+    }
+
+    /// Every `STATS_INTERVAL` pings every connection & returns the pings to send along with the updated stats.
+    pub(crate) fn update(&mut self, now: Instant) -> (Vec<MessageWithDestination>, Vec<NetworkEvent>) {
+        let elapsed = now.duration_since(self.last_update);
+
+        if elapsed < STATS_INTERVAL {
+            return (vec![], vec![]);
+        }
+
+        self.last_update = now;
+
+        let mut pings = Vec::new();
+        let mut events = Vec::new();
+
+        for (conn, tracker) in self.connections.iter_mut() {
+            tracker.expire_pings(now);
+
+            let seconds = elapsed.as_secs_f32();
+            tracker.stats.bytes_sent_per_sec = tracker.bytes_sent as f32 / seconds;
+            tracker.stats.bytes_received_per_sec = tracker.bytes_received as f32 / seconds;
+            tracker.bytes_sent = 0;
+            tracker.bytes_received = 0;
+
+            events.push(NetworkEvent::Stats(*conn, tracker.stats.clone()));
+
+            let id = tracker.next_ping;
+            tracker.next_ping = tracker.next_ping.wrapping_add(1);
+            tracker.pings.push_back((id, now));
+
+            pings.push(control_message(*conn, frame(PAYLOAD_PING, &id.to_be_bytes())));
+        }
+
+        for ping in pings.iter() {
+            self.count_sent(ping);
+        }
+
+        (pings, events)
+    }
+
+    fn count_sent(&mut self, message: &MessageWithDestination) {
+        let conn = Connection {
+            addr: message.destination,
+            socket: message.socket_handle,
+        };
+
+        if let Some(tracker) = self.connections.get_mut(&conn) {
+            tracker.stats.packets_sent += 1;
+            tracker.bytes_sent += message.message.len() as u64;
+        }
+    }
+}
+
+impl ConnectionTracker {
+    fn on_pong(&mut self, data: &[u8], now: Instant) {
+        if data.len() != 4 {
+            return;
+        }
+
+        let mut id = [0u8; 4];
+        id.copy_from_slice(data);
+        let id = u32::from_be_bytes(id);
+
+        let sent = match self.pings.iter().position(|(ping, _)| *ping == id) {
+            Some(idx) => self.pings.remove(idx).map(|(_, sent)| sent),
+            None => return,
+        };
+
+        if let Some(sent) = sent {
+            let sample = now.duration_since(sent);
+            self.pings_answered += 1;
+
+            // Smooth like TCP does, so a single slow packet doesn't make the ping jump around
+            self.stats.rtt = Some(match self.stats.rtt {
+                Some(rtt) => (rtt * 7 + sample) / 8,
+                None => sample,
+            });
+        }
+    }
+
+    fn expire_pings(&mut self, now: Instant) {
+        while let Some(&(_, sent)) = self.pings.front() {
+            if now.duration_since(sent) < PING_TIMEOUT {
+                break;
+            }
+
+            self.pings.pop_front();
+            self.stats.packets_lost += 1;
+        }
+
+        // Pings still in flight are neither, counting them would understate the loss
+        let settled = self.pings_answered + self.stats.packets_lost;
+
+        if settled > 0 {
+            self.stats.packet_loss = self.stats.packets_lost as f32 / settled as f32;
+        }
+    }
+}
+
+
+fn frame(kind: u8, payload: &[u8]) -> Bytes {
+    let mut data = BytesMut::with_capacity(payload.len() + 1);
+    data.put_u8(kind);
+    data.put_slice(payload);
+
+    data.freeze()
+}
+
+fn control_message(conn: Connection, message: Bytes) -> MessageWithDestination {
+    MessageWithDestination {
+        id: None,
+        message,
+        delivery: NetworkDelivery::UnreliableUnordered,
+        socket_handle: conn.socket,
+        destination: conn.addr,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SocketHandle;
+    use std::net::SocketAddr;
+
+    #[test]
+    fn smooths_the_rtt_of_answered_pings() {
+        let (mut tracker, _) = connected();
+        let start = tracker.next_update() - STATS_INTERVAL;
+
+        let (pings, _) = tracker.update(start + secs(1));
+        tracker.on_event(pong(&pings[0]), start + secs(1) + millis(100));
+
+        let (pings, events) = tracker.update(start + secs(2));
+        assert_eq!(stats(&events).rtt, Some(millis(100)));
+
+        // A single slow answer only moves it an eighth of the way
+        tracker.on_event(pong(&pings[0]), start + secs(2) + millis(900));
+
+        let (_, events) = tracker.update(start + secs(3));
+        assert_eq!(stats(&events).rtt, Some(millis(200)));
+    }
+
+    #[test]
+    fn counts_expired_pings_as_lost() {
+        let (mut tracker, conn) = connected();
+        let start = tracker.next_update() - STATS_INTERVAL;
+
+        // The first ping is never answered, the second is & the third is still in flight
+        tracker.update(start + secs(1));
+        let (pings, _) = tracker.update(start + secs(2));
+        tracker.on_event(pong(&pings[0]), start + secs(2) + millis(50));
+        let (_, events) = tracker.update(start + secs(3));
+
+        assert_eq!(stats(&events).packets_lost, 0);
+
+        let (_, events) = tracker.update(start + secs(4));
+        let last = stats(&events);
+
+        assert_eq!(last.packets_lost, 1);
+        assert_eq!(last.packet_loss, 0.5);
+        assert_eq!(last.rtt, Some(millis(50)));
+        assert_eq!(tracker.connections[&conn].pings.len(), 2);
+    }
+
+    #[test]
+    fn measures_bytes_per_sec_over_the_interval() {
+        let (mut tracker, conn) = connected();
+        let start = tracker.next_update() - STATS_INTERVAL;
+
+        // Framed, each message is a byte longer
+        tracker.on_send(message(conn, &[0; 99]));
+        tracker.on_send(message(conn, &[0; 99]));
+        tracker.on_event(NetworkEvent::Message(conn, frame(PAYLOAD_DATA, &[0; 49])), start);
+
+        let (_, events) = tracker.update(start + secs(2));
+        let first = stats(&events);
+
+        assert_eq!(first.bytes_sent_per_sec, 100.0);
+        assert_eq!(first.bytes_received_per_sec, 25.0);
+        assert_eq!((first.packets_sent, first.packets_received), (2, 1));
+
+        // The counts start over, the ping sent with the last stats being the only traffic since
+        let (_, events) = tracker.update(start + secs(3));
+        assert_eq!(stats(&events).bytes_sent_per_sec, 5.0);
+    }
+
+    #[test]
+    fn strips_the_payload_kind_from_app_messages() {
+        let (mut tracker, conn) = connected();
+
+        let sent = tracker.on_send(message(conn, b"Hello"));
+        assert_eq!(sent.message, Bytes::from_static(b"\x00Hello"));
+
+        let (event, pong) = tracker.on_event(NetworkEvent::Message(conn, sent.message), Instant::now());
+
+        match event {
+            Some(NetworkEvent::Message(from, data)) => assert_eq!((from, data), (conn, Bytes::from_static(b"Hello"))),
+            event => panic!("Expected the message, got {:?}", event),
+        }
+        assert!(pong.is_none());
+    }
+
+    #[test]
+    fn answers_pings_without_telling_the_app() {
+        let (mut tracker, conn) = connected();
+        let ping = NetworkEvent::Message(conn, frame(PAYLOAD_PING, &7u32.to_be_bytes()));
+
+        let (event, pong) = tracker.on_event(ping, Instant::now());
+
+        assert!(event.is_none());
+        assert_eq!(pong.unwrap().message, frame(PAYLOAD_PONG, &7u32.to_be_bytes()));
+    }
+
+
+    // ==========================================================================
+    // --- Helpers
+    // ==========================================================================
+    fn connected() -> (StatsTracker, Connection) {
+        let conn = Connection {
+            addr: SocketAddr::from(([127, 0, 0, 1], 1)),
+            socket: SocketHandle::new(),
+        };
+
+        let mut tracker = StatsTracker::new();
+        tracker.on_event(NetworkEvent::Connected(conn), Instant::now());
+
+        (tracker, conn)
+    }
+
+    fn message(conn: Connection, data: &[u8]) -> MessageWithDestination {
+        MessageWithDestination {
+            message: Bytes::copy_from_slice(data),
+            delivery: NetworkDelivery::ReliableOrdered(None),
+            ..control_message(conn, Bytes::new())
+        }
+    }
+
+    /// The peer's answer to a ping the tracker sent.
+    fn pong(ping: &MessageWithDestination) -> NetworkEvent {
+        let conn = Connection {
+            addr: ping.destination,
+            socket: ping.socket_handle,
+        };
+
+        NetworkEvent::Message(conn, frame(PAYLOAD_PONG, &ping.message[1..]))
+    }
+
+    /// The stats of the only connection.
+    fn stats(events: &[NetworkEvent]) -> ConnectionStats {
+        match events {
+            [NetworkEvent::Stats(_, stats)] => stats.clone(),
+            events => panic!("Expected the stats of a connection, got {:?}", events),
+        }
+    }
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    fn millis(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+}
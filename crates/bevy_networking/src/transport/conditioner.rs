@@ -0,0 +1,375 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use super::WorkerSocket;
+use crate::{
+    error::NetworkError,
+    events::{NetworkEvent, SendError},
+    types::{LinkConditionerConfig, MessageWithDestination, NetworkDelivery, SocketHandle},
+};
+
+
+/// Wraps another socket & degrades its traffic, both ways, according to a `LinkConditionerConfig`.
+///
+/// Unreliable messages are dropped & duplicated like datagrams on a bad link. Reliable messages are never lost or
+/// duplicated, since the transport would recover them; a "lost" reliable message is resent a round trip later
+/// instead, and keeps its order relative to the other reliable messages to the same peer.
+///
+/// Received messages go through the same queue. Their delivery is unknown by the time the transport hands them up, so
+/// they are all treated as reliable: a lost one arrives a round trip late, & never overtakes what the peer sent before.
+pub(crate) struct ConditionedSocket {
+    inner: Box<dyn WorkerSocket>,
+    config: LinkConditionerConfig,
+    random: StdRng,
+    queue: Vec<(Instant, Queued)>,
+    last_reliable: HashMap<SocketAddr, Instant>,
+    last_received: HashMap<SocketAddr, Instant>,
+}
+
+enum Queued {
+    /// On its way to a peer.
+    Outgoing(MessageWithDestination),
+    /// Received from a peer, on its way to the app.
+    Incoming(NetworkEvent),
+}
+
+impl ConditionedSocket {
+    pub(crate) fn new(inner: Box<dyn WorkerSocket>, config: LinkConditionerConfig) -> Self {
+        ConditionedSocket {
+            inner,
+            random: StdRng::seed_from_u64(config.seed),
+            config,
+            queue: Vec::new(),
+            last_reliable: HashMap::new(),
+            last_received: HashMap::new(),
+        }
+    }
+
+    fn delay(&mut self) -> Duration {
+        let jitter = self.config.jitter.as_secs_f64();
+        let offset = match jitter > 0.0 {
+            true => self.random.gen_range(-jitter, jitter),
+            false => 0.0,
+        };
+
+        Duration::from_secs_f64((self.config.latency.as_secs_f64() + offset).max(0.0))
+    }
+
+    fn roll(&mut self, chance: f32) -> bool {
+        chance > 0.0 && self.random.gen::<f32>() < chance
+    }
+
+    /// Like a reliable send, a lost message is late but arrives. Connects & disconnects aren't delayed themselves,
+    /// but wait for the messages of their peer, so a peer never disconnects before its last message arrives.
+    fn receive(&mut self, event: NetworkEvent, now: Instant, events: &mut Vec<NetworkEvent>) {
+        let (addr, mut release_at) = match &event {
+            NetworkEvent::Message(conn, _) => {
+                let mut release_at = now + self.delay();
+
+                if self.roll(self.config.packet_loss) {
+                    release_at += self.config.latency * 2 + self.delay();
+                }
+
+                (conn.addr, release_at)
+            }
+            NetworkEvent::Connected(conn) | NetworkEvent::Disconnected(conn) => (conn.addr, now),
+            _ => {
+                events.push(event);
+                return;
+            }
+        };
+
+        let last = self.last_received.entry(addr).or_insert(now);
+        release_at = release_at.max(*last);
+        *last = release_at;
+
+        self.queue.push((release_at, Queued::Incoming(event)));
+    }
+
+    /// Sends the outgoing messages which are due & hands the incoming ones to the app.
+    fn release(&mut self, now: Instant, events: &mut Vec<NetworkEvent>) {
+        // Keep the queue sorted so messages released together go out in release order
+        self.queue.sort_by_key(|(release_at, _)| *release_at);
+
+        let due = self.queue.iter().take_while(|(release_at, _)| *release_at <= now).count();
+
+        for (_, queued) in self.queue.drain(..due) {
+            match queued {
+                Queued::Outgoing(message) => {
+                    if let Err(err) = self.inner.send(&message) {
+                        events.push(NetworkEvent::SendError(SendError::for_message(&message, err)));
+                    }
+                }
+                Queued::Incoming(event) => events.push(event),
+            }
+        }
+    }
+}
+
+impl WorkerSocket for ConditionedSocket {
+    fn send(&mut self, message: &MessageWithDestination) -> Result<(), NetworkError> {
+        let now = Instant::now();
+        let lost = self.roll(self.config.packet_loss);
+
+        if !is_reliable(message.delivery) {
+            if lost {
+                return Ok(());
+            }
+
+            if self.roll(self.config.duplication) {
+                let release_at = now + self.delay();
+                self.queue.push((release_at, Queued::Outgoing(message.clone())));
+            }
+
+            let release_at = now + self.delay();
+            self.queue.push((release_at, Queued::Outgoing(message.clone())));
+            return Ok(());
+        }
+
+        let mut release_at = now + self.delay();
+
+        if lost {
+            release_at += self.config.latency * 2 + self.delay();
+        }
+
+        // Reliable messages may be late but never overtake each other
+        let last = self.last_reliable.entry(message.destination).or_insert(now);
+        release_at = release_at.max(*last);
+        *last = release_at;
+
+        self.queue.push((release_at, Queued::Outgoing(message.clone())));
+        Ok(())
+    }
+
+    fn poll(&mut self, now: Instant, handle: SocketHandle, events: &mut Vec<NetworkEvent>) {
+        self.release(now, events);
+
+        let mut received = Vec::new();
+        self.inner.poll(now, handle, &mut received);
+
+        for event in received {
+            self.receive(event, now, events);
+        }
+
+        // Without latency, what was just received is already due
+        self.release(now, events);
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        let next_release = self.queue.iter().map(|(release_at, _)| *release_at).min();
+
+        next_release.into_iter().chain(self.inner.next_deadline()).min()
+    }
+
+    fn disconnect(&mut self, addr: SocketAddr) {
+        self.inner.disconnect(addr);
+    }
+}
+
+
+fn is_reliable(delivery: NetworkDelivery) -> bool {
+    match delivery {
+        NetworkDelivery::UnreliableUnordered | NetworkDelivery::UnreliableSequenced(_) => false,
+        NetworkDelivery::ReliableUnordered
+        | NetworkDelivery::ReliableSequenced(_)
+        | NetworkDelivery::ReliableOrdered(_) => true,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Connection;
+    use bytes::Bytes;
+    use std::sync::{Arc, Mutex};
+
+    use Seen::*;
+
+    const LATENCY: u64 = 100;
+
+    #[test]
+    fn delays_received_messages() {
+        let mut link = Link::new(config(0.0));
+        link.receive(&[Message(1)]);
+
+        assert_eq!(link.poll(0), vec![]);
+        assert_eq!(link.next_deadline(), Some(LATENCY));
+        assert_eq!(link.poll(LATENCY), vec![Message(1)]);
+    }
+
+    #[test]
+    fn lost_received_messages_arrive_a_round_trip_late() {
+        let mut link = Link::new(config(1.0));
+        link.receive(&[Message(1), Message(2)]);
+
+        assert_eq!(link.poll(0), vec![]);
+        assert_eq!(link.poll(LATENCY), vec![]);
+        // Noticed missing a round trip later, then resent
+        assert_eq!(link.poll(LATENCY * 4 - 1), vec![]);
+        assert_eq!(link.poll(LATENCY * 4), vec![Message(1), Message(2)]);
+    }
+
+    #[test]
+    fn disconnects_wait_for_the_messages_before_them() {
+        let mut link = Link::new(config(0.0));
+        link.receive(&[Connected, Message(1), Disconnected]);
+
+        assert_eq!(link.poll(0), vec![Connected]);
+        assert_eq!(link.poll(LATENCY), vec![Message(1), Disconnected]);
+    }
+
+    #[test]
+    fn passes_received_messages_straight_through_without_latency() {
+        let mut link = Link::new(LinkConditionerConfig::default());
+        link.receive(&[Connected, Message(1)]);
+
+        assert_eq!(link.poll(0), vec![Connected, Message(1)]);
+    }
+
+    #[test]
+    fn delays_sent_messages() {
+        let mut link = Link::new(config(0.0));
+        link.send(NetworkDelivery::ReliableOrdered(None));
+
+        link.poll(0);
+        assert_eq!(link.sent(), 0);
+
+        // Sends are timed from when they happen, a little after the start
+        link.poll(LATENCY * 2);
+        assert_eq!(link.sent(), 1);
+    }
+
+    #[test]
+    fn drops_lost_unreliable_sent_messages() {
+        let mut link = Link::new(config(1.0));
+        link.send(NetworkDelivery::UnreliableUnordered);
+        link.send(NetworkDelivery::ReliableOrdered(None));
+
+        link.poll(LATENCY * 5);
+        assert_eq!(link.sent(), 1);
+    }
+
+
+    // ==========================================================================
+    // --- Helpers
+    // ==========================================================================
+    /// What the app is told about the peer, messages by their only byte.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Seen {
+        Connected,
+        Message(u8),
+        Disconnected,
+    }
+
+    fn config(packet_loss: f32) -> LinkConditionerConfig {
+        LinkConditionerConfig {
+            packet_loss,
+            latency: Duration::from_millis(LATENCY),
+            ..Default::default()
+        }
+    }
+
+    /// The transport under the conditioner, handing out whatever the test queued & counting what it was given.
+    struct Wire {
+        incoming: Arc<Mutex<Vec<NetworkEvent>>>,
+        sent: Arc<Mutex<usize>>,
+    }
+
+    impl WorkerSocket for Wire {
+        fn send(&mut self, _message: &MessageWithDestination) -> Result<(), NetworkError> {
+            *self.sent.lock().unwrap() += 1;
+            Ok(())
+        }
+
+        fn poll(&mut self, _now: Instant, _handle: SocketHandle, events: &mut Vec<NetworkEvent>) {
+            events.append(&mut self.incoming.lock().unwrap());
+        }
+    }
+
+    struct Link {
+        socket: ConditionedSocket,
+        conn: Connection,
+        start: Instant,
+        incoming: Arc<Mutex<Vec<NetworkEvent>>>,
+        sent: Arc<Mutex<usize>>,
+    }
+
+    impl Link {
+        fn new(config: LinkConditionerConfig) -> Self {
+            let incoming = Arc::new(Mutex::new(vec![]));
+            let sent = Arc::new(Mutex::new(0));
+
+            let wire = Wire {
+                incoming: incoming.clone(),
+                sent: sent.clone(),
+            };
+
+            Link {
+                socket: ConditionedSocket::new(Box::new(wire), config),
+                conn: Connection {
+                    addr: SocketAddr::from(([127, 0, 0, 1], 1)),
+                    socket: SocketHandle::new(),
+                },
+                start: Instant::now(),
+                incoming,
+                sent,
+            }
+        }
+
+        /// Queues events from the peer for the next poll of the wire.
+        fn receive(&mut self, seen: &[Seen]) {
+            let mut incoming = self.incoming.lock().unwrap();
+
+            for seen in seen {
+                incoming.push(match seen {
+                    Connected => NetworkEvent::Connected(self.conn),
+                    Message(byte) => NetworkEvent::Message(self.conn, Bytes::from(vec![*byte])),
+                    Disconnected => NetworkEvent::Disconnected(self.conn),
+                });
+            }
+        }
+
+        fn send(&mut self, delivery: NetworkDelivery) {
+            let message = MessageWithDestination {
+                id: None,
+                message: Bytes::from_static(&[0]),
+                delivery,
+                socket_handle: self.conn.socket,
+                destination: self.conn.addr,
+            };
+
+            self.socket.send(&message).unwrap();
+        }
+
+        /// Polls `at` milliseconds after the start.
+        fn poll(&mut self, at: u64) -> Vec<Seen> {
+            let mut events = vec![];
+            self.socket.poll(self.start + Duration::from_millis(at), self.conn.socket, &mut events);
+
+            events
+                .into_iter()
+                .filter_map(|event| match event {
+                    NetworkEvent::Connected(_) => Some(Connected),
+                    NetworkEvent::Message(_, data) => Some(Message(data[0])),
+                    NetworkEvent::Disconnected(_) => Some(Disconnected),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        /// Milliseconds after the start.
+        fn next_deadline(&self) -> Option<u64> {
+            let deadline = self.socket.next_deadline()?;
+            Some(deadline.duration_since(self.start).as_millis() as u64)
+        }
+
+        fn sent(&self) -> usize {
+            *self.sent.lock().unwrap()
+        }
+    }
+}
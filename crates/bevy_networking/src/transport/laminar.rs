@@ -0,0 +1,66 @@
+use bytes::Bytes;
+use laminar::{Packet, Socket, SocketEvent};
+use log::debug;
+use std::time::Instant;
+
+use super::WorkerSocket;
+use crate::{
+    error::NetworkError,
+    events::NetworkEvent,
+    types::{Connection, MessageWithDestination, NetworkDelivery, SocketHandle},
+};
+
+
+pub(crate) struct LaminarSocket(Socket);
+
+impl LaminarSocket {
+    pub(crate) fn new(socket: Socket) -> Self {
+        LaminarSocket(socket)
+    }
+}
+
+impl WorkerSocket for LaminarSocket {
+    fn send(&mut self, message: &MessageWithDestination) -> Result<(), NetworkError> {
+        self.0.send(to_packet(message)).map_err(|e| e.into())
+    }
+
+    fn poll(&mut self, now: Instant, handle: SocketHandle, events: &mut Vec<NetworkEvent>) {
+        self.0.manual_poll(now);
+
+        while let Some(event) = self.0.recv() {
+            let e = match event {
+                SocketEvent::Connect(addr) => {
+                    debug!("Laminar connect from {}", addr);
+                    NetworkEvent::Connected(Connection { addr, socket: handle })
+                }
+                SocketEvent::Timeout(addr) => NetworkEvent::Disconnected(Connection { addr, socket: handle }),
+                SocketEvent::Packet(packet) => NetworkEvent::Message(
+                    Connection {
+                        addr: packet.addr(),
+                        socket: handle,
+                    },
+                    Bytes::copy_from_slice(packet.payload()),
+                ),
+                SocketEvent::Disconnect(addr) => {
+                    debug!("Laminar disconnect from {}", addr);
+                    NetworkEvent::Disconnected(Connection { addr, socket: handle })
+                }
+            };
+
+            events.push(e);
+        }
+    }
+}
+
+fn to_packet(message: &MessageWithDestination) -> Packet {
+    let addr = message.destination;
+    let payload = message.message.to_vec();
+
+    match message.delivery {
+        NetworkDelivery::UnreliableUnordered => Packet::unreliable(addr, payload),
+        NetworkDelivery::UnreliableSequenced(stream) => Packet::unreliable_sequenced(addr, payload, stream),
+        NetworkDelivery::ReliableUnordered => Packet::reliable_unordered(addr, payload),
+        NetworkDelivery::ReliableSequenced(stream) => Packet::reliable_sequenced(addr, payload, stream),
+        NetworkDelivery::ReliableOrdered(stream) => Packet::reliable_ordered(addr, payload, stream),
+    }
+}
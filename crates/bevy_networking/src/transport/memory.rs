@@ -0,0 +1,210 @@
+use bytes::Bytes;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::WorkerSocket;
+use crate::{
+    error::NetworkError,
+    events::NetworkEvent,
+    types::{Connection, MemoryConfig, MemoryOrdering, MessageWithDestination, SocketHandle},
+    worker::Waker,
+};
+
+
+/// A set of in-process sockets which can only reach each other. Clone it into the `MemoryConfig` of every
+/// `NetworkResource` that should share the network, e.g. the host & client `App`s of a test.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryNetwork {
+    sockets: Arc<Mutex<HashMap<SocketAddr, (Sender<MemoryPacket>, Waker)>>>,
+}
+
+impl MemoryNetwork {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub(crate) fn bind(
+        &self,
+        addr: SocketAddr,
+        config: MemoryConfig,
+        waker: Waker,
+    ) -> Result<MemorySocket, NetworkError> {
+        let mut sockets = self.sockets.lock()?;
+
+        if sockets.contains_key(&addr) {
+            let message = format!("{} is already bound on this memory network", addr);
+            return Err(NetworkError::IOError(io::Error::new(io::ErrorKind::AddrInUse, message)));
+        }
+
+        let (inbox_tx, inbox_rx) = unbounded();
+        sockets.insert(addr, (inbox_tx, waker));
+
+        let seed = match config.ordering {
+            MemoryOrdering::Shuffled(seed) => seed.max(1),
+            MemoryOrdering::InOrder => 1,
+        };
+
+        Ok(MemorySocket {
+            addr,
+            network: self.clone(),
+            latency: config.latency,
+            ordering: config.ordering,
+            random_state: seed,
+            inbox: inbox_rx,
+            pending: Vec::new(),
+            peers: HashSet::new(),
+            connected: HashSet::new(),
+        })
+    }
+
+    /// Like UDP, packets to an address nobody is bound to are silently dropped.
+    fn deliver(&self, to: SocketAddr, packet: MemoryPacket) -> Result<(), NetworkError> {
+        if let Some((inbox, waker)) = self.sockets.lock()?.get(&to) {
+            // The receiving socket may be closing, in which case the packet is lost
+            let _ = inbox.send(packet);
+            waker.wake();
+        }
+
+        Ok(())
+    }
+
+    fn unbind(&self, addr: &SocketAddr) {
+        if let Ok(mut sockets) = self.sockets.lock() {
+            sockets.remove(addr);
+        }
+    }
+}
+
+
+#[derive(Debug)]
+enum MemoryPayload {
+    Data(Bytes),
+    Disconnect,
+}
+
+#[derive(Debug)]
+struct MemoryPacket {
+    from: SocketAddr,
+    deliver_at: Instant,
+    payload: MemoryPayload,
+}
+
+/// Delivers every message exactly once, so `NetworkDelivery` is ignored. Peers are connected when their first
+/// packet arrives & disconnected when their socket is closed.
+pub(crate) struct MemorySocket {
+    addr: SocketAddr,
+    network: MemoryNetwork,
+    latency: Duration,
+    ordering: MemoryOrdering,
+    random_state: u64,
+    inbox: Receiver<MemoryPacket>,
+    pending: Vec<MemoryPacket>,
+    peers: HashSet<SocketAddr>,
+    connected: HashSet<SocketAddr>,
+}
+
+impl WorkerSocket for MemorySocket {
+    fn send(&mut self, message: &MessageWithDestination) -> Result<(), NetworkError> {
+        self.peers.insert(message.destination);
+
+        let packet = MemoryPacket {
+            from: self.addr,
+            deliver_at: Instant::now() + self.latency,
+            payload: MemoryPayload::Data(message.message.clone()),
+        };
+
+        self.network.deliver(message.destination, packet)
+    }
+
+    fn poll(&mut self, now: Instant, handle: SocketHandle, events: &mut Vec<NetworkEvent>) {
+        let received = self.pending.len();
+        self.pending.extend(self.inbox.try_iter());
+
+        if let MemoryOrdering::Shuffled(_) = self.ordering {
+            shuffle(&mut self.pending[received..], &mut self.random_state);
+        }
+
+        let (due, pending): (Vec<_>, Vec<_>) =
+            self.pending.drain(..).partition(|packet| packet.deliver_at <= now);
+        self.pending = pending;
+
+        for packet in due {
+            let conn = Connection {
+                addr: packet.from,
+                socket: handle,
+            };
+
+            match packet.payload {
+                MemoryPayload::Data(data) => {
+                    self.peers.insert(packet.from);
+
+                    if self.connected.insert(packet.from) {
+                        events.push(NetworkEvent::Connected(conn));
+                    }
+
+                    events.push(NetworkEvent::Message(conn, data));
+                }
+                MemoryPayload::Disconnect => {
+                    self.peers.remove(&packet.from);
+
+                    if self.connected.remove(&packet.from) {
+                        events.push(NetworkEvent::Disconnected(conn));
+                    }
+                }
+            }
+        }
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.pending.iter().map(|packet| packet.deliver_at).min()
+    }
+
+    fn disconnect(&mut self, addr: SocketAddr) {
+        self.connected.remove(&addr);
+
+        if self.peers.remove(&addr) {
+            let packet = MemoryPacket {
+                from: self.addr,
+                deliver_at: Instant::now() + self.latency,
+                payload: MemoryPayload::Disconnect,
+            };
+
+            let _ = self.network.deliver(addr, packet);
+        }
+    }
+}
+
+impl Drop for MemorySocket {
+    fn drop(&mut self) {
+        self.network.unbind(&self.addr);
+
+        for peer in self.peers.drain() {
+            let packet = MemoryPacket {
+                from: self.addr,
+                deliver_at: Instant::now() + self.latency,
+                payload: MemoryPayload::Disconnect,
+            };
+
+            let _ = self.network.deliver(peer, packet);
+        }
+    }
+}
+
+
+/// Fisher-Yates shuffle driven by xorshift, so a seed always produces the same order.
+fn shuffle<T>(items: &mut [T], state: &mut u64) {
+    for i in (1..items.len()).rev() {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+
+        let j = (*state % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
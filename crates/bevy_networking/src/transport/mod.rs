@@ -0,0 +1,43 @@
+use std::{net::SocketAddr, time::Instant};
+
+use super::{
+    error::NetworkError,
+    events::NetworkEvent,
+    types::{MessageWithDestination, SocketHandle},
+};
+
+mod conditioner;
+mod laminar;
+mod memory;
+mod rate_limit;
+mod secure;
+mod tcp;
+mod websocket;
+
+pub(crate) use self::conditioner::ConditionedSocket;
+pub(crate) use self::laminar::LaminarSocket;
+pub use self::memory::MemoryNetwork;
+pub(crate) use self::rate_limit::RateLimitedSocket;
+pub(crate) use self::secure::SecureSocket;
+pub(crate) use self::tcp::TcpSocket;
+pub(crate) use self::websocket::WebSocketSocket;
+
+
+/// A socket owned by the worker thread. Every `Transport` provides one.
+pub(crate) trait WorkerSocket: Send {
+    /// Queues a message for sending. Transports without delivery guarantees of their own may degrade the
+    /// requested `NetworkDelivery`.
+    fn send(&mut self, message: &MessageWithDestination) -> Result<(), NetworkError>;
+
+    /// Flushes queued messages & pushes every connect, disconnect & message event received since the last poll.
+    fn poll(&mut self, now: Instant, handle: SocketHandle, events: &mut Vec<NetworkEvent>);
+
+    /// When the socket next has something scheduled, like a delayed packet, so the worker can sleep until then.
+    fn next_deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    /// Drops the connection to a peer without reporting it, the caller reports the disconnect itself. Transports
+    /// without a way to close a connection, like laminar, keep it until the peer times out.
+    fn disconnect(&mut self, _addr: SocketAddr) {}
+}
@@ -0,0 +1,480 @@
+use log::warn;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+use super::WorkerSocket;
+use crate::{
+    error::NetworkError,
+    events::{NetworkEvent, RateLimitViolated},
+    types::{Connection, MessageWithDestination, RateLimitConfig, SocketHandle, Violation, ViolationPolicy},
+};
+
+/// Violations of a peer that is only throttled are reported at most this often.
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+
+/// Wraps a transport & enforces a `RateLimitConfig` on every packet it receives.
+///
+/// Each peer gets a token bucket holding up to `burst` messages, refilled at `messages_per_sec`. A packet arriving
+/// with the bucket empty, or larger than `max_message_size`, is dropped & the configured `ViolationPolicy` applied.
+///
+/// Peers disconnected by the policy are reported as disconnected straight away. Their packets are dropped until
+/// the transport closes the connection as well or, for transports which can't close connections, until they send
+/// again after any ban is over, which reports them as connected again.
+pub(crate) struct RateLimitedSocket {
+    inner: Box<dyn WorkerSocket>,
+    config: RateLimitConfig,
+    peers: HashMap<SocketAddr, Peer>,
+    /// Peers disconnected for a violation & when.
+    disconnected: HashMap<SocketAddr, Instant>,
+    bans: HashMap<IpAddr, Instant>,
+}
+
+struct Peer {
+    tokens: f32,
+    last_refill: Instant,
+    /// Packets dropped since the last `RateLimitViolated` & the reason for the latest one.
+    dropped: u32,
+    violation: Option<Violation>,
+    last_report: Option<Instant>,
+}
+
+impl RateLimitedSocket {
+    pub(crate) fn new(inner: Box<dyn WorkerSocket>, config: RateLimitConfig) -> Self {
+        RateLimitedSocket {
+            inner,
+            config,
+            peers: HashMap::new(),
+            disconnected: HashMap::new(),
+            bans: HashMap::new(),
+        }
+    }
+
+    fn is_banned(&self, addr: SocketAddr) -> bool {
+        self.bans.contains_key(&addr.ip())
+    }
+
+    fn check(&mut self, addr: SocketAddr, size: usize, now: Instant) -> Result<(), Violation> {
+        if size > self.config.max_message_size {
+            return Err(Violation::MessageTooLarge(size));
+        }
+
+        let config = &self.config;
+        let peer = self.peers.entry(addr).or_insert_with(|| Peer::new(config, now));
+        peer.refill(config, now);
+
+        if peer.tokens < 1.0 {
+            return Err(Violation::TooManyMessages);
+        }
+
+        peer.tokens -= 1.0;
+        Ok(())
+    }
+
+    fn on_violation(&mut self, conn: Connection, violation: Violation, now: Instant, events: &mut Vec<NetworkEvent>) {
+        let config = &self.config;
+        let peer = self.peers.entry(conn.addr).or_insert_with(|| Peer::new(config, now));
+        peer.dropped += 1;
+        peer.violation = Some(violation);
+
+        let policy = self.config.policy;
+
+        match policy {
+            ViolationPolicy::Drop => {
+                if peer.is_report_due(now) {
+                    events.push(peer.report(conn, policy, now));
+                }
+            }
+            ViolationPolicy::Disconnect => {
+                warn!("Disconnecting {} for {:?}", conn, violation);
+
+                events.push(peer.report(conn, policy, now));
+                self.disconnect_peer(conn, now, events);
+            }
+            ViolationPolicy::Ban(duration) => {
+                warn!("Banning {} for {:?} after {:?}", conn.addr.ip(), duration, violation);
+
+                events.push(peer.report(conn, policy, now));
+                self.bans.insert(conn.addr.ip(), now + duration);
+
+                // Every connection from the address goes, not just the one that misbehaved
+                let banned: Vec<SocketAddr> =
+                    self.peers.keys().filter(|addr| addr.ip() == conn.addr.ip()).copied().collect();
+
+                for addr in banned {
+                    self.disconnect_peer(Connection { addr, socket: conn.socket }, now, events);
+                }
+            }
+        }
+    }
+
+    fn disconnect_peer(&mut self, conn: Connection, now: Instant, events: &mut Vec<NetworkEvent>) {
+        self.peers.remove(&conn.addr);
+
+        if self.disconnected.insert(conn.addr, now).is_none() {
+            self.inner.disconnect(conn.addr);
+            events.push(NetworkEvent::Disconnected(conn));
+        }
+    }
+
+    /// Reports throttled peers that were dropped since their last report, once the report is due.
+    fn report_dropped(&mut self, handle: SocketHandle, now: Instant, events: &mut Vec<NetworkEvent>) {
+        let policy = self.config.policy;
+
+        for (addr, peer) in self.peers.iter_mut() {
+            if peer.dropped > 0 && peer.is_report_due(now) {
+                let conn = Connection {
+                    addr: *addr,
+                    socket: handle,
+                };
+
+                events.push(peer.report(conn, policy, now));
+            }
+        }
+    }
+}
+
+impl WorkerSocket for RateLimitedSocket {
+    fn send(&mut self, message: &MessageWithDestination) -> Result<(), NetworkError> {
+        self.inner.send(message)
+    }
+
+    fn poll(&mut self, now: Instant, handle: SocketHandle, events: &mut Vec<NetworkEvent>) {
+        self.bans.retain(|_, until| *until > now);
+
+        let mut inner_events = Vec::new();
+        self.inner.poll(now, handle, &mut inner_events);
+
+        for event in inner_events {
+            match event {
+                NetworkEvent::Connected(conn) if self.is_banned(conn.addr) => {
+                    self.disconnected.insert(conn.addr, now);
+                    self.inner.disconnect(conn.addr);
+                }
+                NetworkEvent::Connected(conn) => {
+                    self.disconnected.remove(&conn.addr);
+                    events.push(NetworkEvent::Connected(conn));
+                }
+                NetworkEvent::Disconnected(conn) => {
+                    self.peers.remove(&conn.addr);
+
+                    // Already reported when the peer was disconnected for a violation
+                    if self.disconnected.remove(&conn.addr).is_none() {
+                        events.push(NetworkEvent::Disconnected(conn));
+                    }
+                }
+                NetworkEvent::Message(conn, data) => {
+                    if self.is_banned(conn.addr) {
+                        continue;
+                    }
+
+                    match self.disconnected.get(&conn.addr) {
+                        // The rest of the batch the peer was disconnected in
+                        Some(at) if *at == now => continue,
+                        Some(_) => {
+                            self.disconnected.remove(&conn.addr);
+                            events.push(NetworkEvent::Connected(conn));
+                        }
+                        None => {}
+                    }
+
+                    match self.check(conn.addr, data.len(), now) {
+                        Ok(()) => events.push(NetworkEvent::Message(conn, data)),
+                        Err(violation) => self.on_violation(conn, violation, now, events),
+                    }
+                }
+                event => events.push(event),
+            }
+        }
+
+        self.report_dropped(handle, now, events);
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.inner.next_deadline()
+    }
+
+    fn disconnect(&mut self, addr: SocketAddr) {
+        self.peers.remove(&addr);
+        self.inner.disconnect(addr);
+    }
+}
+
+impl Peer {
+    fn new(config: &RateLimitConfig, now: Instant) -> Self {
+        Peer {
+            tokens: config.burst as f32,
+            last_refill: now,
+            dropped: 0,
+            violation: None,
+            last_report: None,
+        }
+    }
+
+    fn refill(&mut self, config: &RateLimitConfig, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f32();
+
+        self.tokens = (self.tokens + elapsed * config.messages_per_sec).min(config.burst as f32);
+        self.last_refill = now;
+    }
+
+    fn is_report_due(&self, now: Instant) -> bool {
+        self.last_report.map_or(true, |last| now.duration_since(last) >= REPORT_INTERVAL)
+    }
+
+    fn report(&mut self, conn: Connection, action: ViolationPolicy, now: Instant) -> NetworkEvent {
+        let violated = RateLimitViolated {
+            conn,
+            violation: self.violation.unwrap_or(Violation::TooManyMessages),
+            dropped: self.dropped,
+            action,
+        };
+
+        self.dropped = 0;
+        self.last_report = Some(now);
+
+        NetworkEvent::RateLimitViolated(violated)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::sync::{Arc, Mutex};
+
+    use Seen::*;
+
+    const ALICE: u16 = 1;
+    const ALICE_AGAIN: u16 = 2;
+    const BOB: u16 = 3;
+
+    #[test]
+    fn allows_a_burst() {
+        let mut limiter = Limiter::new(config(ViolationPolicy::Drop));
+
+        let seen = limiter.receive(0, &[ALICE, ALICE, ALICE, ALICE, BOB]);
+
+        assert_eq!(
+            seen,
+            vec![
+                Message(ALICE),
+                Message(ALICE),
+                Message(ALICE),
+                Violated(ALICE, Violation::TooManyMessages, 1),
+                Message(BOB),
+            ]
+        );
+    }
+
+    #[test]
+    fn refills_at_the_rate() {
+        let mut limiter = Limiter::new(config(ViolationPolicy::Drop));
+        limiter.receive(0, &[ALICE, ALICE, ALICE]);
+
+        // Two messages a second, so a token every 500ms
+        assert_eq!(limiter.receive(250, &[ALICE]), vec![Violated(ALICE, Violation::TooManyMessages, 1)]);
+        assert_eq!(limiter.receive(500, &[ALICE]), vec![Message(ALICE)]);
+        assert_eq!(limiter.receive(1000, &[ALICE]), vec![Message(ALICE)]);
+    }
+
+    #[test]
+    fn refills_up_to_the_burst() {
+        let mut limiter = Limiter::new(config(ViolationPolicy::Drop));
+        limiter.receive(0, &[ALICE, ALICE, ALICE]);
+
+        let seen = limiter.receive(60_000, &[ALICE, ALICE, ALICE, ALICE]);
+
+        assert_eq!(seen.iter().filter(|seen| **seen == Message(ALICE)).count(), 3);
+    }
+
+    #[test]
+    fn drops_oversized_messages() {
+        let mut limiter = Limiter::new(config(ViolationPolicy::Drop));
+
+        let seen = limiter.receive_sized(0, &[(ALICE, 64), (ALICE, 65)]);
+
+        assert_eq!(seen, vec![Message(ALICE), Violated(ALICE, Violation::MessageTooLarge(65), 1)]);
+    }
+
+    #[test]
+    fn drop_reports_throttled_peers_once_per_interval() {
+        let mut limiter = Limiter::new(config(ViolationPolicy::Drop));
+        limiter.receive(0, &[ALICE, ALICE, ALICE, ALICE]);
+
+        // Dropped again, but only reported once the interval is over, with everything dropped since
+        assert_eq!(limiter.receive(100, &[ALICE, ALICE]), vec![]);
+        assert_eq!(limiter.receive(1000, &[]), vec![Violated(ALICE, Violation::TooManyMessages, 2)]);
+        assert_eq!(limiter.receive(2000, &[]), vec![]);
+        assert!(limiter.disconnected().is_empty());
+    }
+
+    #[test]
+    fn disconnect_drops_the_rest_of_the_batch() {
+        let mut limiter = Limiter::new(config(ViolationPolicy::Disconnect));
+
+        let seen = limiter.receive(0, &[ALICE, ALICE, ALICE, ALICE, ALICE, BOB]);
+
+        assert_eq!(
+            seen,
+            vec![
+                Message(ALICE),
+                Message(ALICE),
+                Message(ALICE),
+                Violated(ALICE, Violation::TooManyMessages, 1),
+                Disconnected(ALICE),
+                Message(BOB),
+            ]
+        );
+        assert_eq!(limiter.disconnected(), vec![ALICE]);
+
+        // Sending again later connects with a fresh allowance
+        let seen = limiter.receive(100, &[ALICE, ALICE, ALICE]);
+        assert_eq!(seen, vec![Connected(ALICE), Message(ALICE), Message(ALICE), Message(ALICE)]);
+    }
+
+    #[test]
+    fn ban_drops_the_address_until_it_is_over() {
+        let mut limiter = Limiter::new(config(ViolationPolicy::Ban(Duration::from_secs(10))));
+        limiter.receive(0, &[ALICE_AGAIN]);
+
+        let seen = limiter.receive(0, &[ALICE, ALICE, ALICE, ALICE]);
+        assert_eq!(seen[3], Violated(ALICE, Violation::TooManyMessages, 1));
+        assert_eq!(limiter.violations[0].action, ViolationPolicy::Ban(Duration::from_secs(10)));
+
+        // In no particular order, the peers are kept in a map
+        assert_eq!(seen[4..].len(), 2);
+        assert!(seen[4..].contains(&Disconnected(ALICE)));
+        assert!(seen[4..].contains(&Disconnected(ALICE_AGAIN)));
+
+        // Every port of the address is banned, other addresses are not
+        assert_eq!(limiter.receive(5000, &[ALICE_AGAIN, BOB]), vec![Message(BOB)]);
+
+        let seen = limiter.receive(10_000, &[ALICE_AGAIN]);
+        assert_eq!(seen, vec![Connected(ALICE_AGAIN), Message(ALICE_AGAIN)]);
+    }
+
+
+    // ==========================================================================
+    // --- Helpers
+    // ==========================================================================
+    /// What the app is told, by port. The peers on ports `ALICE` & `ALICE_AGAIN` share an IP address.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Seen {
+        Message(u16),
+        Connected(u16),
+        Disconnected(u16),
+        Violated(u16, Violation, u32),
+    }
+
+    fn config(policy: ViolationPolicy) -> RateLimitConfig {
+        RateLimitConfig {
+            messages_per_sec: 2.0,
+            burst: 3,
+            max_message_size: 64,
+            policy,
+        }
+    }
+
+    fn addr(port: u16) -> SocketAddr {
+        match port {
+            BOB => SocketAddr::from(([127, 0, 0, 2], port)),
+            _ => SocketAddr::from(([127, 0, 0, 1], port)),
+        }
+    }
+
+    /// The transport under the limiter, handing out whatever the test queued.
+    struct Inbox {
+        incoming: Arc<Mutex<Vec<NetworkEvent>>>,
+        disconnected: Arc<Mutex<Vec<SocketAddr>>>,
+    }
+
+    impl WorkerSocket for Inbox {
+        fn send(&mut self, _message: &MessageWithDestination) -> Result<(), NetworkError> {
+            Ok(())
+        }
+
+        fn poll(&mut self, _now: Instant, _handle: SocketHandle, events: &mut Vec<NetworkEvent>) {
+            events.append(&mut self.incoming.lock().unwrap());
+        }
+
+        fn disconnect(&mut self, addr: SocketAddr) {
+            self.disconnected.lock().unwrap().push(addr);
+        }
+    }
+
+    struct Limiter {
+        socket: RateLimitedSocket,
+        handle: SocketHandle,
+        start: Instant,
+        incoming: Arc<Mutex<Vec<NetworkEvent>>>,
+        disconnected: Arc<Mutex<Vec<SocketAddr>>>,
+        violations: Vec<RateLimitViolated>,
+    }
+
+    impl Limiter {
+        fn new(config: RateLimitConfig) -> Self {
+            let incoming = Arc::new(Mutex::new(vec![]));
+            let disconnected = Arc::new(Mutex::new(vec![]));
+
+            let inbox = Inbox {
+                incoming: incoming.clone(),
+                disconnected: disconnected.clone(),
+            };
+
+            Limiter {
+                socket: RateLimitedSocket::new(Box::new(inbox), config),
+                handle: SocketHandle::new(),
+                start: Instant::now(),
+                incoming,
+                disconnected,
+                violations: vec![],
+            }
+        }
+
+        /// Polls `at` milliseconds after the start, with a small message from each port arriving in the batch.
+        fn receive(&mut self, at: u64, ports: &[u16]) -> Vec<Seen> {
+            let messages: Vec<(u16, usize)> = ports.iter().map(|port| (*port, 8)).collect();
+            self.receive_sized(at, &messages)
+        }
+
+        fn receive_sized(&mut self, at: u64, messages: &[(u16, usize)]) -> Vec<Seen> {
+            for (port, size) in messages {
+                let conn = Connection {
+                    addr: addr(*port),
+                    socket: self.handle,
+                };
+                let data = Bytes::from(vec![0u8; *size]);
+                self.incoming.lock().unwrap().push(NetworkEvent::Message(conn, data));
+            }
+
+            let mut events = vec![];
+            let now = self.start + Duration::from_millis(at);
+            self.socket.poll(now, self.handle, &mut events);
+
+            events
+                .into_iter()
+                .filter_map(|event| match event {
+                    NetworkEvent::Message(conn, _) => Some(Message(conn.addr.port())),
+                    NetworkEvent::Connected(conn) => Some(Connected(conn.addr.port())),
+                    NetworkEvent::Disconnected(conn) => Some(Disconnected(conn.addr.port())),
+                    NetworkEvent::RateLimitViolated(violated) => {
+                        let seen = Violated(violated.conn.addr.port(), violated.violation, violated.dropped);
+                        self.violations.push(violated);
+                        Some(seen)
+                    }
+                    _ => None,
+                })
+                .collect()
+        }
+
+        /// Ports the limiter told the transport to disconnect.
+        fn disconnected(&self) -> Vec<u16> {
+            self.disconnected.lock().unwrap().iter().map(|addr| addr.port()).collect()
+        }
+    }
+}
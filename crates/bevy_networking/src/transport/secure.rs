@@ -0,0 +1,750 @@
+use bytes::{BufMut, Bytes, BytesMut};
+use log::warn;
+use ring::{
+    aead::{self, Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305},
+    agreement::{self, EphemeralPrivateKey, UnparsedPublicKey, X25519},
+    hkdf, pbkdf2,
+    rand::SystemRandom,
+};
+use std::{collections::HashMap, net::SocketAddr, num::NonZeroU32, time::Instant};
+
+use super::WorkerSocket;
+use crate::{
+    error::NetworkError,
+    events::{NetworkEvent, SendError},
+    types::{Connection, EncryptionConfig, MessageWithDestination, NetworkDelivery, SocketHandle},
+};
+
+const SESSION_HELLO: u8 = 0x10;
+const SESSION_ACCEPT: u8 = 0x11;
+const SESSION_CONFIRM: u8 = 0x12;
+const SESSION_DATA: u8 = 0x13;
+
+const PUBLIC_KEY_LENGTH: usize = 32;
+const COUNTER_LENGTH: usize = 8;
+
+/// Handshakes get their own stream so they are never held back by, or hold back, app messages.
+const HANDSHAKE_DELIVERY: NetworkDelivery = NetworkDelivery::ReliableOrdered(Some(255));
+
+const PASSWORD_SALT: &[u8] = b"bevy_networking room password";
+const PASSWORD_ITERATIONS: u32 = 100_000;
+
+
+/// Wraps another socket & encrypts everything sent through it.
+///
+/// The first message to a peer starts an X25519 key exchange:
+///
+/// * `HELLO [public key]` from the initiator
+/// * `ACCEPT [public key] [sealed confirmation]` from the responder
+/// * `CONFIRM [sealed confirmation]` from the initiator
+///
+/// Session keys are derived with HKDF from the shared secret, salted with a key derived from the room password, so
+/// only peers knowing the password can seal or open packets. Messages are queued until the session is confirmed &
+/// then sent as `DATA [counter] [ChaCha20-Poly1305 ciphertext]`. Replayed counters are dropped.
+///
+/// A `HELLO` on an established session is answered, since the peer may have restarted, but the old keys are kept
+/// until the peer proves which session it holds by sealing a packet with either.
+pub(crate) struct SecureSocket {
+    inner: Box<dyn WorkerSocket>,
+    password_key: [u8; 32],
+    random: SystemRandom,
+    sessions: HashMap<SocketAddr, Session>,
+}
+
+enum Session {
+    /// Sent `HELLO`, waiting for `ACCEPT`.
+    Initiating {
+        private_key: EphemeralPrivateKey,
+        public_key: Vec<u8>,
+        queue: Vec<MessageWithDestination>,
+    },
+    /// Answered a `HELLO`, waiting for the first packet sealed by the peer.
+    Accepting {
+        keys: SessionKeys,
+        queue: Vec<MessageWithDestination>,
+        /// The keys of the session the `HELLO` would replace, anyone can send a `HELLO` from the peer's address.
+        previous: Option<SessionKeys>,
+    },
+    Established(SessionKeys),
+}
+
+struct SessionKeys {
+    seal_key: LessSafeKey,
+    open_key: LessSafeKey,
+    send_counter: u64,
+    replay: ReplayWindow,
+}
+
+/// Tracks the last 64 counters received, like IPsec.
+#[derive(Default)]
+struct ReplayWindow {
+    highest: u64,
+    seen: u64,
+}
+
+impl SecureSocket {
+    pub(crate) fn new(inner: Box<dyn WorkerSocket>, config: EncryptionConfig) -> Self {
+        let mut password_key = [0u8; 32];
+
+        if let Some(password) = config.room_password {
+            pbkdf2::derive(
+                pbkdf2::PBKDF2_HMAC_SHA256,
+                NonZeroU32::new(PASSWORD_ITERATIONS).unwrap(),
+                PASSWORD_SALT,
+                password.as_bytes(),
+                &mut password_key,
+            );
+        }
+
+        SecureSocket {
+            inner,
+            password_key,
+            random: SystemRandom::new(),
+            sessions: HashMap::new(),
+        }
+    }
+
+    fn start_session(
+        &mut self,
+        message: &MessageWithDestination,
+        queue: Vec<MessageWithDestination>,
+    ) -> Result<(), NetworkError> {
+        let private_key = EphemeralPrivateKey::generate(&X25519, &self.random).map_err(crypto_error)?;
+        let public_key = private_key.compute_public_key().map_err(crypto_error)?.as_ref().to_vec();
+
+        let hello = control_message(message, frame(SESSION_HELLO, &[&public_key]));
+
+        self.sessions.insert(
+            message.destination,
+            Session::Initiating {
+                private_key,
+                public_key,
+                queue,
+            },
+        );
+
+        self.inner.send(&hello)
+    }
+
+    fn on_packet(&mut self, conn: Connection, data: Bytes, events: &mut Vec<NetworkEvent>) -> Result<(), NetworkError> {
+        match data.first() {
+            Some(&SESSION_HELLO) => self.on_hello(conn, &data[1..]),
+            Some(&SESSION_ACCEPT) => self.on_accept(conn, &data[1..], events),
+            Some(&SESSION_CONFIRM) => {
+                self.open(conn, &data[1..], events)?;
+                Ok(())
+            }
+            Some(&SESSION_DATA) => {
+                let message = self.open(conn, &data[1..], events)?;
+                events.push(NetworkEvent::Message(conn, message));
+                Ok(())
+            }
+            _ => Err(auth_error("Received a packet outside of a session")),
+        }
+    }
+
+    fn on_hello(&mut self, conn: Connection, peer_public_key: &[u8]) -> Result<(), NetworkError> {
+        if peer_public_key.len() != PUBLIC_KEY_LENGTH {
+            return Err(auth_error("Malformed session hello"));
+        }
+
+        // Both peers started a session at once, the one with the lower public key stays the initiator
+        if let Some(Session::Initiating { public_key, .. }) = self.sessions.get(&conn.addr) {
+            if public_key.as_slice() < peer_public_key {
+                return Ok(());
+            }
+        }
+
+        let private_key = EphemeralPrivateKey::generate(&X25519, &self.random).map_err(crypto_error)?;
+        let public_key = private_key.compute_public_key().map_err(crypto_error)?.as_ref().to_vec();
+
+        let mut keys = self.derive_keys(private_key, peer_public_key, &public_key, false)?;
+
+        let confirmation = keys.seal(&[])?;
+        let accept = frame(SESSION_ACCEPT, &[&public_key, &confirmation]);
+        self.inner.send(&control_message_to(conn, accept))?;
+
+        let (queue, previous) = match self.sessions.remove(&conn.addr) {
+            Some(Session::Initiating { queue, .. }) => (queue, None),
+            Some(Session::Accepting { queue, previous, .. }) => (queue, previous),
+            // The peer may have restarted, but only sealing with the new keys proves it
+            Some(Session::Established(keys)) => (vec![], Some(keys)),
+            None => (vec![], None),
+        };
+
+        self.sessions.insert(conn.addr, Session::Accepting { keys, queue, previous });
+        Ok(())
+    }
+
+    fn on_accept(&mut self, conn: Connection, data: &[u8], events: &mut Vec<NetworkEvent>) -> Result<(), NetworkError> {
+        if data.len() < PUBLIC_KEY_LENGTH {
+            return Err(auth_error("Malformed session accept"));
+        }
+
+        let (private_key, public_key, queue) = match self.sessions.remove(&conn.addr) {
+            Some(Session::Initiating {
+                private_key,
+                public_key,
+                queue,
+            }) => (private_key, public_key, queue),
+            Some(session) => {
+                self.sessions.insert(conn.addr, session);
+                return Err(auth_error("Unexpected session accept"));
+            }
+            None => return Err(auth_error("Unexpected session accept")),
+        };
+
+        let (peer_public_key, confirmation) = data.split_at(PUBLIC_KEY_LENGTH);
+        let mut keys = self.derive_keys(private_key, peer_public_key, &public_key, true)?;
+
+        // Fails when the peer used another room password, nothing queued for it can be sent
+        if let Err(err) = keys.open(confirmation) {
+            let lost = auth_error("Peer failed the room password check");
+            events.push(NetworkEvent::SendError(SendError::for_messages(conn.addr, &queue, lost)));
+            return Err(err);
+        }
+
+        let confirm = frame(SESSION_CONFIRM, &[&keys.seal(&[])?]);
+        self.inner.send(&control_message_to(conn, confirm))?;
+
+        self.send_queued(&mut keys, queue, events);
+        self.sessions.insert(conn.addr, Session::Established(keys));
+        Ok(())
+    }
+
+    /// Opens a sealed packet, confirming the session if this is the first one from the peer.
+    fn open(&mut self, conn: Connection, sealed: &[u8], events: &mut Vec<NetworkEvent>) -> Result<Bytes, NetworkError> {
+        let session = self.sessions.remove(&conn.addr);
+
+        let (mut keys, pending) = match session {
+            Some(Session::Accepting { keys, queue, previous }) => (keys, Some((queue, previous))),
+            Some(Session::Established(keys)) => (keys, None),
+            Some(session) => {
+                self.sessions.insert(conn.addr, session);
+                return Err(auth_error("Received a sealed packet before the session was accepted"));
+            }
+            None => return Err(auth_error("Received a sealed packet outside of a session")),
+        };
+
+        let result = keys.open(sealed);
+
+        match (result, pending) {
+            (Ok(message), Some((queue, _))) => {
+                self.send_queued(&mut keys, queue, events);
+                self.sessions.insert(conn.addr, Session::Established(keys));
+                Ok(message)
+            }
+            (Err(err), Some((queue, Some(mut previous)))) => match previous.open(sealed) {
+                // The peer still holds the old session, so the `HELLO` was not its own
+                Ok(message) => {
+                    warn!("Ignoring session hello from {}, the established session is still in use", conn);
+                    self.send_queued(&mut previous, queue, events);
+                    self.sessions.insert(conn.addr, Session::Established(previous));
+                    Ok(message)
+                }
+                Err(_) => {
+                    let previous = Some(previous);
+                    self.sessions.insert(conn.addr, Session::Accepting { keys, queue, previous });
+                    Err(err)
+                }
+            },
+            (Err(err), Some((queue, None))) => {
+                let previous = None;
+                self.sessions.insert(conn.addr, Session::Accepting { keys, queue, previous });
+                Err(err)
+            }
+            (result, None) => {
+                self.sessions.insert(conn.addr, Session::Established(keys));
+                result
+            }
+        }
+    }
+
+    /// Sends the messages queued while the session was being established.
+    fn send_queued(
+        &mut self,
+        keys: &mut SessionKeys,
+        queue: Vec<MessageWithDestination>,
+        events: &mut Vec<NetworkEvent>,
+    ) {
+        for message in queue {
+            let result = keys.seal(&message.message).and_then(|sealed| {
+                self.inner.send(&MessageWithDestination {
+                    message: frame(SESSION_DATA, &[&sealed]),
+                    ..message.clone()
+                })
+            });
+
+            if let Err(err) = result {
+                events.push(NetworkEvent::SendError(SendError::for_message(&message, err)));
+            }
+        }
+    }
+
+    fn derive_keys(
+        &self,
+        private_key: EphemeralPrivateKey,
+        peer_public_key: &[u8],
+        public_key: &[u8],
+        is_initiator: bool,
+    ) -> Result<SessionKeys, NetworkError> {
+        let (initiator_key, responder_key) = match is_initiator {
+            true => (public_key, peer_public_key),
+            false => (peer_public_key, public_key),
+        };
+
+        let salt = hkdf::Salt::new(hkdf::HKDF_SHA256, &self.password_key);
+        let peer_public_key = UnparsedPublicKey::new(&X25519, peer_public_key);
+
+        let (initiator_seal, responder_seal) = agreement::agree_ephemeral(
+            private_key,
+            &peer_public_key,
+            ring::error::Unspecified,
+            |shared_secret| {
+                let prk = salt.extract(shared_secret);
+                let key = |label: &[u8]| -> Result<UnboundKey, ring::error::Unspecified> {
+                    let info = [label, initiator_key, responder_key];
+                    Ok(prk.expand(&info, &CHACHA20_POLY1305)?.into())
+                };
+
+                Ok((key(b"initiator")?, key(b"responder")?))
+            },
+        )
+        .map_err(crypto_error)?;
+
+        let (seal_key, open_key) = match is_initiator {
+            true => (initiator_seal, responder_seal),
+            false => (responder_seal, initiator_seal),
+        };
+
+        Ok(SessionKeys {
+            seal_key: LessSafeKey::new(seal_key),
+            open_key: LessSafeKey::new(open_key),
+            send_counter: 0,
+            replay: ReplayWindow::default(),
+        })
+    }
+}
+
+impl WorkerSocket for SecureSocket {
+    fn send(&mut self, message: &MessageWithDestination) -> Result<(), NetworkError> {
+        match self.sessions.get_mut(&message.destination) {
+            Some(Session::Established(keys)) => {
+                let sealed = frame(SESSION_DATA, &[&keys.seal(&message.message)?]);
+                self.inner.send(&MessageWithDestination {
+                    message: sealed,
+                    ..message.clone()
+                })
+            }
+            Some(Session::Initiating { queue, .. }) | Some(Session::Accepting { queue, .. }) => {
+                queue.push(message.clone());
+                Ok(())
+            }
+            None => self.start_session(message, vec![message.clone()]),
+        }
+    }
+
+    fn poll(&mut self, now: Instant, handle: SocketHandle, events: &mut Vec<NetworkEvent>) {
+        let mut inner_events = Vec::new();
+        self.inner.poll(now, handle, &mut inner_events);
+
+        for event in inner_events {
+            match event {
+                NetworkEvent::Message(conn, data) => {
+                    if let Err(err) = self.on_packet(conn, data, events) {
+                        warn!("Dropping packet from {}: {}", conn, err);
+                        events.push(NetworkEvent::AuthenticationFailed(conn));
+                    }
+                }
+                NetworkEvent::Disconnected(conn) => {
+                    self.sessions.remove(&conn.addr);
+                    events.push(NetworkEvent::Disconnected(conn));
+                }
+                event => events.push(event),
+            }
+        }
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.inner.next_deadline()
+    }
+
+    fn disconnect(&mut self, addr: SocketAddr) {
+        self.sessions.remove(&addr);
+        self.inner.disconnect(addr);
+    }
+}
+
+impl SessionKeys {
+    fn seal(&mut self, message: &[u8]) -> Result<Vec<u8>, NetworkError> {
+        self.send_counter += 1;
+        let counter = self.send_counter.to_be_bytes();
+
+        let mut sealed = message.to_vec();
+        self.seal_key
+            .seal_in_place_append_tag(nonce(&counter), Aad::from(&counter), &mut sealed)
+            .map_err(crypto_error)?;
+
+        Ok([&counter[..], &sealed[..]].concat())
+    }
+
+    fn open(&mut self, sealed: &[u8]) -> Result<Bytes, NetworkError> {
+        if sealed.len() < COUNTER_LENGTH + aead::MAX_TAG_LEN {
+            return Err(auth_error("Sealed packet is too short"));
+        }
+
+        let (counter, ciphertext) = sealed.split_at(COUNTER_LENGTH);
+
+        let mut counter_bytes = [0u8; COUNTER_LENGTH];
+        counter_bytes.copy_from_slice(counter);
+        let counter_value = u64::from_be_bytes(counter_bytes);
+
+        if !self.replay.is_fresh(counter_value) {
+            return Err(auth_error("Replayed packet"));
+        }
+
+        let mut plaintext = ciphertext.to_vec();
+        let length = self
+            .open_key
+            .open_in_place(nonce(&counter_bytes), Aad::from(&counter_bytes), &mut plaintext)
+            .map_err(|_| auth_error("Packet failed authentication"))?
+            .len();
+
+        self.replay.mark(counter_value);
+        plaintext.truncate(length);
+
+        Ok(Bytes::from(plaintext))
+    }
+}
+
+impl ReplayWindow {
+    fn is_fresh(&self, counter: u64) -> bool {
+        if counter > self.highest {
+            return true;
+        }
+
+        let offset = self.highest - counter;
+        offset < 64 && self.seen & (1 << offset) == 0
+    }
+
+    fn mark(&mut self, counter: u64) {
+        if counter > self.highest {
+            let shift = counter - self.highest;
+            self.seen = if shift >= 64 { 0 } else { self.seen << shift };
+            self.seen |= 1;
+            self.highest = counter;
+        } else {
+            self.seen |= 1 << (self.highest - counter);
+        }
+    }
+}
+
+
+fn nonce(counter: &[u8; COUNTER_LENGTH]) -> Nonce {
+    let mut nonce = [0u8; aead::NONCE_LEN];
+    nonce[aead::NONCE_LEN - COUNTER_LENGTH..].copy_from_slice(counter);
+
+    Nonce::assume_unique_for_key(nonce)
+}
+
+fn frame(kind: u8, parts: &[&[u8]]) -> Bytes {
+    let mut data = BytesMut::new();
+    data.put_u8(kind);
+
+    for part in parts {
+        data.put_slice(part);
+    }
+
+    data.freeze()
+}
+
+fn control_message(message: &MessageWithDestination, data: Bytes) -> MessageWithDestination {
+    MessageWithDestination {
+        id: None,
+        message: data,
+        delivery: HANDSHAKE_DELIVERY,
+        socket_handle: message.socket_handle,
+        destination: message.destination,
+    }
+}
+
+fn control_message_to(conn: Connection, data: Bytes) -> MessageWithDestination {
+    MessageWithDestination {
+        id: None,
+        message: data,
+        delivery: HANDSHAKE_DELIVERY,
+        socket_handle: conn.socket,
+        destination: conn.addr,
+    }
+}
+
+fn auth_error(reason: &'static str) -> NetworkError {
+    NetworkError::AuthenticationError(reason)
+}
+
+fn crypto_error(_: ring::error::Unspecified) -> NetworkError {
+    NetworkError::AuthenticationError("Cryptographic operation failed")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MessageId;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn round_trip() {
+        let network = Network::default();
+        let (mut alice, mut bob) = (Peer::new(&network, 1, None), Peer::new(&network, 2, None));
+
+        alice.send(bob.addr, b"Hello");
+        alice.send(bob.addr, b"Anyone there?");
+        pump(&mut alice, &mut bob);
+
+        bob.send(alice.addr, b"Welcome");
+        pump(&mut alice, &mut bob);
+
+        assert_eq!(bob.received, vec![Bytes::from_static(b"Hello"), Bytes::from_static(b"Anyone there?")]);
+        assert_eq!(alice.received, vec![Bytes::from_static(b"Welcome")]);
+        assert_eq!((alice.failures, bob.failures), (0, 0));
+    }
+
+    #[test]
+    fn rejects_tampered_frames() {
+        let network = Network::default();
+        let (mut alice, mut bob) = (Peer::new(&network, 1, None), Peer::new(&network, 2, None));
+
+        alice.send(bob.addr, b"Hello");
+        pump(&mut alice, &mut bob);
+
+        // Every byte is covered, the counter included
+        for index in [1, COUNTER_LENGTH, COUNTER_LENGTH + 1, COUNTER_LENGTH + 3].iter() {
+            alice.send(bob.addr, b"Move pawn");
+            network.tamper(|data| data[*index] ^= 1);
+            pump(&mut alice, &mut bob);
+        }
+
+        assert_eq!(bob.received, vec![Bytes::from_static(b"Hello")]);
+        assert_eq!(bob.failures, 4);
+
+        // The session survives the tampered frames
+        alice.send(bob.addr, b"Move knight");
+        pump(&mut alice, &mut bob);
+
+        assert_eq!(bob.received.last(), Some(&Bytes::from_static(b"Move knight")));
+    }
+
+    #[test]
+    fn rejects_replayed_frames() {
+        let network = Network::default();
+        let (mut alice, mut bob) = (Peer::new(&network, 1, None), Peer::new(&network, 2, None));
+
+        alice.send(bob.addr, b"Hello");
+        pump(&mut alice, &mut bob);
+
+        alice.send(bob.addr, b"Move pawn");
+        let replay = network.packets.lock().unwrap()[0].clone();
+        pump(&mut alice, &mut bob);
+
+        network.packets.lock().unwrap().push(replay);
+        pump(&mut alice, &mut bob);
+
+        assert_eq!(bob.received, vec![Bytes::from_static(b"Hello"), Bytes::from_static(b"Move pawn")]);
+        assert_eq!(bob.failures, 1);
+    }
+
+    #[test]
+    fn rejects_another_password() {
+        let network = Network::default();
+        let mut alice = Peer::new(&network, 1, Some("rook"));
+        let mut bob = Peer::new(&network, 2, Some("bishop"));
+
+        let hello = alice.send(bob.addr, b"Hello");
+        let move_pawn = alice.send(bob.addr, b"Move pawn");
+        pump(&mut alice, &mut bob);
+
+        assert!(bob.received.is_empty());
+        assert_eq!(alice.failures, 1);
+
+        // The app learns which messages never made it
+        assert_eq!(alice.send_errors, vec![(Some(bob.addr), vec![hello, move_pawn])]);
+    }
+
+    #[test]
+    fn keeps_the_session_on_a_forged_hello() {
+        let network = Network::default();
+        let (mut alice, mut bob) = (Peer::new(&network, 1, None), Peer::new(&network, 2, None));
+
+        alice.send(bob.addr, b"Hello");
+        pump(&mut alice, &mut bob);
+
+        // Anyone can send a hello from Alice's address, only Alice can seal for her session
+        let random = SystemRandom::new();
+        let private_key = EphemeralPrivateKey::generate(&X25519, &random).unwrap();
+        let public_key = private_key.compute_public_key().unwrap();
+        network.inject(alice.addr, bob.addr, frame(SESSION_HELLO, &[public_key.as_ref()]));
+        pump(&mut alice, &mut bob);
+
+        // Bob holds his messages until he knows which session Alice has
+        bob.send(alice.addr, b"Are you there?");
+        alice.send(bob.addr, b"Still me");
+        pump(&mut alice, &mut bob);
+
+        assert_eq!(bob.received, vec![Bytes::from_static(b"Hello"), Bytes::from_static(b"Still me")]);
+        assert_eq!(alice.received, vec![Bytes::from_static(b"Are you there?")]);
+
+        bob.send(alice.addr, b"Welcome back");
+        pump(&mut alice, &mut bob);
+
+        assert_eq!(alice.received.last(), Some(&Bytes::from_static(b"Welcome back")));
+    }
+
+    #[test]
+    fn accepts_a_restarted_peer() {
+        let network = Network::default();
+        let (mut alice, mut bob) = (Peer::new(&network, 1, None), Peer::new(&network, 2, None));
+
+        alice.send(bob.addr, b"Hello");
+        pump(&mut alice, &mut bob);
+
+        let mut alice = Peer::new(&network, 1, None);
+        alice.send(bob.addr, b"Hello again");
+        pump(&mut alice, &mut bob);
+
+        bob.send(alice.addr, b"Welcome back");
+        pump(&mut alice, &mut bob);
+
+        assert_eq!(bob.received, vec![Bytes::from_static(b"Hello"), Bytes::from_static(b"Hello again")]);
+        assert_eq!(alice.received, vec![Bytes::from_static(b"Welcome back")]);
+        assert_eq!(bob.failures, 0);
+    }
+
+
+    // ==========================================================================
+    // --- Helpers
+    // ==========================================================================
+    #[derive(Debug, Clone)]
+    struct Packet {
+        from: SocketAddr,
+        to: SocketAddr,
+        data: Bytes,
+    }
+
+    /// Packets in flight between `Wire`s, delivered in order.
+    #[derive(Default, Clone)]
+    struct Network {
+        packets: Arc<Mutex<Vec<Packet>>>,
+    }
+
+    impl Network {
+        fn inject(&self, from: SocketAddr, to: SocketAddr, data: Bytes) {
+            self.packets.lock().unwrap().push(Packet { from, to, data });
+        }
+
+        /// Changes the last packet sent.
+        fn tamper(&self, change: impl FnOnce(&mut Vec<u8>)) {
+            let mut packets = self.packets.lock().unwrap();
+            let packet = packets.last_mut().unwrap();
+
+            let mut data = packet.data.to_vec();
+            change(&mut data);
+            packet.data = Bytes::from(data);
+        }
+    }
+
+    /// The socket under the `SecureSocket`, sending through the `Network`.
+    struct Wire {
+        addr: SocketAddr,
+        network: Network,
+    }
+
+    impl WorkerSocket for Wire {
+        fn send(&mut self, message: &MessageWithDestination) -> Result<(), NetworkError> {
+            self.network.inject(self.addr, message.destination, message.message.clone());
+            Ok(())
+        }
+
+        fn poll(&mut self, _now: Instant, handle: SocketHandle, events: &mut Vec<NetworkEvent>) {
+            let mut packets = self.network.packets.lock().unwrap();
+            let (received, rest) = packets.drain(..).partition(|packet| packet.to == self.addr);
+            *packets = rest;
+
+            for Packet { from, data, .. } in received {
+                let conn = Connection { addr: from, socket: handle };
+                events.push(NetworkEvent::Message(conn, data));
+            }
+        }
+    }
+
+    struct Peer {
+        addr: SocketAddr,
+        handle: SocketHandle,
+        socket: SecureSocket,
+        received: Vec<Bytes>,
+        failures: usize,
+        send_errors: Vec<(Option<SocketAddr>, Vec<MessageId>)>,
+        next_id: u64,
+    }
+
+    impl Peer {
+        fn new(network: &Network, port: u16, room_password: Option<&str>) -> Self {
+            let addr = SocketAddr::from(([127, 0, 0, 1], port));
+            let wire = Wire {
+                addr,
+                network: network.clone(),
+            };
+            let config = EncryptionConfig {
+                room_password: room_password.map(|password| password.to_string()),
+            };
+
+            Peer {
+                addr,
+                handle: SocketHandle::new(),
+                socket: SecureSocket::new(Box::new(wire), config),
+                received: vec![],
+                failures: 0,
+                send_errors: vec![],
+                next_id: 0,
+            }
+        }
+
+        fn send(&mut self, to: SocketAddr, message: &'static [u8]) -> MessageId {
+            self.next_id += 1;
+            let id = MessageId(self.next_id);
+
+            let message = MessageWithDestination {
+                id: Some(id),
+                message: Bytes::from_static(message),
+                delivery: NetworkDelivery::ReliableOrdered(None),
+                socket_handle: self.handle,
+                destination: to,
+            };
+
+            self.socket.send(&message).unwrap();
+            id
+        }
+
+        fn poll(&mut self) {
+            let mut events = vec![];
+            self.socket.poll(Instant::now(), self.handle, &mut events);
+
+            for event in events {
+                match event {
+                    NetworkEvent::Message(_, data) => self.received.push(data),
+                    NetworkEvent::AuthenticationFailed(_) => self.failures += 1,
+                    NetworkEvent::SendError(error) => self.send_errors.push((error.addr, error.messages)),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Polls both peers often enough for a handshake & the messages queued during it to arrive.
+    fn pump(alice: &mut Peer, bob: &mut Peer) {
+        for _ in 0..10 {
+            alice.poll();
+            bob.poll();
+        }
+    }
+}
@@ -0,0 +1,371 @@
+use bytes::Bytes;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use log::warn;
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread,
+    time::Instant,
+};
+
+use super::WorkerSocket;
+use crate::{
+    error::NetworkError,
+    events::{NetworkEvent, SendError},
+    types::{Connection, MessageWithDestination, SocketHandle, TcpConfig},
+    worker::Waker,
+};
+
+/// Every message is prefixed with its length as a big endian u32.
+const LENGTH_PREFIX: usize = 4;
+
+
+/// Listens for incoming streams & opens outgoing ones on the first message to a peer. Every `NetworkDelivery`
+/// is delivered reliably & in order, since that is all TCP offers.
+pub(crate) struct TcpSocket {
+    listener: TcpListener,
+    config: TcpConfig,
+    streams: HashMap<SocketAddr, TcpConnection>,
+    connecting: HashMap<SocketAddr, Vec<MessageWithDestination>>,
+    connect_tx: Sender<(SocketAddr, io::Result<TcpStream>)>,
+    connect_rx: Receiver<(SocketAddr, io::Result<TcpStream>)>,
+    waker: Waker,
+}
+
+struct TcpConnection {
+    stream: TcpStream,
+    read_buf: Vec<u8>,
+    write_buf: Vec<u8>,
+}
+
+impl TcpSocket {
+    pub(crate) fn bind(addr: SocketAddr, config: TcpConfig, waker: Waker) -> Result<Self, NetworkError> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        let (connect_tx, connect_rx) = unbounded();
+
+        Ok(TcpSocket {
+            listener,
+            config,
+            streams: HashMap::new(),
+            connecting: HashMap::new(),
+            connect_tx,
+            connect_rx,
+            waker,
+        })
+    }
+
+    fn add_stream(&mut self, addr: SocketAddr, stream: TcpStream) -> io::Result<()> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(self.config.nodelay)?;
+
+        let mut connection = TcpConnection {
+            stream,
+            read_buf: Vec::new(),
+            write_buf: Vec::new(),
+        };
+
+        for message in self.connecting.remove(&addr).unwrap_or_default() {
+            write_frame(&mut connection.write_buf, &message.message);
+        }
+
+        self.streams.insert(addr, connection);
+        Ok(())
+    }
+
+    fn accept_streams(&mut self, handle: SocketHandle, events: &mut Vec<NetworkEvent>) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, addr)) => match self.add_stream(addr, stream) {
+                    Ok(()) => events.push(NetworkEvent::Connected(Connection { addr, socket: handle })),
+                    Err(err) => events.push(NetworkEvent::SendError(SendError::for_addr(addr, err.into()))),
+                },
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    events.push(NetworkEvent::SendError(SendError::new(err.into())));
+                    break;
+                }
+            }
+        }
+    }
+
+    fn finish_connects(&mut self, handle: SocketHandle, events: &mut Vec<NetworkEvent>) {
+        while let Ok((addr, result)) = self.connect_rx.try_recv() {
+            match result.and_then(|stream| self.add_stream(addr, stream)) {
+                Ok(()) => events.push(NetworkEvent::Connected(Connection { addr, socket: handle })),
+                Err(err) => {
+                    // Messages queued for the peer are lost along with the connection attempt
+                    let lost = self.connecting.remove(&addr).unwrap_or_default();
+                    events.push(NetworkEvent::SendError(SendError::for_messages(addr, &lost, err.into())));
+                }
+            }
+        }
+    }
+}
+
+impl WorkerSocket for TcpSocket {
+    fn send(&mut self, message: &MessageWithDestination) -> Result<(), NetworkError> {
+        let addr = message.destination;
+
+        if message.message.len() > self.config.max_message_size {
+            let error = format!("Message of {} bytes is over the TCP message limit", message.message.len());
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, error).into());
+        }
+
+        if let Some(connection) = self.streams.get_mut(&addr) {
+            write_frame(&mut connection.write_buf, &message.message);
+            return Ok(());
+        }
+
+        // Connect off the worker thread & queue messages until the stream is open
+        if let Some(queue) = self.connecting.get_mut(&addr) {
+            queue.push(message.clone());
+            return Ok(());
+        }
+
+        self.connecting.insert(addr, vec![message.clone()]);
+
+        let connect_tx = self.connect_tx.clone();
+        let waker = self.waker.clone();
+        thread::spawn(move || {
+            let _ = connect_tx.send((addr, TcpStream::connect(addr)));
+            waker.wake();
+        });
+
+        Ok(())
+    }
+
+    fn poll(&mut self, _now: Instant, handle: SocketHandle, events: &mut Vec<NetworkEvent>) {
+        self.accept_streams(handle, events);
+        self.finish_connects(handle, events);
+
+        let max_message_size = self.config.max_message_size;
+        let mut closed = Vec::new();
+
+        for (addr, connection) in self.streams.iter_mut() {
+            let conn = Connection {
+                addr: *addr,
+                socket: handle,
+            };
+
+            let result = connection.flush().and_then(|_| {
+                connection.read(|message| events.push(NetworkEvent::Message(conn, message)), max_message_size)
+            });
+
+            if let Err(err) = result {
+                if err.kind() != io::ErrorKind::UnexpectedEof {
+                    warn!("Closing TCP stream to {}: {}", addr, err);
+                }
+
+                closed.push(*addr);
+            }
+        }
+
+        for addr in closed {
+            self.streams.remove(&addr);
+            events.push(NetworkEvent::Disconnected(Connection { addr, socket: handle }));
+        }
+    }
+
+    fn disconnect(&mut self, addr: SocketAddr) {
+        // Dropping the stream closes it
+        self.streams.remove(&addr);
+    }
+}
+
+impl TcpConnection {
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.write_buf.is_empty() {
+            match self.stream.write(&self.write_buf) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.write_buf.drain(..written);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads everything available & hands each complete message to `on_message`. Fails once the peer has closed
+    /// the stream or sends a message longer than `max_message_size`.
+    fn read(&mut self, mut on_message: impl FnMut(Bytes), max_message_size: usize) -> io::Result<()> {
+        let mut chunk = [0u8; 4096];
+        let mut is_closed = false;
+
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    is_closed = true;
+                    break;
+                }
+                Ok(read) => self.read_buf.extend_from_slice(&chunk[..read]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        while self.read_buf.len() >= LENGTH_PREFIX {
+            let mut length = [0u8; LENGTH_PREFIX];
+            length.copy_from_slice(&self.read_buf[..LENGTH_PREFIX]);
+            let length = u32::from_be_bytes(length) as usize;
+
+            if length > max_message_size {
+                let message = format!("Message of {} bytes is over the {} byte limit", length, max_message_size);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+
+            if self.read_buf.len() < LENGTH_PREFIX + length {
+                break;
+            }
+
+            let frame: Vec<u8> = self.read_buf.drain(..LENGTH_PREFIX + length).collect();
+            on_message(Bytes::copy_from_slice(&frame[LENGTH_PREFIX..]));
+        }
+
+        match is_closed {
+            true => Err(io::ErrorKind::UnexpectedEof.into()),
+            false => Ok(()),
+        }
+    }
+}
+
+
+fn write_frame(buf: &mut Vec<u8>, message: &[u8]) {
+    buf.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    buf.extend_from_slice(message);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{thread, time::Duration};
+
+    const MAX_MESSAGE_SIZE: usize = 64;
+
+    #[test]
+    fn reads_a_frame_split_across_reads() {
+        let (mut writer, mut conn) = connection_pair();
+
+        let mut frame = vec![];
+        write_frame(&mut frame, b"Hello");
+
+        // Split inside the length prefix, then inside the message
+        let mut received = vec![];
+        for part in [&frame[..2], &frame[2..6], &frame[6..]].iter() {
+            assert!(received.is_empty());
+            writer.write_all(part).unwrap();
+            received.extend(read_available(&mut conn).unwrap());
+        }
+
+        assert_eq!(received, vec![Bytes::from_static(b"Hello")]);
+        assert!(conn.read_buf.is_empty());
+    }
+
+    #[test]
+    fn reads_several_frames_in_one_read() {
+        let (mut writer, mut conn) = connection_pair();
+
+        let mut frames = vec![];
+        write_frame(&mut frames, b"One");
+        write_frame(&mut frames, b"");
+        write_frame(&mut frames, b"Three");
+        // The start of a fourth frame stays buffered
+        write_frame(&mut frames, b"Four");
+        frames.truncate(frames.len() - 2);
+        writer.write_all(&frames).unwrap();
+
+        let received = read_available(&mut conn).unwrap();
+
+        assert_eq!(received, vec![Bytes::from_static(b"One"), Bytes::new(), Bytes::from_static(b"Three")]);
+        assert_eq!(conn.read_buf.len(), LENGTH_PREFIX + 2);
+    }
+
+    #[test]
+    fn rejects_oversized_frames() {
+        let (mut writer, mut conn) = connection_pair();
+
+        // Refused on the length alone, before the message arrives
+        let length = (MAX_MESSAGE_SIZE as u32 + 1).to_be_bytes();
+        writer.write_all(&length).unwrap();
+
+        let err = read_available(&mut conn).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn accepts_frames_at_the_limit() {
+        let (mut writer, mut conn) = connection_pair();
+
+        let message = vec![7u8; MAX_MESSAGE_SIZE];
+        let mut frame = vec![];
+        write_frame(&mut frame, &message);
+        writer.write_all(&frame).unwrap();
+
+        assert_eq!(read_available(&mut conn).unwrap(), vec![Bytes::from(message)]);
+    }
+
+    #[test]
+    fn delivers_buffered_frames_before_the_close() {
+        let (mut writer, mut conn) = connection_pair();
+
+        let mut frame = vec![];
+        write_frame(&mut frame, b"Bye");
+        writer.write_all(&frame).unwrap();
+        drop(writer);
+
+        let mut received = vec![];
+        let result = read_until_closed(&mut conn, &mut received);
+
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(received, vec![Bytes::from_static(b"Bye")]);
+    }
+
+
+    // ==========================================================================
+    // --- Helpers
+    // ==========================================================================
+    /// A blocking stream to write with & the non-blocking connection reading from it.
+    fn connection_pair() -> (TcpStream, TcpConnection) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let writer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        writer.set_nodelay(true).unwrap();
+
+        let (stream, _) = listener.accept().unwrap();
+        stream.set_nonblocking(true).unwrap();
+
+        let conn = TcpConnection {
+            stream,
+            read_buf: vec![],
+            write_buf: vec![],
+        };
+
+        (writer, conn)
+    }
+
+    /// Loopback writes may take a moment to become readable, so this waits for the bytes written so far.
+    fn read_available(conn: &mut TcpConnection) -> io::Result<Vec<Bytes>> {
+        let mut received = vec![];
+
+        for _ in 0..20 {
+            conn.read(|message| received.push(message), MAX_MESSAGE_SIZE)?;
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        Ok(received)
+    }
+
+    fn read_until_closed(conn: &mut TcpConnection, received: &mut Vec<Bytes>) -> io::Result<()> {
+        for _ in 0..200 {
+            conn.read(|message| received.push(message), MAX_MESSAGE_SIZE)?;
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,315 @@
+use bytes::Bytes;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use log::warn;
+use std::{
+    collections::HashMap,
+    io,
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread,
+    time::Instant,
+};
+use tungstenite::{
+    handshake::{
+        client::ClientHandshake,
+        server::{NoCallback, ServerHandshake},
+        HandshakeError, MidHandshake,
+    },
+    protocol::WebSocketConfig as ProtocolConfig,
+    Message, WebSocket,
+};
+
+use super::WorkerSocket;
+use crate::{
+    error::NetworkError,
+    events::{NetworkEvent, SendError},
+    types::{Connection, MessageWithDestination, SocketHandle, WebSocketConfig},
+    worker::Waker,
+};
+
+
+enum PendingHandshake {
+    Server(MidHandshake<ServerHandshake<TcpStream, NoCallback>>),
+    Client(MidHandshake<ClientHandshake<TcpStream>>),
+}
+
+/// Accepts WebSocket clients & connects to WebSocket servers on the first message to a peer. Messages travel as
+/// binary frames, so like TCP every `NetworkDelivery` is delivered reliably & in order.
+pub(crate) struct WebSocketSocket {
+    listener: TcpListener,
+    config: WebSocketConfig,
+    sockets: HashMap<SocketAddr, WebSocket<TcpStream>>,
+    handshakes: Vec<(SocketAddr, PendingHandshake)>,
+    queued: HashMap<SocketAddr, Vec<MessageWithDestination>>,
+    connect_tx: Sender<(SocketAddr, io::Result<TcpStream>)>,
+    connect_rx: Receiver<(SocketAddr, io::Result<TcpStream>)>,
+    waker: Waker,
+}
+
+impl WebSocketSocket {
+    pub(crate) fn bind(addr: SocketAddr, config: WebSocketConfig, waker: Waker) -> Result<Self, NetworkError> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        let (connect_tx, connect_rx) = unbounded();
+
+        Ok(WebSocketSocket {
+            listener,
+            config,
+            sockets: HashMap::new(),
+            handshakes: Vec::new(),
+            queued: HashMap::new(),
+            connect_tx,
+            connect_rx,
+            waker,
+        })
+    }
+
+    fn accept_streams(&mut self, handle: SocketHandle, events: &mut Vec<NetworkEvent>) {
+        loop {
+            let (stream, addr) = match self.listener.accept() {
+                Ok(accepted) => accepted,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    events.push(NetworkEvent::SendError(SendError::new(err.into())));
+                    break;
+                }
+            };
+
+            if let Err(err) = prepare_stream(&stream) {
+                events.push(NetworkEvent::SendError(SendError::for_addr(addr, err.into())));
+                continue;
+            }
+
+            match tungstenite::server::accept_with_config(stream, Some(self.protocol_config())) {
+                Ok(socket) => self.open(addr, socket, handle, events),
+                Err(HandshakeError::Interrupted(mid)) => {
+                    self.handshakes.push((addr, PendingHandshake::Server(mid)));
+                }
+                Err(HandshakeError::Failure(err)) => {
+                    warn!("WebSocket handshake with {} failed: {}", addr, err);
+                    self.fail_queued(addr, to_network_error(err), events);
+                }
+            }
+        }
+    }
+
+    fn finish_connects(&mut self, handle: SocketHandle, events: &mut Vec<NetworkEvent>) {
+        while let Ok((addr, result)) = self.connect_rx.try_recv() {
+            let stream = match result.and_then(|stream| prepare_stream(&stream).map(|_| stream)) {
+                Ok(stream) => stream,
+                Err(err) => {
+                    // Messages queued for the peer are lost along with the connection attempt
+                    self.fail_queued(addr, err.into(), events);
+                    continue;
+                }
+            };
+
+            let url = format!("ws://{}{}", addr, self.config.path);
+
+            match tungstenite::client::client_with_config(url.as_str(), stream, Some(self.protocol_config())) {
+                Ok((socket, _response)) => self.open(addr, socket, handle, events),
+                Err(HandshakeError::Interrupted(mid)) => {
+                    self.handshakes.push((addr, PendingHandshake::Client(mid)));
+                }
+                Err(HandshakeError::Failure(err)) => self.fail_queued(addr, to_network_error(err), events),
+            }
+        }
+    }
+
+    fn continue_handshakes(&mut self, handle: SocketHandle, events: &mut Vec<NetworkEvent>) {
+        for (addr, handshake) in std::mem::take(&mut self.handshakes) {
+            let result = match handshake {
+                PendingHandshake::Server(mid) => match mid.handshake() {
+                    Ok(socket) => Ok(socket),
+                    Err(HandshakeError::Interrupted(mid)) => Err(Some(PendingHandshake::Server(mid))),
+                    Err(HandshakeError::Failure(err)) => {
+                        warn!("WebSocket handshake with {} failed: {}", addr, err);
+                        self.fail_queued(addr, to_network_error(err), events);
+                        Err(None)
+                    }
+                },
+                PendingHandshake::Client(mid) => match mid.handshake() {
+                    Ok((socket, _response)) => Ok(socket),
+                    Err(HandshakeError::Interrupted(mid)) => Err(Some(PendingHandshake::Client(mid))),
+                    Err(HandshakeError::Failure(err)) => {
+                        self.fail_queued(addr, to_network_error(err), events);
+                        Err(None)
+                    }
+                },
+            };
+
+            match result {
+                Ok(socket) => self.open(addr, socket, handle, events),
+                Err(Some(handshake)) => self.handshakes.push((addr, handshake)),
+                Err(None) => {}
+            }
+        }
+    }
+
+    /// Drops the messages queued for a peer which couldn't be reached & reports them as lost.
+    fn fail_queued(&mut self, addr: SocketAddr, error: NetworkError, events: &mut Vec<NetworkEvent>) {
+        // Peers which connected to us only have messages queued if the app answered before the handshake was done
+        if let Some(lost) = self.queued.remove(&addr) {
+            events.push(NetworkEvent::SendError(SendError::for_messages(addr, &lost, error)));
+        }
+    }
+
+    /// Frames are limited like messages, tungstenite would otherwise buffer up to 64 MiB before `read()` sees them.
+    fn protocol_config(&self) -> ProtocolConfig {
+        ProtocolConfig {
+            max_message_size: Some(self.config.max_message_size),
+            max_frame_size: Some(self.config.max_message_size),
+            ..Default::default()
+        }
+    }
+
+    fn open(
+        &mut self,
+        addr: SocketAddr,
+        mut socket: WebSocket<TcpStream>,
+        handle: SocketHandle,
+        events: &mut Vec<NetworkEvent>,
+    ) {
+        for message in self.queued.remove(&addr).unwrap_or_default() {
+            if let Err(err) = write(&mut socket, &message.message) {
+                events.push(NetworkEvent::SendError(SendError::for_message(&message, err)));
+            }
+        }
+
+        self.sockets.insert(addr, socket);
+        events.push(NetworkEvent::Connected(Connection { addr, socket: handle }));
+    }
+}
+
+impl WorkerSocket for WebSocketSocket {
+    fn send(&mut self, message: &MessageWithDestination) -> Result<(), NetworkError> {
+        let addr = message.destination;
+
+        if message.message.len() > self.config.max_message_size {
+            let error = format!("Message of {} bytes is over the WebSocket message limit", message.message.len());
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, error).into());
+        }
+
+        if let Some(socket) = self.sockets.get_mut(&addr) {
+            return write(socket, &message.message);
+        }
+
+        // Connect off the worker thread & queue messages until the handshake completes
+        if let Some(queue) = self.queued.get_mut(&addr) {
+            queue.push(message.clone());
+            return Ok(());
+        }
+
+        // The peer connected to us & is still handshaking
+        if self.handshakes.iter().any(|(handshake_addr, _)| *handshake_addr == addr) {
+            self.queued.insert(addr, vec![message.clone()]);
+            return Ok(());
+        }
+
+        self.queued.insert(addr, vec![message.clone()]);
+
+        let connect_tx = self.connect_tx.clone();
+        let waker = self.waker.clone();
+        thread::spawn(move || {
+            let _ = connect_tx.send((addr, TcpStream::connect(addr)));
+            waker.wake();
+        });
+
+        Ok(())
+    }
+
+    fn poll(&mut self, _now: Instant, handle: SocketHandle, events: &mut Vec<NetworkEvent>) {
+        self.accept_streams(handle, events);
+        self.finish_connects(handle, events);
+        self.continue_handshakes(handle, events);
+
+        let max_message_size = self.config.max_message_size;
+        let mut closed = Vec::new();
+
+        for (addr, socket) in self.sockets.iter_mut() {
+            let conn = Connection {
+                addr: *addr,
+                socket: handle,
+            };
+
+            if let Err(err) = read(socket, conn, max_message_size, events) {
+                if !is_closed(&err) {
+                    warn!("Closing WebSocket to {}: {}", addr, err);
+                }
+
+                closed.push(*addr);
+            }
+        }
+
+        for addr in closed {
+            self.sockets.remove(&addr);
+            events.push(NetworkEvent::Disconnected(Connection { addr, socket: handle }));
+        }
+    }
+
+    fn disconnect(&mut self, addr: SocketAddr) {
+        self.handshakes.retain(|(handshake_addr, _)| *handshake_addr != addr);
+
+        // Closing politely is best effort, the stream is dropped either way
+        if let Some(mut socket) = self.sockets.remove(&addr) {
+            let _ = socket.close(None);
+            let _ = socket.write_pending();
+        }
+    }
+}
+
+
+/// Reads every available frame & flushes anything tungstenite still has to write, such as pongs.
+fn read(
+    socket: &mut WebSocket<TcpStream>,
+    conn: Connection,
+    max_message_size: usize,
+    events: &mut Vec<NetworkEvent>,
+) -> Result<(), tungstenite::Error> {
+    loop {
+        match socket.read_message() {
+            Ok(Message::Binary(data)) if data.len() > max_message_size => {
+                return Err(tungstenite::Error::Capacity("Message is over the WebSocket message limit".into()));
+            }
+            Ok(Message::Binary(data)) => events.push(NetworkEvent::Message(conn, Bytes::from(data))),
+            Ok(Message::Close(_)) => return Err(tungstenite::Error::ConnectionClosed),
+            // Text frames aren't part of the protocol, pings & pongs are answered by tungstenite
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => break,
+            Err(err) => return Err(err),
+        }
+    }
+
+    match socket.write_pending() {
+        Err(tungstenite::Error::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => Ok(()),
+        result => result,
+    }
+}
+
+/// Frames that can't be written yet stay queued in tungstenite & are flushed on the next poll.
+fn write(socket: &mut WebSocket<TcpStream>, message: &[u8]) -> Result<(), NetworkError> {
+    match socket.write_message(Message::Binary(message.to_vec())) {
+        Ok(()) => Ok(()),
+        Err(tungstenite::Error::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => Ok(()),
+        Err(err) => Err(to_network_error(err)),
+    }
+}
+
+fn prepare_stream(stream: &TcpStream) -> io::Result<()> {
+    stream.set_nonblocking(true)?;
+    stream.set_nodelay(true)
+}
+
+fn is_closed(err: &tungstenite::Error) -> bool {
+    match err {
+        tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => true,
+        _ => false,
+    }
+}
+
+fn to_network_error(err: tungstenite::Error) -> NetworkError {
+    match err {
+        tungstenite::Error::Io(err) => NetworkError::IOError(err),
+        err => NetworkError::IOError(io::Error::new(io::ErrorKind::Other, err.to_string())),
+    }
+}
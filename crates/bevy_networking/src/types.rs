@@ -1,5 +1,5 @@
 use bytes::Bytes;
-use laminar::{Config, Socket};
+use laminar::Config;
 use std::{
     fmt,
     net::{SocketAddr, ToSocketAddrs},
@@ -7,6 +7,8 @@ use std::{
 };
 use uuid::Uuid;
 
+use super::transport::{MemoryNetwork, WorkerSocket};
+
 
 
 
@@ -36,21 +38,76 @@ impl fmt::Display for Connection {
 
 
 
+/// Identifies the channel a typed message travels on. Each channel carries a single message type and is
+/// sent as the first byte of every packet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ChannelId(pub u8);
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ChannelConfig {
+    pub(crate) id: ChannelId,
+    pub(crate) delivery: NetworkDelivery,
+    pub(crate) type_name: &'static str,
+}
+
+
 #[derive(Default)]
 pub struct SendConfig {
     pub socket: Option<SocketHandle>, // if none, use the default socket
 }
 
-#[derive(Debug)]
+/// Identifies a sent message, so failures reported in a `SendError` can be traced back to it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MessageId(pub(crate) u64);
+
+#[derive(Debug, Clone)]
 pub(crate) struct MessageWithDestination {
+    /// `None` for messages the worker sends itself, such as pings.
+    pub(crate) id: Option<MessageId>,
     pub(crate) message: Bytes,
     pub(crate) delivery: NetworkDelivery,
     pub(crate) socket_handle: SocketHandle,
     pub(crate) destination: SocketAddr,
 }
 
+/// Tunes the worker thread. Insert it as a resource before adding the `NetworkingPlugin` to change the defaults.
+#[derive(Debug, Clone)]
+pub struct WorkerConfig {
+    /// Messages waiting for the worker before `backpressure` applies.
+    pub send_queue_capacity: usize,
+    /// Events waiting for the app before the worker stops reading from its sockets until the app catches up.
+    pub event_queue_capacity: usize,
+    pub backpressure: BackpressurePolicy,
+    /// Longest the worker waits between polls while its sockets are quiet. The worker wakes straight away when the
+    /// app sends & for in-memory transports, so this mostly bounds the delay of packets arriving on UDP, TCP & WebSocket
+    /// sockets.
+    pub max_idle_interval: Duration,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        WorkerConfig {
+            send_queue_capacity: 4096,
+            event_queue_capacity: 4096,
+            backpressure: BackpressurePolicy::Block(Duration::from_millis(100)),
+            max_idle_interval: Duration::from_millis(4),
+        }
+    }
+}
+
+/// What sending does while the worker's send queue is full.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BackpressurePolicy {
+    /// Wait for the worker to make room, failing with `NetworkError::SendQueueFull` after the timeout.
+    Block(Duration),
+    /// Fail with `NetworkError::SendQueueFull` straight away, e.g. to resend later without stalling the frame.
+    Reject,
+}
+
+
 pub(crate) enum WorkerInstructions {
-    AddSocket(SocketHandle, Socket),
+    AddSocket(SocketHandle, Box<dyn WorkerSocket>),
+    CloseSocket(SocketHandle),
     Terminate,
 }
 
@@ -69,12 +126,18 @@ pub enum NetworkDelivery {
 
 pub enum Transport {
     Laminar(LaminarConfig),
+    Memory(MemoryConfig),
+    Tcp(TcpConfig),
+    WebSocket(WebSocketConfig),
 }
 
 pub struct LaminarConfig {
     pub idle_connection_timeout: Duration,
     pub heartbeat_interval: Option<Duration>,
     pub max_packets_in_flight: u16,
+    pub link_conditioner: Option<LinkConditionerConfig>,
+    pub encryption: Option<EncryptionConfig>,
+    pub rate_limit: Option<RateLimitConfig>,
 }
 
 impl Default for LaminarConfig {
@@ -83,6 +146,9 @@ impl Default for LaminarConfig {
             idle_connection_timeout: Duration::from_millis(5000),
             heartbeat_interval: Some(Duration::from_millis(1000)),
             max_packets_in_flight: 1024,
+            link_conditioner: None,
+            encryption: None,
+            rate_limit: None,
         }
     }
 }
@@ -97,3 +163,166 @@ impl From<LaminarConfig> for Config {
         }
     }
 }
+
+
+/// Length-prefixed messages over TCP, for networks which block UDP. Every `NetworkDelivery` is degraded to
+/// reliable & ordered.
+#[derive(Debug, Clone)]
+pub struct TcpConfig {
+    /// Messages larger than this are refused when sending & close the stream when received.
+    pub max_message_size: usize,
+    /// Disables Nagle's algorithm, trading bandwidth for latency.
+    pub nodelay: bool,
+    pub link_conditioner: Option<LinkConditionerConfig>,
+    pub encryption: Option<EncryptionConfig>,
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+impl Default for TcpConfig {
+    fn default() -> Self {
+        TcpConfig {
+            max_message_size: 1 << 20,
+            nodelay: true,
+            link_conditioner: None,
+            encryption: None,
+            rate_limit: None,
+        }
+    }
+}
+
+
+/// Binary WebSocket frames over TCP, for play behind an HTTP reverse proxy. Every `NetworkDelivery` is degraded
+/// to reliable & ordered.
+#[derive(Debug, Clone)]
+pub struct WebSocketConfig {
+    /// Path requested when connecting to a server, e.g. the location the proxy forwards to the game.
+    pub path: String,
+    /// Messages larger than this are refused when sending & close the WebSocket when received.
+    pub max_message_size: usize,
+    pub link_conditioner: Option<LinkConditionerConfig>,
+    pub encryption: Option<EncryptionConfig>,
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        WebSocketConfig {
+            path: "/".into(),
+            max_message_size: 1 << 20,
+            link_conditioner: None,
+            encryption: None,
+            rate_limit: None,
+        }
+    }
+}
+
+
+/// An in-process transport, see `MemoryNetwork`.
+#[derive(Debug, Clone)]
+pub struct MemoryConfig {
+    pub network: MemoryNetwork,
+    /// Delay added to every packet.
+    pub latency: Duration,
+    pub ordering: MemoryOrdering,
+    pub link_conditioner: Option<LinkConditionerConfig>,
+    pub encryption: Option<EncryptionConfig>,
+    pub rate_limit: Option<RateLimitConfig>,
+}
+
+impl MemoryConfig {
+    pub fn new(network: &MemoryNetwork) -> Self {
+        MemoryConfig {
+            network: network.clone(),
+            latency: Duration::from_millis(0),
+            ordering: MemoryOrdering::InOrder,
+            link_conditioner: None,
+            encryption: None,
+            rate_limit: None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MemoryOrdering {
+    /// Packets arrive in the order they were sent.
+    InOrder,
+    /// Packets arriving within the same worker poll are shuffled, deterministically for a given seed.
+    Shuffled(u64),
+}
+
+
+/// Simulates a bad connection on everything a socket sends & receives, so one end is enough to degrade both directions.
+#[derive(Debug, Clone, Default)]
+pub struct LinkConditionerConfig {
+    /// Chance, between 0 and 1, of a packet being lost.
+    pub packet_loss: f32,
+    pub latency: Duration,
+    /// Largest random amount added to or taken from `latency` for each packet.
+    pub jitter: Duration,
+    /// Chance, between 0 and 1, of an unreliable packet arriving twice.
+    pub duplication: f32,
+    /// Seeds the random rolls, so a run can be reproduced.
+    pub seed: u64,
+}
+
+
+/// Encrypts & authenticates everything a socket sends. Both ends must enable it with the same room password.
+#[derive(Clone, Default)]
+pub struct EncryptionConfig {
+    /// Peers with another password fail to establish a session, so it doubles as the password of a private room.
+    /// Without one, sessions are still encrypted but anyone can join.
+    pub room_password: Option<String>,
+}
+
+impl fmt::Debug for EncryptionConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Configs get logged, keep the password out of the logs
+        let room_password = self.room_password.as_ref().map(|_| "<hidden>");
+        f.debug_struct("EncryptionConfig").field("room_password", &room_password).finish()
+    }
+}
+
+
+/// Limits what each peer may send to a socket, so a misbehaving peer can't flood the app. Packets are checked as
+/// they arrive, before they are decrypted.
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// Messages a peer may send per second on average.
+    pub messages_per_sec: f32,
+    /// Messages a peer may send at once after being quiet for a while.
+    pub burst: u32,
+    /// Larger messages are violations regardless of the rate.
+    pub max_message_size: usize,
+    /// Applied to the peer on every violation.
+    pub policy: ViolationPolicy,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            messages_per_sec: 100.0,
+            burst: 200,
+            max_message_size: 64 * 1024,
+            policy: ViolationPolicy::Drop,
+        }
+    }
+}
+
+/// What happens to a peer breaking its `RateLimitConfig`. The offending packet is always dropped.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ViolationPolicy {
+    /// Only drop the packet.
+    Drop,
+    /// Disconnect the peer, which may connect again with a fresh allowance.
+    Disconnect,
+    /// Disconnect the peer & drop everything from its IP address for the duration.
+    Ban(Duration),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Violation {
+    /// The peer sent faster than `messages_per_sec` for longer than `burst` allows.
+    TooManyMessages,
+    /// The peer sent a message of this many bytes, over `max_message_size`.
+    MessageTooLarge(usize),
+}
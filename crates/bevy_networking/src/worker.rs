@@ -1,66 +1,144 @@
-use crossbeam_channel::{unbounded, Receiver, Sender};
-use std::sync::Mutex;
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender, TryRecvError};
+use log::warn;
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Condvar, Mutex,
+};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use bytes::Bytes;
+use super::error::NetworkError;
+use super::events::SendError;
+use super::stats::StatsTracker;
+use super::transport::WorkerSocket;
+use super::{NetworkEvent, NetworkResource, SocketHandle};
+use super::types::{WorkerConfig, WorkerInstructions, MessageWithDestination};
+
+/// Shortest wait between polls once sockets go quiet. Doubled on every idle poll up to
+/// `WorkerConfig::max_idle_interval`.
+///
+/// The app & the in-memory transport wake the worker, but the other transports still have to be polled: laminar keeps
+/// its `UdpSocket` to itself & needs `manual_poll` to time out connections, while TCP & WebSocket streams are read
+/// without blocking. Readiness with mio would only cover the latter, so the backoff stays for all of them, and at
+/// 4ms it costs an idle worker under 1% of a core, see `benches/worker.rs`.
+const MIN_IDLE_INTERVAL: Duration = Duration::from_micros(250);
+
+/// Fails once the `NetworkResource` holding the receiving end has been dropped, at which point the worker stops.
+type EventResult<T = ()> = Result<T, crossbeam_channel::SendError<NetworkEvent>>;
+
+
+/// Wakes the worker from its wait, when the app hands it something or a transport with threads of its own has
+/// something to deliver.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Waker(Arc<WakeSignal>);
+
+#[derive(Debug, Default)]
+pub(crate) struct WakeSignal {
+    pending: AtomicBool,
+    lock: Mutex<()>,
+    condvar: Condvar,
+}
 
-use laminar::{Packet, Socket, SocketEvent};
+impl Waker {
+    pub(crate) fn wake(&self) {
+        // Only the first wake up since the worker last waited needs to notify it, sends are cheap while it is busy
+        if self.0.pending.swap(true, Ordering::SeqCst) {
+            return;
+        }
 
-use super::error::NetworkError;
-use super::{Connection, NetworkEvent, NetworkResource, SocketHandle};
-use super::types::{WorkerInstructions, MessageWithDestination};
+        let _lock = self.0.lock.lock();
+        self.0.condvar.notify_one();
+    }
+
+    /// Blocks until woken or until the timeout passes. A condvar parks the thread straight away, where waiting on the
+    /// channels with a crossbeam `Select` spins & yields first, costing as much CPU as the sleep this replaced.
+    fn wait(&self, timeout: Duration) {
+        if let Ok(lock) = self.0.lock.lock() {
+            let _ = self
+                .0
+                .condvar
+                .wait_timeout_while(lock, timeout, |_| !self.0.pending.load(Ordering::SeqCst));
+        }
+
+        self.0.pending.store(false, Ordering::SeqCst);
+    }
+}
 
-const SEND_EXPECT: &str =
-    "The networking worker thread is no longer able to send messages back to the receiver.";
 
-pub fn start_worker_thread() -> NetworkResource {
-    let (mut event_tx, event_rx) = unbounded::<NetworkEvent>();
-    let (message_tx, message_rx) = unbounded::<MessageWithDestination>();
+pub fn start_worker_thread(config: WorkerConfig) -> NetworkResource {
+    let (mut event_tx, event_rx) = bounded::<NetworkEvent>(config.event_queue_capacity);
+    let (message_tx, message_rx) = bounded::<MessageWithDestination>(config.send_queue_capacity);
     let (instruction_tx, instruction_rx) = unbounded::<WorkerInstructions>();
+    let waker = Waker::default();
 
     let mut sockets = TrackedSockets {
         sockets: Vec::new(),
     };
 
-    let sleep_time = Duration::from_millis(1);
+    let max_idle_interval = config.max_idle_interval.max(MIN_IDLE_INTERVAL);
 
     let resource = NetworkResource {
         default_socket: None,
         bound_sockets: Vec::new(),
         connections: Vec::new(),
+        stats: HashMap::new(),
+        channels: HashMap::new(),
+        next_message_id: AtomicU64::new(0),
+        backpressure: config.backpressure,
+        waker: waker.clone(),
         message_tx: Mutex::new(message_tx),
         event_rx: Mutex::new(event_rx),
         instruction_tx: Mutex::new(instruction_tx),
     };
 
-    let mut start = std::time::Instant::now();
-    let mut end = std::time::Instant::now();
+    let mut stats = StatsTracker::new();
+    let mut idle_interval = MIN_IDLE_INTERVAL;
 
     thread::spawn(move || loop {
-        let millis = start.elapsed().as_millis();
-        if millis > 50 {
-            println!(
-                "warning: thread worker loop took {:.3?} ({:.3?} after sleeping)",
-                start.elapsed(),
-                end.elapsed()
-            );
-        }
+        let start = Instant::now();
 
-        start = Instant::now();
+        let result = handle_instructions(&mut sockets, &instruction_rx, &mut event_tx).and_then(|should_terminate| {
+            if should_terminate {
+                return Ok(None);
+            }
 
-        let should_terminate = handle_instructions(&mut sockets, &instruction_rx);
-        if should_terminate {
-            break;
-        }
-        poll_sockets(&mut sockets);
-        send_messages(&mut sockets, &message_rx, &mut event_tx);
-        receive_messages(&mut sockets, &mut event_tx);
+            let sent = send_messages(&mut sockets, &mut stats, &message_rx, &mut event_tx)?;
+            let received = poll_sockets(&mut sockets, &mut stats, &mut event_tx)?;
+            update_stats(&mut sockets, &mut stats, &mut event_tx)?;
 
-        end = Instant::now();
+            Ok(Some(sent + received))
+        });
 
-        // go dark
-        std::thread::sleep(sleep_time);
+        let activity = match result {
+            Ok(Some(activity)) => activity,
+            Ok(None) => break,
+            Err(_) => {
+                warn!("Network events are no longer received, stopping the worker thread");
+                break;
+            }
+        };
+
+        if start.elapsed() > Duration::from_millis(50) {
+            warn!("Network worker loop took {:.3?}", start.elapsed());
+        }
+
+        // Poll again soon while traffic flows & back off while the sockets are quiet
+        idle_interval = match activity > 0 {
+            true => MIN_IDLE_INTERVAL,
+            false => (idle_interval * 2).min(max_idle_interval),
+        };
+
+        let now = Instant::now();
+        let deadline = sockets
+            .next_deadline()
+            .into_iter()
+            .chain(Some(stats.next_update()))
+            .chain(Some(now + idle_interval))
+            .min()
+            .unwrap_or(now);
+
+        waker.wait(deadline.saturating_duration_since(now));
     });
 
     resource
@@ -69,145 +147,164 @@ pub fn start_worker_thread() -> NetworkResource {
 fn handle_instructions(
     sockets: &mut TrackedSockets,
     instruction_rx: &Receiver<WorkerInstructions>,
-) -> bool {
-    while let Ok(instruction) = instruction_rx.try_recv() {
+    event_tx: &mut Sender<NetworkEvent>,
+) -> EventResult<bool> {
+    loop {
+        let instruction = match instruction_rx.try_recv() {
+            Ok(instruction) => instruction,
+            Err(TryRecvError::Empty) => return Ok(false),
+            // The `NetworkResource` is gone, waiting on the channel would now return straight away
+            Err(TryRecvError::Disconnected) => return Ok(true),
+        };
+
         match instruction {
             WorkerInstructions::AddSocket(handle, socket) => {
                 sockets.add_socket(handle, socket);
-            } // future work: allow manual closing of sockets
-            // WorkerInstructions::CloseSocket(handle) => {
-            //     sockets.close_socket(handle);
-            // }
-            WorkerInstructions::Terminate => return true,
+            }
+            WorkerInstructions::CloseSocket(handle) => {
+                sockets.close_socket(handle);
+                event_tx.send(NetworkEvent::SocketClosed(handle))?;
+            }
+            WorkerInstructions::Terminate => return Ok(true),
         }
     }
+}
+
+fn poll_sockets(
+    sockets: &mut TrackedSockets,
+    stats: &mut StatsTracker,
+    event_tx: &mut Sender<NetworkEvent>,
+) -> EventResult<usize> {
+    let now = Instant::now();
+    let mut received = 0;
+
+    for (handle, socket) in sockets.iter_mut() {
+        let mut events = Vec::new();
+        socket.poll(now, *handle, &mut events);
+        received += events.len();
+
+        for e in events {
+            let (e, pong) = stats.on_event(e, now);
 
-    false
+            let e = match pong.map(|pong| socket.send(&pong).map_err(|err| (pong, err))) {
+                Some(Err((pong, err))) => Some(NetworkEvent::SendError(SendError::for_message(&pong, err))),
+                _ => e,
+            };
+
+            // Blocks while the app is behind, leaving further packets in the OS buffers
+            if let Some(e) = e {
+                event_tx.send(e)?;
+            }
+        }
+    }
+
+    Ok(received)
 }
 
-fn poll_sockets(sockets: &mut TrackedSockets) {
-    for (_, socket) in sockets.iter_mut() {
-        socket.manual_poll(Instant::now());
+/// Pings every connection & publishes its stats, see `StatsTracker`.
+fn update_stats(
+    sockets: &mut TrackedSockets,
+    stats: &mut StatsTracker,
+    event_tx: &mut Sender<NetworkEvent>,
+) -> EventResult {
+    let (pings, events) = stats.update(Instant::now());
+
+    for ping in pings {
+        // A failed ping is counted as lost, so the error isn't worth reporting
+        let _ = sockets
+            .get_socket_mut(ping.socket_handle)
+            .and_then(|socket| socket.send(&ping));
+    }
+
+    for e in events {
+        event_tx.send(e)?;
     }
+
+    Ok(())
 }
 
 fn send_messages(
     sockets: &mut TrackedSockets,
+    stats: &mut StatsTracker,
     message_rx: &Receiver<MessageWithDestination>,
     event_tx: &mut Sender<NetworkEvent>,
-) {
+) -> EventResult<usize> {
+    let mut sent = 0;
+
     while let Ok(message) = message_rx.try_recv() {
-        let handle = message.socket_handle;
+        sent += 1;
 
-        sockets
-            .get_socket_mut(handle)
-            .and_then(|socket| {
-                socket
-                    .send(Packet::reliable_unordered(
-                        message.destination,
-                        message.message.to_vec(),
-                    ))
-                    .map_err(|e| e.into())
-            })
-            .or_else(|err| event_tx.send(NetworkEvent::SendError(err)))
-            // this expect() is OK, since our only way of communicating errors back to the callers through this event channel. If
-            // we can no longer push events back through this channel, it's time to panic.
-            .expect(SEND_EXPECT);
-    }
-}
+        let handle = message.socket_handle;
+        let message = stats.on_send(message);
 
-fn receive_messages(sockets: &mut TrackedSockets, event_tx: &mut Sender<NetworkEvent>) {
-    for (socket_handle, socket) in sockets.iter_mut() {
-        while let Some(event) = socket.recv() {
-            let e = match event {
-                SocketEvent::Connect(addr) => {
-                    println!("!!! SOCKET CONNECT EVENT");
-                    Some(NetworkEvent::Connected(Connection {
-                        addr,
-                        socket: *socket_handle,
-                    }))
-                },
-                SocketEvent::Timeout(addr) => Some(NetworkEvent::Disconnected(Connection {
-                    addr,
-                    socket: *socket_handle,
-                })),
-                SocketEvent::Packet(packet) => Some(NetworkEvent::Message(
-                    Connection {
-                        addr: packet.addr(),
-                        socket: *socket_handle,
-                    },
-                    Bytes::copy_from_slice(packet.payload()),
-                )),
-                SocketEvent::Disconnect(addr) => {
-                    println!("!!! SOCKET DISCONNECT EVENT");
-
-                    Some(NetworkEvent::Disconnected(Connection {
-                        addr,
-                        socket: *socket_handle,
-                    }))
-                },
-            };
+        let result = sockets.get_socket_mut(handle).and_then(|socket| socket.send(&message));
 
-            if let Some(e) = e {
-                // this expect() is OK, since our only way of communicating errors back to the callers through this event channel. If
-                // we can no longer push events back through this channel, it's time to panic.
-                event_tx.send(e).expect(SEND_EXPECT);
-            }
+        if let Err(err) = result {
+            event_tx.send(NetworkEvent::SendError(SendError::for_message(&message, err)))?;
         }
     }
+
+    Ok(sent)
 }
 
 struct TrackedSockets {
-    sockets: Vec<(SocketHandle, Socket)>,
+    sockets: Vec<(SocketHandle, Box<dyn WorkerSocket>)>,
 }
 
 impl TrackedSockets {
-    pub fn iter_mut(&mut self) -> std::slice::IterMut<(SocketHandle, Socket)> {
+    /// The earliest time a socket has something scheduled, like a delayed packet.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.sockets.iter().filter_map(|(_, socket)| socket.next_deadline()).min()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<(SocketHandle, Box<dyn WorkerSocket>)> {
         self.sockets.iter_mut()
     }
 
-    pub fn add_socket(&mut self, handle: SocketHandle, socket: Socket) {
+    pub fn add_socket(&mut self, handle: SocketHandle, socket: Box<dyn WorkerSocket>) {
         if self.has_socket(handle) {
             // todo: communicate socket error back
-            println!(
-                "Warning: attempted to add socket with an existing handle, dropping the new socket"
-            );
+            warn!("Attempted to add socket with an existing handle, dropping the new socket");
             return;
         }
 
         self.sockets.push((handle, socket));
     }
 
-    // pub fn close_socket(&mut self, handle: SocketHandle) {
-    //     let sock = self.sockets.iter().position(|(h, _)| *h == handle);
+    /// Drops the socket, which unbinds its address. Packets still queued for it are discarded.
+    pub fn close_socket(&mut self, handle: SocketHandle) {
+        let sock = self.sockets.iter().position(|(h, _)| *h == handle);
 
-    //     match sock {
-    //         Some(idx) => {
-    //             self.sockets.remove(idx);
-    //         }
-    //         None => {
-    //             println!("Warning: attempting to close a socket that doesn't exist.");
-    //         }
-    //     }
-    // }
+        match sock {
+            Some(idx) => {
+                self.sockets.remove(idx);
+            }
+            None => {
+                warn!("Attempting to close a socket that doesn't exist");
+            }
+        }
+    }
 
     pub fn has_socket(&self, handle: SocketHandle) -> bool {
         self.get_socket(handle).is_ok()
     }
 
-    pub fn get_socket(&self, handle: SocketHandle) -> Result<&Socket, NetworkError> {
+    pub fn get_socket(&self, handle: SocketHandle) -> Result<&(dyn WorkerSocket + 'static), NetworkError> {
         self.sockets
             .iter()
             .find(|(h, _)| handle == *h)
-            .map(|(_, s)| s)
+            .map(|(_, s)| s.as_ref())
             .ok_or(NetworkError::NoSocket(handle))
     }
 
-    pub fn get_socket_mut(&mut self, handle: SocketHandle) -> Result<&mut Socket, NetworkError> {
+    pub fn get_socket_mut(
+        &mut self,
+        handle: SocketHandle,
+    ) -> Result<&mut (dyn WorkerSocket + 'static), NetworkError> {
         self.sockets
             .iter_mut()
             .find(|(h, _)| handle == *h)
-            .map(|(_, s)| s)
+            .map(|(_, s)| s.as_mut())
             .ok_or(NetworkError::NoSocket(handle))
     }
 }
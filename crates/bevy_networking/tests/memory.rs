@@ -0,0 +1,204 @@
+//! A host & a client `App` in the same process, connected through a `MemoryNetwork`.
+use bevy::prelude::*;
+
+use std::{
+    net::SocketAddr,
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy_networking::{
+    events::{ClientConnected, ClientDisconnected, NetworkMessage},
+    AddNetworkMessage, ChannelId, MemoryConfig, MemoryNetwork, NetworkDelivery, NetworkError, NetworkResource,
+    NetworkingPlugin, SocketHandle, Transport,
+};
+
+const SERVER: &str = "127.0.0.1:12351";
+const CLIENT: &str = "127.0.0.1:12350";
+
+/// The worker delivers on its own thread, so every wait is bounded by this rather than a number of updates.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+
+#[test]
+fn delivers_messages_both_ways() {
+    let network = MemoryNetwork::new();
+    let mut server = Peer::bind(&network, SERVER);
+    let mut client = Peer::bind(&network, CLIENT);
+
+    client.send(server.addr, "Hello").unwrap();
+    assert!(update_until(&mut server, &mut client, |server, _| !server.messages.is_empty()));
+
+    let log = server.log();
+    assert_eq!(log.messages, vec![(client.addr, "Hello".to_string())]);
+    assert_eq!(log.connected, vec![client.addr]);
+
+    server.send(client.addr, "Welcome").unwrap();
+    assert!(update_until(&mut server, &mut client, |_, client| !client.messages.is_empty()));
+
+    let log = client.log();
+    assert_eq!(log.messages, vec![(server.addr, "Welcome".to_string())]);
+    assert_eq!(log.connected, vec![server.addr]);
+}
+
+#[test]
+fn keeps_the_order_of_messages() {
+    let network = MemoryNetwork::new();
+    let mut server = Peer::bind(&network, SERVER);
+    let mut client = Peer::bind(&network, CLIENT);
+
+    let sent: Vec<String> = (0..20).map(|i| format!("Message {}", i)).collect();
+    for message in sent.iter() {
+        client.send(server.addr, message).unwrap();
+    }
+
+    assert!(update_until(&mut server, &mut client, |server, _| server.messages.len() == sent.len()));
+
+    let received: Vec<String> = server.log().messages.into_iter().map(|(_, message)| message).collect();
+    assert_eq!(received, sent);
+}
+
+#[test]
+fn close_disconnects_both_ends() {
+    let network = MemoryNetwork::new();
+    let mut server = Peer::bind(&network, SERVER);
+    let mut client = Peer::bind(&network, CLIENT);
+
+    client.send(server.addr, "Hello").unwrap();
+    assert!(update_until(&mut server, &mut client, |server, _| !server.connected.is_empty()));
+
+    server.send(client.addr, "Welcome").unwrap();
+    assert!(update_until(&mut server, &mut client, |_, client| !client.connected.is_empty()));
+
+    client.close();
+    assert!(update_until(&mut server, &mut client, |server, client| {
+        !server.disconnected.is_empty() && !client.disconnected.is_empty()
+    }));
+
+    assert_eq!(server.log().disconnected, vec![client.addr]);
+    assert_eq!(client.log().disconnected, vec![server.addr]);
+    assert!(server.net().connections().is_empty());
+    assert!(client.net().connections().is_empty());
+
+    // The closed socket was the only one, there is nothing left to send from
+    match client.send(server.addr, "Anyone there?") {
+        Err(NetworkError::NoDefaultSocket) => {}
+        other => panic!("expected NoDefaultSocket, got {:?}", other.map(|_| ())),
+    }
+
+    // The address is free again once the socket is gone
+    Peer::bind(&network, CLIENT);
+}
+
+#[test]
+fn close_unknown_socket_fails() {
+    let network = MemoryNetwork::new();
+    let mut client = Peer::bind(&network, CLIENT);
+
+    client.close();
+
+    let socket = client.socket;
+    let result = client.app.resources.get_mut::<NetworkResource>().unwrap().close(socket);
+
+    match result {
+        Err(NetworkError::NoSocket(handle)) => assert_eq!(handle, socket),
+        other => panic!("expected NoSocket, got {:?}", other),
+    }
+}
+
+
+// ==========================================================================
+// --- Helpers
+// ==========================================================================
+/// Everything a peer's `App` received, in order.
+#[derive(Debug, Clone, Default)]
+struct Log {
+    connected: Vec<SocketAddr>,
+    disconnected: Vec<SocketAddr>,
+    messages: Vec<(SocketAddr, String)>,
+}
+
+struct Peer {
+    app: App,
+    addr: SocketAddr,
+    socket: SocketHandle,
+}
+
+impl Peer {
+    fn bind(network: &MemoryNetwork, addr: &str) -> Self {
+        let mut builder = App::build();
+        builder
+            .add_plugins(MinimalPlugins)
+            .add_plugin(NetworkingPlugin)
+            .add_network_message::<String>(ChannelId(0), NetworkDelivery::ReliableOrdered(None))
+            .init_resource::<Log>()
+            .add_system(record_events.system());
+
+        let app = builder.app;
+        let addr = addr.parse().unwrap();
+
+        let socket = {
+            let mut net = app.resources.get_mut::<NetworkResource>().unwrap();
+            net.bind_with_transport(addr, Transport::Memory(MemoryConfig::new(network))).unwrap()
+        };
+
+        Peer { app, addr, socket }
+    }
+
+    fn send(&self, to: SocketAddr, message: &str) -> Result<(), NetworkError> {
+        self.net().send(to, &message.to_string()).map(|_| ())
+    }
+
+    fn close(&mut self) {
+        let socket = self.socket;
+        self.app.resources.get_mut::<NetworkResource>().unwrap().close(socket).unwrap();
+    }
+
+    fn net(&self) -> Ref<'_, NetworkResource> {
+        self.app.resources.get::<NetworkResource>().unwrap()
+    }
+
+    fn log(&self) -> Log {
+        (*self.app.resources.get::<Log>().unwrap()).clone()
+    }
+}
+
+/// Updates both apps until `done` holds for their logs, returns false if it never did.
+fn update_until(server: &mut Peer, client: &mut Peer, done: impl Fn(&Log, &Log) -> bool) -> bool {
+    let start = Instant::now();
+
+    while start.elapsed() < TIMEOUT {
+        server.app.update();
+        client.app.update();
+
+        if done(&server.log(), &client.log()) {
+            return true;
+        }
+
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    false
+}
+
+fn record_events(
+    mut log: ResMut<Log>,
+    mut connected_reader: Local<EventReader<ClientConnected>>,
+    mut disconnected_reader: Local<EventReader<ClientDisconnected>>,
+    mut message_reader: Local<EventReader<NetworkMessage<String>>>,
+    connected_events: Res<Events<ClientConnected>>,
+    disconnected_events: Res<Events<ClientDisconnected>>,
+    message_events: Res<Events<NetworkMessage<String>>>,
+) {
+    for ClientConnected(conn) in connected_reader.iter(&connected_events) {
+        log.connected.push(conn.addr);
+    }
+
+    for ClientDisconnected(conn) in disconnected_reader.iter(&disconnected_events) {
+        log.disconnected.push(conn.addr);
+    }
+
+    for NetworkMessage { conn, message } in message_reader.iter(&message_events) {
+        log.messages.push((conn.addr, message.clone()));
+    }
+}
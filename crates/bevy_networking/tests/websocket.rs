@@ -0,0 +1,231 @@
+//! A `WebSocket` transport `App` against a local tungstenite echo server standing in for the other side.
+use bevy::prelude::*;
+
+use std::{
+    io::Write,
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use bevy_networking::{
+    events::{ClientConnected, ClientDisconnected, NetworkMessage, SendError},
+    AddNetworkMessage, ChannelId, MessageId, NetworkDelivery, NetworkResource, NetworkingPlugin, SocketHandle,
+    Transport, WebSocketConfig,
+};
+use tungstenite::Message;
+
+/// The worker delivers on its own thread, so every wait is bounded by this rather than a number of updates.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+
+#[test]
+fn round_trips_binary_frames() {
+    let echo = EchoServer::spawn(None);
+    let mut peer = Peer::bind("127.0.0.1:12460");
+
+    let sent: Vec<String> = (0..3).map(|i| format!("Message {}", i)).collect();
+    for message in sent.iter() {
+        peer.send(echo.addr, message);
+    }
+
+    assert!(update_until(&mut peer, |log| log.messages.len() == sent.len()));
+
+    let log = peer.log();
+    assert_eq!(log.connected, vec![echo.addr]);
+    assert_eq!(log.messages, sent.iter().map(|message| (echo.addr, message.clone())).collect::<Vec<_>>());
+
+    // Closing the socket ends the connection, which stops the echo server
+    peer.close();
+    let frames = echo.join();
+
+    assert!(frames.len() >= sent.len());
+    assert!(frames.iter().all(|frame| frame.is_binary()));
+}
+
+#[test]
+fn disconnects_when_the_server_closes() {
+    let echo = EchoServer::spawn(Some(1));
+    let mut peer = Peer::bind("127.0.0.1:12461");
+
+    peer.send(echo.addr, "Hello");
+
+    assert!(update_until(&mut peer, |log| !log.disconnected.is_empty()));
+
+    let log = peer.log();
+    assert_eq!(log.connected, vec![echo.addr]);
+    assert_eq!(log.messages, vec![(echo.addr, "Hello".to_string())]);
+    assert_eq!(log.disconnected, vec![echo.addr]);
+
+    echo.join();
+}
+
+#[test]
+fn reports_messages_queued_for_a_failed_handshake() {
+    let addr: SocketAddr = "127.0.0.1:12462".parse().unwrap();
+    let mut peer = Peer::bind("127.0.0.1:12462");
+
+    // Connected, but the WebSocket handshake request is yet to come
+    let mut stream = TcpStream::connect(addr).unwrap();
+    let stream_addr = stream.local_addr().unwrap();
+    update_for(&mut peer, Duration::from_millis(100));
+
+    let id = peer.send(stream_addr, "Welcome");
+    update_for(&mut peer, Duration::from_millis(100));
+
+    stream.write_all(b"NOT A WEBSOCKET REQUEST\r\n\r\n").unwrap();
+
+    assert!(update_until(&mut peer, |log| !log.send_errors.is_empty()));
+    assert_eq!(peer.log().send_errors, vec![(Some(stream_addr), vec![id])]);
+    assert!(peer.log().connected.is_empty());
+}
+
+
+// ==========================================================================
+// --- Helpers
+// ==========================================================================
+/// Echoes every frame of the first connection back, closing it after `close_after` frames if set.
+struct EchoServer {
+    addr: SocketAddr,
+    thread: JoinHandle<Vec<Message>>,
+}
+
+impl EchoServer {
+    fn spawn(close_after: Option<usize>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let thread = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            let mut frames = vec![];
+
+            while let Ok(message) = socket.read_message() {
+                if message.is_close() {
+                    break;
+                }
+
+                frames.push(message.clone());
+                socket.write_message(message).unwrap();
+
+                if close_after == Some(frames.len()) {
+                    socket.close(None).unwrap();
+                }
+            }
+
+            frames
+        });
+
+        EchoServer { addr, thread }
+    }
+
+    /// The frames received, once the connection is over.
+    fn join(self) -> Vec<Message> {
+        self.thread.join().unwrap()
+    }
+}
+
+/// Everything the `App` received, in order.
+#[derive(Debug, Clone, Default)]
+struct Log {
+    connected: Vec<SocketAddr>,
+    disconnected: Vec<SocketAddr>,
+    messages: Vec<(SocketAddr, String)>,
+    send_errors: Vec<(Option<SocketAddr>, Vec<MessageId>)>,
+}
+
+struct Peer {
+    app: App,
+    socket: SocketHandle,
+}
+
+impl Peer {
+    fn bind(addr: &str) -> Self {
+        let mut builder = App::build();
+        builder
+            .add_plugins(MinimalPlugins)
+            .add_plugin(NetworkingPlugin)
+            .add_network_message::<String>(ChannelId(0), NetworkDelivery::ReliableOrdered(None))
+            .init_resource::<Log>()
+            .add_system(record_events.system());
+
+        let app = builder.app;
+
+        let socket = {
+            let mut net = app.resources.get_mut::<NetworkResource>().unwrap();
+            let transport = Transport::WebSocket(WebSocketConfig::default());
+            net.bind_with_transport(addr.parse::<SocketAddr>().unwrap(), transport).unwrap()
+        };
+
+        Peer { app, socket }
+    }
+
+    fn send(&self, to: SocketAddr, message: &str) -> MessageId {
+        let net = self.app.resources.get::<NetworkResource>().unwrap();
+        net.send(to, &message.to_string()).unwrap()
+    }
+
+    fn close(&mut self) {
+        let socket = self.socket;
+        self.app.resources.get_mut::<NetworkResource>().unwrap().close(socket).unwrap();
+    }
+
+    fn log(&self) -> Log {
+        (*self.app.resources.get::<Log>().unwrap()).clone()
+    }
+}
+
+/// Updates the app until `done` holds for its log, returns false if it never did.
+fn update_until(peer: &mut Peer, done: impl Fn(&Log) -> bool) -> bool {
+    let start = Instant::now();
+
+    while start.elapsed() < TIMEOUT {
+        peer.app.update();
+
+        if done(&peer.log()) {
+            return true;
+        }
+
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    false
+}
+
+/// Gives the worker time to get to what was sent or connected.
+fn update_for(peer: &mut Peer, duration: Duration) {
+    let start = Instant::now();
+
+    while start.elapsed() < duration {
+        peer.app.update();
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+fn record_events(
+    mut log: ResMut<Log>,
+    mut connected_reader: Local<EventReader<ClientConnected>>,
+    mut disconnected_reader: Local<EventReader<ClientDisconnected>>,
+    mut message_reader: Local<EventReader<NetworkMessage<String>>>,
+    mut error_reader: Local<EventReader<SendError>>,
+    connected_events: Res<Events<ClientConnected>>,
+    disconnected_events: Res<Events<ClientDisconnected>>,
+    message_events: Res<Events<NetworkMessage<String>>>,
+    error_events: Res<Events<SendError>>,
+) {
+    for ClientConnected(conn) in connected_reader.iter(&connected_events) {
+        log.connected.push(conn.addr);
+    }
+
+    for ClientDisconnected(conn) in disconnected_reader.iter(&disconnected_events) {
+        log.disconnected.push(conn.addr);
+    }
+
+    for NetworkMessage { conn, message } in message_reader.iter(&message_events) {
+        log.messages.push((conn.addr, message.clone()));
+    }
+
+    for error in error_reader.iter(&error_events) {
+        log.send_errors.push((error.addr, error.messages.clone()));
+    }
+}
@@ -0,0 +1,242 @@
+use bevy::prelude::*;
+use bevy_networking::events::NetworkMessage;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use super::{
+    outbox::Outbox,
+    protocol::ProtocolState,
+    GameState, PlayerType,
+};
+
+
+/// Longest chat message the host will relay, in characters.
+pub const MAX_CHAT_MESSAGE_LENGTH: usize = 120;
+
+/// Number of messages a single sender may send within `CHAT_FLOOD_INTERVAL`.
+pub const CHAT_FLOOD_MESSAGES: usize = 5;
+pub const CHAT_FLOOD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Number of lines kept in the chat scrollback.
+pub const CHAT_HISTORY_LENGTH: usize = 100;
+
+
+/// ==========================================================================
+/// Chat Types
+/// ==========================================================================
+/// Chat traffic is relayed through the host, which is the only peer enforcing limits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ChatMessage {
+    /// Sent to the host asking for a message to be relayed.
+    Request(String),
+    /// Sent by the host to every player & spectator: sender name, text.
+    Broadcast(String, String),
+    /// Sent by the host when a request breaks the chat limits.
+    Rejected(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum ChatLine {
+    Message { name: String, text: String },
+    System(String),
+}
+
+
+/// ==========================================================================
+/// Events
+/// ==========================================================================
+/// Send a chat message from the local player.
+#[derive(Debug, Clone)]
+pub struct SendChatEvent(pub String);
+
+
+/// ==========================================================================
+/// Resources
+/// ==========================================================================
+/// Scrollback of every chat line received during the current game.
+#[derive(Debug, Default)]
+pub struct ChatHistory {
+    lines: VecDeque<ChatLine>,
+}
+
+impl ChatHistory {
+    pub fn lines(&self) -> &VecDeque<ChatLine> {
+        &self.lines
+    }
+
+    pub fn push(&mut self, line: ChatLine) {
+        if self.lines.len() == CHAT_HISTORY_LENGTH {
+            self.lines.pop_front();
+        }
+
+        self.lines.push_back(line);
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
+
+/// Host side flood & length limits, tracked per sender. The local player is keyed by `None`.
+#[derive(Debug, Default)]
+pub struct ChatLimiter {
+    sent: HashMap<Option<SocketAddr>, VecDeque<Instant>>,
+}
+
+impl ChatLimiter {
+    pub fn check(&mut self, sender: Option<SocketAddr>, text: &str, now: Instant) -> Result<(), String> {
+        if text.trim().is_empty() {
+            return Err("Message is empty".into());
+        }
+
+        if text.chars().count() > MAX_CHAT_MESSAGE_LENGTH {
+            return Err(format!("Message is longer than {} characters", MAX_CHAT_MESSAGE_LENGTH));
+        }
+
+        let sent = self.sent.entry(sender).or_insert_with(VecDeque::new);
+
+        while let Some(&first) = sent.front() {
+            if now.duration_since(first) < CHAT_FLOOD_INTERVAL {
+                break;
+            }
+
+            sent.pop_front();
+        }
+
+        if sent.len() >= CHAT_FLOOD_MESSAGES {
+            return Err("Too many messages, slow down".into());
+        }
+
+        sent.push_back(now);
+        Ok(())
+    }
+}
+
+
+/// ==========================================================================
+/// Systems
+/// ==========================================================================
+pub fn handle_send_chat_event(
+    mut reader: Local<EventReader<SendChatEvent>>,
+    events: Res<Events<SendChatEvent>>,
+    state: Res<GameState>,
+    mut history: ResMut<ChatHistory>,
+    mut limiter: ResMut<ChatLimiter>,
+    protocol: Res<ProtocolState>,
+    mut outbox: ResMut<Outbox>,
+) {
+    for SendChatEvent(text) in reader.iter(&events) {
+        debug!("handle_send_chat_event() - {:?}", text);
+
+        if !state.connection_info.is_server() {
+            send(&protocol, &mut outbox, &state.remote_addrs(), ChatMessage::Request(text.clone()));
+            continue;
+        }
+
+        match limiter.check(None, text, Instant::now()) {
+            Ok(()) => {
+                let name = state.local_player_info.name.clone();
+                broadcast(&state, &protocol, &mut outbox, &mut history, name, text.clone());
+            }
+            Err(reason) => history.push(ChatLine::System(reason)),
+        }
+    }
+}
+
+pub fn handle_chat_messages(
+    mut reader: Local<EventReader<NetworkMessage<ChatMessage>>>,
+    events: Res<Events<NetworkMessage<ChatMessage>>>,
+    state: Res<GameState>,
+    mut history: ResMut<ChatHistory>,
+    mut limiter: ResMut<ChatLimiter>,
+    protocol: Res<ProtocolState>,
+    mut outbox: ResMut<Outbox>,
+) {
+    for NetworkMessage { conn, message } in reader.iter(&events) {
+        let from = conn.addr;
+
+        if !protocol.is_accepted(&from) {
+            info!("handle_chat_messages() - ignoring chat from {:?} before its handshake", from);
+            continue;
+        }
+
+        match message.clone() {
+            ChatMessage::Request(text) => {
+                if !state.connection_info.is_server() {
+                    continue;
+                }
+
+                let name = match sender_name(&state, from) {
+                    Some(name) => name,
+                    None => {
+                        info!("handle_chat_messages() - ignoring chat from unknown peer {:?}", from);
+                        continue;
+                    }
+                };
+
+                match limiter.check(Some(from), &text, Instant::now()) {
+                    Ok(()) => broadcast(&state, &protocol, &mut outbox, &mut history, name, text),
+                    Err(reason) => send(&protocol, &mut outbox, &[from], ChatMessage::Rejected(reason)),
+                }
+            }
+            // Only the host relays, anyone else could put words in another player's mouth
+            ChatMessage::Broadcast(..) | ChatMessage::Rejected(_) if !is_from_host(&state, from) => {
+                info!("handle_chat_messages() - ignoring chat relayed by {:?}, not the host", from);
+            }
+            ChatMessage::Broadcast(name, text) => {
+                history.push(ChatLine::Message { name, text });
+            }
+            ChatMessage::Rejected(reason) => {
+                history.push(ChatLine::System(reason));
+            }
+        }
+    }
+}
+
+
+// ==========================================================================
+// --- Helpers
+// ==========================================================================
+/// Players & spectators only know the host, every remote player is reached through it.
+fn is_from_host(state: &GameState, from: SocketAddr) -> bool {
+    !state.connection_info.is_server() && state.remote_addrs().contains(&from)
+}
+
+fn sender_name(state: &GameState, from: SocketAddr) -> Option<String> {
+    if state.spectators.contains(&from) {
+        return Some("Spectator".into());
+    }
+
+    state.players.iter().find_map(|(player_type, player_info)| match player_type {
+        PlayerType::Remote(addr) if *addr == from => Some(player_info.name.clone()),
+        _ => None,
+    })
+}
+
+/// Relays a message to every remote player & spectator and adds it to the local scrollback.
+fn broadcast(
+    state: &GameState,
+    protocol: &ProtocolState,
+    outbox: &mut Outbox,
+    history: &mut ChatHistory,
+    name: String,
+    text: String,
+) {
+    let mut recipients = state.remote_addrs();
+    recipients.extend(state.spectators.iter().cloned());
+
+    send(protocol, outbox, &recipients, ChatMessage::Broadcast(name.clone(), text.clone()));
+    history.push(ChatLine::Message { name, text });
+}
+
+/// Sends to every recipient that negotiated the "chat" capability.
+fn send(protocol: &ProtocolState, outbox: &mut Outbox, recipients: &[SocketAddr], message: ChatMessage) {
+    for addr in recipients.iter().filter(|addr| protocol.supports(addr, "chat")) {
+        outbox.send(*addr, message.clone());
+    }
+}
@@ -0,0 +1,334 @@
+use bevy::prelude::*;
+use log::{debug, info, warn};
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use super::{
+    lobby::TimeControl, outbox::Outbox, GameOverReason, GameResult, GameStartedEvent, GameState,
+    Message, Team,
+};
+use crate::prelude::*;
+
+
+/// ==========================================================================
+/// Clocks
+/// ==========================================================================
+/// The time left to each team. Only the clock of the team to move runs, the time it has thought so far is charged
+/// once it moves.
+#[derive(Debug, Clone)]
+pub struct Clocks {
+    time_control: TimeControl,
+    white: Duration,
+    black: Duration,
+    /// When the team to move started its turn, `None` once the clocks are stopped.
+    turn_started: Option<Instant>,
+}
+
+impl Clocks {
+    /// Untimed games have no clocks.
+    pub fn new(time_control: TimeControl, now: Instant) -> Option<Self> {
+        let initial = time_control.initial()?;
+        Self::resume(time_control, initial, initial, now)
+    }
+
+    /// Clocks with the time left to White & Black, the team to move thinking from `now` on.
+    pub fn resume(time_control: TimeControl, white: Duration, black: Duration, now: Instant) -> Option<Self> {
+        time_control.initial()?;
+
+        Some(Self {
+            time_control,
+            white,
+            black,
+            turn_started: Some(now),
+        })
+    }
+
+    pub fn time_control(&self) -> TimeControl {
+        self.time_control
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.turn_started.is_some()
+    }
+
+    /// The time left to `team`, counting the turn so far if it is the team to move.
+    pub fn remaining(&self, team: Team, active_team: Team, now: Instant) -> Duration {
+        let stored = match team {
+            Team::White => self.white,
+            Team::Black => self.black,
+        };
+
+        match self.turn_started {
+            Some(started) if team == active_team => {
+                // The delay is thought away for free, only the time after it counts
+                let thought = now.saturating_duration_since(started);
+                let charged = thought.checked_sub(self.time_control.delay()).unwrap_or_default();
+                stored.checked_sub(charged).unwrap_or_default()
+            }
+            _ => stored,
+        }
+    }
+
+    pub fn is_flagged(&self, active_team: Team, now: Instant) -> bool {
+        self.is_running() && self.remaining(active_team, active_team, now) == Duration::default()
+    }
+
+    /// Charges the team to move for the time it has thought & starts a new turn.
+    pub fn restart_turn(&mut self, active_team: Team, now: Instant) {
+        if !self.is_running() {
+            return;
+        }
+
+        *self.stored_mut(active_team) = self.remaining(active_team, active_team, now);
+        self.turn_started = Some(now);
+    }
+
+    /// Charges the team which just moved & adds the increment, the opponent's clock starts.
+    pub fn complete_move(&mut self, team: Team, now: Instant) {
+        if !self.is_running() {
+            return;
+        }
+
+        let increment = self.time_control.increment();
+        self.restart_turn(team, now);
+        *self.stored_mut(team) += increment;
+    }
+
+    pub fn stop(&mut self, active_team: Team, now: Instant) {
+        self.restart_turn(active_team, now);
+        self.turn_started = None;
+    }
+
+    fn stored_mut(&mut self, team: Team) -> &mut Duration {
+        match team {
+            Team::White => &mut self.white,
+            Team::Black => &mut self.black,
+        }
+    }
+}
+
+
+/// ==========================================================================
+/// Systems
+/// ==========================================================================
+/// Every game starts with full clocks. Spectators wait for the host's, see `handle_clock_sync()`, & loaded games
+/// keep the clocks they were saved with.
+pub fn handle_game_started_event(
+    mut reader: Local<EventReader<GameStartedEvent>>,
+    events: Res<Events<GameStartedEvent>>,
+    mut state: ResMut<GameState>,
+) {
+    for _event in reader.iter(&events) {
+        if state.connection_info.is_spectator() || state.clocks.is_some() {
+            continue;
+        }
+
+        let time_control = state.options.time_control;
+        debug!("handle_game_started_event() - starting clocks: {}", time_control);
+
+        state.clocks = Clocks::new(time_control, Instant::now());
+    }
+}
+
+/// The host decides when a flag falls & tells everyone else.
+pub fn handle_flag_fall(mut state: ResMut<GameState>, mut outbox: ResMut<Outbox>) {
+    if !state.connection_info.is_server() || state.result.is_some() {
+        return;
+    }
+
+    // Only borrowed mutably once a flag falls, so the state does not change every frame
+    let active_team = state.active_team;
+    let is_flagged = state
+        .clocks
+        .as_ref()
+        .map_or(false, |clocks| clocks.is_flagged(active_team, Instant::now()));
+
+    if !is_flagged {
+        return;
+    }
+
+    info!("handle_flag_fall() - {} ran out of time", active_team);
+
+    state.end_game(GameResult {
+        winner: Some(active_team.opponent()),
+        reason: GameOverReason::Timeout,
+    });
+
+    if let Some(game_id) = state.game_id {
+        broadcast(&state, &mut outbox, Message::FlagFell(game_id, active_team));
+    }
+}
+
+
+// ==========================================================================
+// --- Message Handlers
+// ==========================================================================
+pub(super) fn handle_clock_sync(
+    state: &mut ResMut<GameState>,
+    from: SocketAddr,
+    game_id: Id,
+    time_control: TimeControl,
+    white: Duration,
+    black: Duration,
+) {
+    if !is_from_host(state, from, game_id) {
+        warn!("handle_clock_sync() - ignoring clocks from {:?}", from);
+        return;
+    }
+
+    debug!("handle_clock_sync() - white: {:?}, black: {:?}", white, black);
+
+    let is_decided = state.result.is_some();
+
+    // The turn of the team to move starts over from the host's time
+    state.clocks = Clocks::resume(time_control, white, black, Instant::now()).map(|mut clocks| {
+        if is_decided {
+            clocks.turn_started = None;
+        }
+
+        clocks
+    });
+}
+
+pub(super) fn handle_flag_fell(state: &mut ResMut<GameState>, from: SocketAddr, game_id: Id, team: Team) {
+    if !is_from_host(state, from, game_id) {
+        warn!("handle_flag_fell() - ignoring flag fall from {:?}", from);
+        return;
+    }
+
+    info!("handle_flag_fell() - {} ran out of time", team);
+
+    state.end_game(GameResult {
+        winner: Some(team.opponent()),
+        reason: GameOverReason::Timeout,
+    });
+}
+
+
+// ==========================================================================
+// --- Helpers
+// ==========================================================================
+/// The host's clocks as they are now, for the other player & spectators.
+pub(super) fn sync_message(state: &GameState) -> Option<Message> {
+    let game_id = state.game_id?;
+    let clocks = state.clocks.as_ref()?;
+    let now = Instant::now();
+
+    Some(Message::ClockSync(
+        game_id,
+        clocks.time_control(),
+        clocks.remaining(Team::White, state.active_team, now),
+        clocks.remaining(Team::Black, state.active_team, now),
+    ))
+}
+
+/// Hosts send their clocks after every move, so the other clocks do not drift by the time moves take to arrive.
+pub(super) fn send_sync(state: &GameState, outbox: &mut Outbox) {
+    if !state.connection_info.is_server() {
+        return;
+    }
+
+    if let Some(message) = sync_message(state) {
+        broadcast(state, outbox, message);
+    }
+}
+
+/// Sends to the other player & every spectator.
+fn broadcast(state: &GameState, outbox: &mut Outbox, message: Message) {
+    for addr in state.remote_addrs().iter().chain(state.spectators.iter()) {
+        outbox.send(*addr, message.clone());
+    }
+}
+
+fn is_from_host(state: &GameState, from: SocketAddr, game_id: Id) -> bool {
+    !state.connection_info.is_server() && state.game_id == Some(game_id) && state.remote_addrs().contains(&from)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(seconds: u64) -> Duration {
+        Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn untimed_games_have_no_clocks() {
+        assert!(Clocks::new(TimeControl::Unlimited, Instant::now()).is_none());
+    }
+
+    #[test]
+    fn sudden_death_charges_the_team_to_move() {
+        let start = Instant::now();
+        let mut clocks = Clocks::new(TimeControl::SuddenDeath { minutes: 5 }, start).unwrap();
+
+        assert_eq!(clocks.remaining(Team::White, Team::White, start + secs(10)), secs(290));
+        assert_eq!(clocks.remaining(Team::Black, Team::White, start + secs(10)), secs(300));
+
+        clocks.complete_move(Team::White, start + secs(10));
+
+        // Only Black's clock runs now
+        assert_eq!(clocks.remaining(Team::White, Team::Black, start + secs(40)), secs(290));
+        assert_eq!(clocks.remaining(Team::Black, Team::Black, start + secs(40)), secs(270));
+
+        clocks.complete_move(Team::Black, start + secs(40));
+
+        assert_eq!(clocks.remaining(Team::White, Team::White, start + secs(40)), secs(290));
+        assert_eq!(clocks.remaining(Team::Black, Team::White, start + secs(40)), secs(270));
+    }
+
+    #[test]
+    fn sudden_death_flags_once_out_of_time() {
+        let start = Instant::now();
+        let clocks = Clocks::new(TimeControl::SuddenDeath { minutes: 1 }, start).unwrap();
+
+        assert!(!clocks.is_flagged(Team::White, start + secs(59)));
+        assert!(clocks.is_flagged(Team::White, start + secs(60)));
+        assert_eq!(clocks.remaining(Team::White, Team::White, start + secs(90)), Duration::default());
+    }
+
+    #[test]
+    fn increment_is_added_after_every_move() {
+        let start = Instant::now();
+        let mut clocks = Clocks::new(TimeControl::Increment { minutes: 3, seconds: 2 }, start).unwrap();
+
+        clocks.complete_move(Team::White, start + secs(5));
+        assert_eq!(clocks.remaining(Team::White, Team::Black, start + secs(5)), secs(177));
+
+        // Moving quicker than the increment gains time
+        clocks.complete_move(Team::Black, start + secs(6));
+        assert_eq!(clocks.remaining(Team::Black, Team::White, start + secs(6)), secs(181));
+    }
+
+    #[test]
+    fn delay_is_thought_away_for_free() {
+        let start = Instant::now();
+        let mut clocks = Clocks::new(TimeControl::Delay { minutes: 15, seconds: 5 }, start).unwrap();
+
+        assert_eq!(clocks.remaining(Team::White, Team::White, start + secs(3)), secs(900));
+        assert_eq!(clocks.remaining(Team::White, Team::White, start + secs(8)), secs(897));
+
+        // Unused delay is not banked
+        clocks.complete_move(Team::White, start + secs(3));
+        assert_eq!(clocks.remaining(Team::White, Team::Black, start + secs(3)), secs(900));
+
+        clocks.complete_move(Team::Black, start + secs(13));
+        assert_eq!(clocks.remaining(Team::Black, Team::White, start + secs(13)), secs(895));
+    }
+
+    #[test]
+    fn stopped_clocks_keep_their_time() {
+        let start = Instant::now();
+        let mut clocks = Clocks::new(TimeControl::Increment { minutes: 3, seconds: 2 }, start).unwrap();
+
+        clocks.stop(Team::White, start + secs(10));
+        clocks.complete_move(Team::White, start + secs(20));
+
+        assert!(!clocks.is_running());
+        assert!(!clocks.is_flagged(Team::White, start + secs(1000)));
+        assert_eq!(clocks.remaining(Team::White, Team::White, start + secs(1000)), secs(170));
+    }
+}
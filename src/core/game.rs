@@ -1,6 +1,7 @@
-use super::{map::*, unit::*};
+use super::{history::UnitSnapshot, lobby::Variant, map::*, phase::PhaseScoped, ruleset::Ruleset, unit::*};
 use crate::{prelude::*, units::*};
 use bevy::prelude::*;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -11,57 +12,103 @@ use uuid::Uuid;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameDescriptor {
     pub map: MapDescriptor,
-    pub units: Vec<(Team, Unit, Position, Id)>,
+    pub units: Vec<UnitSnapshot>,
 }
 
 impl SpawnWithCommands for GameDescriptor {
     fn spawn_with_commands(self, commands: &mut Commands) -> &mut Commands {
         let GameDescriptor { map, units } = self;
 
-        map.spawn_with_commands(commands).with_children(|commands| {
-            for descriptor in units.into_iter() {
-                commands.spawn(UnitComponents::from(descriptor));
-            }
-        })
+        map.spawn_with_commands(commands)
+            .with(PhaseScoped::GAME)
+            .with_children(|commands| {
+                for snapshot in units.iter() {
+                    commands.spawn(snapshot.components());
+                }
+            })
     }
 }
 
 impl Default for GameDescriptor {
     fn default() -> Self {
+        GameDescriptor::new(Variant::Standard, &Ruleset::default())
+    }
+}
+
+impl GameDescriptor {
+    pub fn new(variant: Variant, ruleset: &Ruleset) -> Self {
         let map = MapDescriptor::default();
         let mut units = Vec::new();
+        let health = Health(ruleset.starting_health);
+
+        let mut add_unit = |team, unit, position: Position| {
+            units.push(UnitSnapshot {
+                id: Id::new(),
+                unit,
+                team,
+                position,
+                health,
+            })
+        };
 
         for x in 0..=7 {
-            units.push((Team::White, Unit::Pawn, (x, 1).into(), Id::new()));
-            units.push((Team::Black, Unit::Pawn, (x, 6).into(), Id::new()));
+            add_unit(Team::White, Unit::Pawn, (x, 1).into());
+            add_unit(Team::Black, Unit::Pawn, (x, 6).into());
         }
 
-        let team = Team::White;
-        let home_row = 0;
-        units.push((team, Unit::Rook, (0, home_row).into(), Id::new()));
-        units.push((team, Unit::Knight, (1, home_row).into(), Id::new()));
-        units.push((team, Unit::Bishop, (2, home_row).into(), Id::new()));
-        units.push((team, Unit::Queen, (3, home_row).into(), Id::new()));
-        units.push((team, Unit::King, (4, home_row).into(), Id::new()));
-        units.push((team, Unit::Bishop, (5, home_row).into(), Id::new()));
-        units.push((team, Unit::Knight, (6, home_row).into(), Id::new()));
-        units.push((team, Unit::Rook, (7, home_row).into(), Id::new()));
-
-        let team = Team::Black;
-        let home_row = 7;
-        units.push((team, Unit::Rook, (0, home_row).into(), Id::new()));
-        units.push((team, Unit::Knight, (1, home_row).into(), Id::new()));
-        units.push((team, Unit::Bishop, (2, home_row).into(), Id::new()));
-        units.push((team, Unit::Queen, (3, home_row).into(), Id::new()));
-        units.push((team, Unit::King, (4, home_row).into(), Id::new()));
-        units.push((team, Unit::Bishop, (5, home_row).into(), Id::new()));
-        units.push((team, Unit::Knight, (6, home_row).into(), Id::new()));
-        units.push((team, Unit::Rook, (7, home_row).into(), Id::new()));
+        let back_rank = match variant {
+            Variant::Standard => STANDARD_BACK_RANK,
+            Variant::Chess960 => chess960_back_rank(&mut rand::thread_rng()),
+        };
+
+        // Black mirrors White, so both kings face each other in every variant
+        for &(team, home_row) in [(Team::White, 0), (Team::Black, 7)].iter() {
+            for (x, &unit) in back_rank.iter().enumerate() {
+                add_unit(team, unit, (x as i32, home_row).into());
+            }
+        }
 
         GameDescriptor { map, units }
     }
 }
 
+const STANDARD_BACK_RANK: [Unit; 8] = [
+    Unit::Rook,
+    Unit::Knight,
+    Unit::Bishop,
+    Unit::Queen,
+    Unit::King,
+    Unit::Bishop,
+    Unit::Knight,
+    Unit::Rook,
+];
+
+/// Bishops on opposite colors & the king somewhere between the rooks, the rest anywhere.
+fn chess960_back_rank(random: &mut impl Rng) -> [Unit; 8] {
+    let mut rank: [Option<Unit>; 8] = [None; 8];
+
+    rank[random.gen_range(0, 4) * 2] = Some(Unit::Bishop);
+    rank[random.gen_range(0, 4) * 2 + 1] = Some(Unit::Bishop);
+
+    for &unit in [Unit::Queen, Unit::Knight, Unit::Knight].iter() {
+        let free: Vec<usize> = (0..8).filter(|&x| rank[x].is_none()).collect();
+        rank[free[random.gen_range(0, free.len())]] = Some(unit);
+    }
+
+    // The three squares left are filled left to right, which puts the king between the rooks
+    let free: Vec<usize> = (0..8).filter(|&x| rank[x].is_none()).collect();
+    for (&x, &unit) in free.iter().zip([Unit::Rook, Unit::King, Unit::Rook].iter()) {
+        rank[x] = Some(unit);
+    }
+
+    let mut back_rank = STANDARD_BACK_RANK;
+    for (x, unit) in rank.iter().enumerate() {
+        back_rank[x] = unit.expect("every square of the back rank is filled");
+    }
+
+    back_rank
+}
+
 // ==========================================================================
 // -- Helper Functions
 // ==========================================================================
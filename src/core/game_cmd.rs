@@ -0,0 +1,411 @@
+use bevy::prelude::*;
+use bevy_networking::events::NetworkMessage;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{iter, net::SocketAddr};
+use strum::Display;
+
+use super::{
+    chat::{ChatHistory, ChatLine},
+    game::GameDescriptor,
+    history::UndoMoveEvent,
+    outbox::Outbox,
+    protocol::ProtocolState,
+    GameOverReason, GameResult, GameStartedEvent, GameState, Map, Message, PlayerInfo, PlayerType, Team, Tile, Unit,
+};
+use crate::prelude::*;
+
+
+/// ==========================================================================
+/// Commands
+/// ==========================================================================
+/// Commands of the local player about the game as a whole, next to the `UnitCmd`s moving the pieces.
+#[derive(Debug, Clone, Copy)]
+pub enum GameCmd {
+    Resign,
+    OfferDraw,
+    /// Takes back the last move, straight away when nobody else has a say & otherwise by asking the opponent.
+    Takeback,
+    OfferRematch,
+    /// Answers the pending offer of the opponent.
+    AcceptOffer,
+    DeclineOffer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+pub enum Offer {
+    #[strum(serialize = "a draw")]
+    Draw,
+    #[strum(serialize = "a takeback")]
+    Takeback,
+    #[strum(serialize = "a rematch")]
+    Rematch,
+}
+
+impl Offer {
+    /// Draws are offered while the game is on, rematches once it is decided. Takebacks are asked for by the team
+    /// which made the last move.
+    fn is_allowed(&self, state: &GameState, from: Team) -> bool {
+        let is_decided = state.result.is_some();
+
+        state.offer.is_none()
+            && match self {
+                Offer::Draw => !is_decided,
+                Offer::Takeback => !is_decided && state.history.last().map_or(false, |record| record.team == from),
+                Offer::Rematch => is_decided,
+            }
+    }
+}
+
+/// An offer waiting for an answer & the team which made it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PendingOffer {
+    pub offer: Offer,
+    pub from: Team,
+}
+
+
+/// ==========================================================================
+/// Systems
+/// ==========================================================================
+pub fn handle_game_cmd(
+    mut commands: Commands,
+    mut reader: Local<EventReader<GameCmd>>,
+    events: Res<Events<GameCmd>>,
+    mut state: ResMut<GameState>,
+    mut outbox: ResMut<Outbox>,
+    mut history: ResMut<ChatHistory>,
+    mut game_started_events: ResMut<Events<GameStartedEvent>>,
+    mut undo_events: ResMut<Events<UndoMoveEvent>>,
+    mut id_map: ResMut<EntityMap<Id>>,
+    mut unit_position_map: ResMut<PositionMap<Unit>>,
+    mut tile_position_map: ResMut<PositionMap<Tile>>,
+    map_query: Query<With<Map, Entity>>,
+) {
+    for cmd in reader.iter(&events) {
+        debug!("handle_game_cmd() {:?}", cmd);
+
+        let game_id = match state.game_id {
+            Some(game_id) if !state.connection_info.is_spectator() => game_id,
+            _ => continue,
+        };
+
+        // In hotseat games this is whoever is at the board
+        let team = state.local_player_info.team;
+
+        match cmd {
+            GameCmd::Resign => {
+                if state.result.is_some() {
+                    continue;
+                }
+
+                send(&state, &mut outbox, Message::Resign(game_id));
+                history.push(ChatLine::System(format!("{} resigned", team)));
+
+                state.end_game(GameResult {
+                    winner: Some(team.opponent()),
+                    reason: GameOverReason::Resignation,
+                });
+            }
+            // Hotseat players take back moves freely, they are sat at the same board
+            GameCmd::Takeback if !state.game_type.is_networked() => {
+                if state.result.is_none() && !state.history.is_empty() {
+                    undo_events.send(UndoMoveEvent);
+                }
+            }
+            GameCmd::OfferDraw | GameCmd::Takeback | GameCmd::OfferRematch => {
+                let offer = match cmd {
+                    GameCmd::OfferDraw => Offer::Draw,
+                    GameCmd::Takeback => Offer::Takeback,
+                    _ => Offer::Rematch,
+                };
+
+                if !offer.is_allowed(&state, team) {
+                    debug!("handle_game_cmd() - {} may not be offered now", offer);
+                    continue;
+                }
+
+                if !state.game_type.is_hotseat() && state.remote_addrs().is_empty() {
+                    history.push(ChatLine::System("Your opponent has left".into()));
+                    continue;
+                }
+
+                send(&state, &mut outbox, Message::Offer(game_id, offer));
+                history.push(ChatLine::System(format!("{} offered {}", team, offer)));
+
+                state.offer = Some(PendingOffer { offer, from: team });
+            }
+            GameCmd::AcceptOffer | GameCmd::DeclineOffer => {
+                let accepted = match cmd {
+                    GameCmd::AcceptOffer => true,
+                    _ => false,
+                };
+
+                // Only offers made to a team played at this machine can be answered here
+                let pending = match state.offer {
+                    Some(pending) if state.is_local_team(pending.from.opponent()) => pending,
+                    _ => continue,
+                };
+
+                state.offer = None;
+                send(&state, &mut outbox, Message::OfferResponse(game_id, accepted));
+
+                if !accepted {
+                    history.push(ChatLine::System(format!("{} declined {}", pending.from.opponent(), pending.offer)));
+                    continue;
+                }
+
+                match pending.offer {
+                    Offer::Draw => state.end_game(GameResult {
+                        winner: None,
+                        reason: GameOverReason::DrawAgreed,
+                    }),
+                    Offer::Takeback => undo_events.send(UndoMoveEvent),
+                    // The host deals the new board, clients wait for `Message::Rematch`
+                    Offer::Rematch if state.connection_info.is_server() => {
+                        start_rematch(
+                            &mut commands,
+                            &mut state,
+                            &mut outbox,
+                            game_id,
+                            &mut id_map,
+                            &mut unit_position_map,
+                            &mut tile_position_map,
+                            &map_query,
+                        );
+                        game_started_events.send(GameStartedEvent);
+                    }
+                    Offer::Rematch => {}
+                }
+            }
+        }
+    }
+}
+
+/// Handles the `Message`s about resignations, offers & rematches, every other `Message` is handled by the `Game`.
+pub fn handle_network_events(
+    mut commands: Commands,
+    mut reader: Local<EventReader<NetworkMessage<Message>>>,
+    events: Res<Events<NetworkMessage<Message>>>,
+    mut state: ResMut<GameState>,
+    mut outbox: ResMut<Outbox>,
+    mut history: ResMut<ChatHistory>,
+    mut game_started_events: ResMut<Events<GameStartedEvent>>,
+    mut undo_events: ResMut<Events<UndoMoveEvent>>,
+    mut id_map: ResMut<EntityMap<Id>>,
+    // Grouped, systems take at most 10 resources
+    (mut unit_position_map, mut tile_position_map): (ResMut<PositionMap<Unit>>, ResMut<PositionMap<Tile>>),
+    protocol: Res<ProtocolState>,
+    map_query: Query<With<Map, Entity>>,
+) {
+    for NetworkMessage { conn, message } in reader.iter(&events) {
+        let from = conn.addr;
+
+        // Already logged by `Game::handle_network_events()`
+        if !protocol.is_accepted(&from) {
+            continue;
+        }
+
+        let game_id = match message {
+            Message::Resign(game_id)
+            | Message::Offer(game_id, _)
+            | Message::OfferResponse(game_id, _)
+            | Message::Rematch(game_id, _, _, _)
+            | Message::UndoMove(game_id) => *game_id,
+            _ => continue,
+        };
+
+        // Only the opponent in the current game has a say
+        let remote_team = match remote_team(&state, from) {
+            Some(team) if state.game_id == Some(game_id) => team,
+            _ => {
+                warn!("handle_network_events() - ignoring {:?} from {:?}", message, from);
+                continue;
+            }
+        };
+
+        match message.clone() {
+            Message::Resign(_) => {
+                history.push(ChatLine::System(format!("{} resigned", remote_team)));
+
+                state.end_game(GameResult {
+                    winner: Some(remote_team.opponent()),
+                    reason: GameOverReason::Resignation,
+                });
+            }
+            Message::Offer(_, offer) => {
+                if !offer.is_allowed(&state, remote_team) {
+                    info!("handle_network_events() - {} may not be offered now", offer);
+                    continue;
+                }
+
+                history.push(ChatLine::System(format!("{} offers {}", remote_team, offer)));
+                state.offer = Some(PendingOffer {
+                    offer,
+                    from: remote_team,
+                });
+            }
+            Message::OfferResponse(_, accepted) => {
+                let pending = match state.offer {
+                    Some(pending) if pending.from != remote_team => pending,
+                    _ => continue,
+                };
+
+                state.offer = None;
+
+                if !accepted {
+                    history.push(ChatLine::System(format!("{} declined {}", remote_team, pending.offer)));
+                    continue;
+                }
+
+                match pending.offer {
+                    Offer::Draw => state.end_game(GameResult {
+                        winner: None,
+                        reason: GameOverReason::DrawAgreed,
+                    }),
+                    Offer::Takeback => undo_events.send(UndoMoveEvent),
+                    Offer::Rematch if state.connection_info.is_server() => {
+                        start_rematch(
+                            &mut commands,
+                            &mut state,
+                            &mut outbox,
+                            game_id,
+                            &mut id_map,
+                            &mut unit_position_map,
+                            &mut tile_position_map,
+                            &map_query,
+                        );
+                        game_started_events.send(GameStartedEvent);
+                    }
+                    Offer::Rematch => {}
+                }
+            }
+            Message::Rematch(_, host, guest, game_descriptor) => {
+                // Spectators aren't told every way a game can end, so they take the host's word for it
+                let is_spectator = state.connection_info.is_spectator();
+                if state.connection_info.is_server() || (state.result.is_none() && !is_spectator) {
+                    warn!("handle_network_events() - unexpected rematch from {:?}", from);
+                    continue;
+                }
+
+                info!("handle_network_events() - rematch, {} plays {}", guest.name, guest.team);
+
+                // Spectators reach both players through the host, like in `Message::SpectateResponse`
+                let players = match is_spectator {
+                    true => vec![(PlayerType::Remote(from), host), (PlayerType::Remote(from), guest)],
+                    false => vec![(PlayerType::Local, guest), (PlayerType::Remote(from), host)],
+                };
+                restart(
+                    &mut commands,
+                    &mut state,
+                    players,
+                    game_descriptor,
+                    &mut id_map,
+                    &mut unit_position_map,
+                    &mut tile_position_map,
+                    &map_query,
+                );
+                game_started_events.send(GameStartedEvent);
+            }
+            // Hosts relay takebacks to their spectators, see `spectator::forward_undo_to_spectators()`
+            Message::UndoMove(_) if state.connection_info.is_spectator() => {
+                undo_events.send(UndoMoveEvent);
+            }
+            _ => {}
+        }
+    }
+}
+
+
+// ==========================================================================
+// --- Helpers
+// ==========================================================================
+fn remote_team(state: &GameState, from: SocketAddr) -> Option<Team> {
+    state.players.iter().find_map(|(player_type, player_info)| match player_type {
+        PlayerType::Remote(addr) if *addr == from => Some(player_info.team),
+        _ => None,
+    })
+}
+
+/// Sends to the opponent, hotseat games have nobody to tell.
+fn send(state: &GameState, outbox: &mut Outbox, message: Message) {
+    for addr in state.remote_addrs() {
+        outbox.send(addr, message.clone());
+    }
+}
+
+fn swap_teams(players: &[(PlayerType, PlayerInfo)]) -> Vec<(PlayerType, PlayerInfo)> {
+    players
+        .iter()
+        .map(|(player_type, player_info)| {
+            let player_info = PlayerInfo {
+                team: player_info.team.opponent(),
+                ..player_info.clone()
+            };
+
+            (player_type.clone(), player_info)
+        })
+        .collect()
+}
+
+/// Hosts deal the rematch with swapped teams & send it to the guest, seated like in `Message::JoinResponse`, & to
+/// every spectator, who keep watching.
+fn start_rematch(
+    commands: &mut Commands,
+    state: &mut GameState,
+    outbox: &mut Outbox,
+    game_id: Id,
+    id_map: &mut EntityMap<Id>,
+    unit_position_map: &mut PositionMap<Unit>,
+    tile_position_map: &mut PositionMap<Tile>,
+    map_query: &Query<With<Map, Entity>>,
+) {
+    let game_descriptor = GameDescriptor::new(state.options.variant, &state.options.ruleset);
+    let players = swap_teams(&state.players);
+
+    let host = players.iter().find(|(player_type, _)| player_type.is_local());
+    let guest = players.iter().find(|(player_type, _)| !player_type.is_local());
+
+    if let (Some((_, host)), Some((PlayerType::Remote(addr), guest))) = (host, guest) {
+        let message = Message::Rematch(game_id, host.clone(), guest.clone(), game_descriptor.clone());
+
+        for addr in iter::once(addr).chain(state.spectators.iter()) {
+            outbox.send(*addr, message.clone());
+        }
+    }
+
+    info!("start_rematch() - {:?}", players);
+    restart(
+        commands,
+        state,
+        players,
+        game_descriptor,
+        id_map,
+        unit_position_map,
+        tile_position_map,
+        map_query,
+    );
+}
+
+/// Replaces the board with a fresh one & seats the players for another game.
+fn restart(
+    commands: &mut Commands,
+    state: &mut GameState,
+    players: Vec<(PlayerType, PlayerInfo)>,
+    game_descriptor: GameDescriptor,
+    id_map: &mut EntityMap<Id>,
+    unit_position_map: &mut PositionMap<Unit>,
+    tile_position_map: &mut PositionMap<Tile>,
+    map_query: &Query<With<Map, Entity>>,
+) {
+    for entity in map_query.iter() {
+        commands.despawn_recursive(entity);
+    }
+
+    id_map.clear();
+    unit_position_map.clear();
+    tile_position_map.clear();
+
+    state.restart(players);
+    game_descriptor.spawn_with_commands(commands);
+}
@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+use derive_more::From;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    unit::{ActionResult, Actions, Health, Team, Unit, UnitComponents},
+    GameState, Map,
+};
+use crate::prelude::*;
+
+
+/// ==========================================================================
+/// Move History
+/// ==========================================================================
+/// A unit as it was before a move touched it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct UnitSnapshot {
+    pub id: Id,
+    pub unit: Unit,
+    pub team: Team,
+    pub position: Position,
+    pub health: Health,
+}
+
+impl UnitSnapshot {
+    pub fn components(&self) -> UnitComponents {
+        UnitComponents {
+            health: self.health,
+            ..UnitComponents::from((self.team, self.unit, self.position, self.id))
+        }
+    }
+}
+
+/// Every unit a move changed, so the move can be reverted, captures included.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveRecord {
+    pub team: Team,
+    pub units: Vec<UnitSnapshot>,
+}
+
+/// The moves of the current game, oldest first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, From)]
+pub struct MoveHistory {
+    moves: Vec<MoveRecord>,
+}
+
+impl MoveHistory {
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    pub fn last(&self) -> Option<&MoveRecord> {
+        self.moves.last()
+    }
+
+    pub fn moves(&self) -> &[MoveRecord] {
+        &self.moves
+    }
+
+    /// Whether no move has touched the square, neither moving a unit off it nor hitting a unit on it.
+    pub fn is_untouched(&self, position: &Position) -> bool {
+        self.moves
+            .iter()
+            .all(|record| record.units.iter().all(|unit| unit.position != *position))
+    }
+
+    /// Snapshots every unit the results of a move are about to change.
+    pub fn record(
+        &mut self,
+        team: Team,
+        results: &[ActionResult],
+        query: &Query<(&Unit, &Position, &Team, &Health, &Id)>,
+    ) {
+        let mut units: Vec<UnitSnapshot> = vec![];
+
+        for result in results.iter() {
+            let entity = match result {
+                ActionResult::SetPosition(entity, _)
+                | ActionResult::SetHealth(entity, _)
+                | ActionResult::Promote(entity, _) => *entity,
+            };
+
+            let snapshot = match query.get(entity) {
+                Ok((&unit, &position, &team, &health, &id)) => UnitSnapshot {
+                    id,
+                    unit,
+                    team,
+                    position,
+                    health,
+                },
+                Err(_) => {
+                    warn!("MoveHistory::record() - {:?} is not a unit", entity);
+                    continue;
+                }
+            };
+
+            if units.iter().all(|unit| unit.id != snapshot.id) {
+                units.push(snapshot);
+            }
+        }
+
+        self.moves.push(MoveRecord { team, units });
+    }
+
+    fn pop(&mut self) -> Option<MoveRecord> {
+        self.moves.pop()
+    }
+}
+
+
+/// ==========================================================================
+/// Events
+/// ==========================================================================
+/// Reverts the last move of the current game. Fired once both players agreed to it, or straight away when both
+/// players sit at this machine.
+#[derive(Debug, Clone)]
+pub struct UndoMoveEvent;
+
+
+/// ==========================================================================
+/// Systems
+/// ==========================================================================
+/// Puts the units of the last move back as they were & hands the turn back to whoever made the move.
+pub fn handle_undo_move_event(
+    mut commands: Commands,
+    mut reader: Local<EventReader<UndoMoveEvent>>,
+    events: Res<Events<UndoMoveEvent>>,
+    mut state: ResMut<GameState>,
+    id_map: Res<EntityMap<Id>>,
+    map_query: Query<With<Map, Entity>>,
+    mut unit_query: Query<(&mut Unit, &mut Position, &mut Health, &mut Actions)>,
+) {
+    for _event in reader.iter(&events) {
+        let record = match state.history.pop() {
+            Some(record) => record,
+            None => continue,
+        };
+
+        info!("handle_undo_move_event() - undoing move of {}", record.team);
+
+        for snapshot in record.units {
+            // Captured units were despawned & come back as new entities, the `Id` stays the same
+            if let Some(entity) = id_map.get(&snapshot.id) {
+                if let Ok((mut unit, mut position, mut health, mut actions)) = unit_query.get_mut(*entity) {
+                    *position = snapshot.position;
+                    *health = snapshot.health;
+
+                    // Promoted pawns go back to being pawns
+                    if *unit != snapshot.unit {
+                        *unit = snapshot.unit;
+                        *actions = snapshot.components().actions;
+                    }
+
+                    continue;
+                }
+            }
+
+            debug!("handle_undo_move_event() - respawning {:?}", snapshot);
+            commands.spawn(snapshot.components());
+
+            // Units live on the map, so they go with the board
+            if let (Some(unit_entity), Some(map_entity)) = (commands.current_entity(), map_query.iter().next()) {
+                commands.push_children(map_entity, &[unit_entity]);
+            }
+        }
+
+        state.undo_turn(record.team);
+    }
+}
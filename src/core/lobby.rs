@@ -0,0 +1,376 @@
+use bevy::prelude::*;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{fmt, net::SocketAddr, time::Duration};
+use strum::Display;
+
+use super::{
+    outbox::Outbox, protocol::ProtocolState, ruleset::Ruleset, GameOverReason, GameResult, GameState, Message,
+    PlayerInfo, Team,
+};
+use crate::prelude::*;
+
+
+/// ==========================================================================
+/// Lobby Types
+/// ==========================================================================
+/// Options chosen by the host when a game is created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameOptions {
+    pub host_team: TeamChoice,
+    pub time_control: TimeControl,
+    pub variant: Variant,
+    pub ruleset: Ruleset,
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        GameOptions {
+            host_team: TeamChoice::White,
+            time_control: TimeControl::Unlimited,
+            variant: Variant::Standard,
+            ruleset: Ruleset::default(),
+        }
+    }
+}
+
+/// The team a player would like to play. Random is settled when the second player joins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+pub enum TeamChoice {
+    White,
+    Black,
+    Random,
+}
+
+impl TeamChoice {
+    pub const ALL: [TeamChoice; 3] = [TeamChoice::White, TeamChoice::Black, TeamChoice::Random];
+
+    pub fn team(&self) -> Option<Team> {
+        match self {
+            TeamChoice::White => Some(Team::White),
+            TeamChoice::Black => Some(Team::Black),
+            TeamChoice::Random => None,
+        }
+    }
+}
+
+/// Seats the host & a joining player, returning their teams in that order. A host that picked a team always gets
+/// it, the joining player's choice only settles a random host & a coin flip settles the rest.
+pub fn assign_teams(host: TeamChoice, guest: TeamChoice) -> (Team, Team) {
+    let host_team = match (host.team(), guest.team()) {
+        (Some(team), _) => team,
+        (None, Some(team)) => team.opponent(),
+        (None, None) if rand::random::<bool>() => Team::White,
+        (None, None) => Team::Black,
+    };
+
+    (host_team, host_team.opponent())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeControl {
+    Unlimited,
+    /// Minutes per player for the whole game.
+    SuddenDeath { minutes: u32 },
+    /// Minutes per player, plus seconds added after every move.
+    Increment { minutes: u32, seconds: u32 },
+    /// Minutes per player, the clock only starts once the first seconds of every move have passed.
+    Delay { minutes: u32, seconds: u32 },
+}
+
+impl TimeControl {
+    /// Offered on the game options screen.
+    pub const PRESETS: [TimeControl; 6] = [
+        TimeControl::Unlimited,
+        TimeControl::SuddenDeath { minutes: 5 },
+        TimeControl::Increment { minutes: 3, seconds: 2 },
+        TimeControl::Increment { minutes: 10, seconds: 5 },
+        TimeControl::Delay { minutes: 15, seconds: 5 },
+        TimeControl::SuddenDeath { minutes: 30 },
+    ];
+
+    /// The time each player starts with, `None` for untimed games.
+    pub fn initial(&self) -> Option<Duration> {
+        match self {
+            TimeControl::Unlimited => None,
+            TimeControl::SuddenDeath { minutes }
+            | TimeControl::Increment { minutes, .. }
+            | TimeControl::Delay { minutes, .. } => Some(Duration::from_secs(*minutes as u64 * 60)),
+        }
+    }
+
+    pub fn increment(&self) -> Duration {
+        match self {
+            TimeControl::Increment { seconds, .. } => Duration::from_secs(*seconds as u64),
+            _ => Duration::default(),
+        }
+    }
+
+    pub fn delay(&self) -> Duration {
+        match self {
+            TimeControl::Delay { seconds, .. } => Duration::from_secs(*seconds as u64),
+            _ => Duration::default(),
+        }
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeControl::Unlimited => write!(f, "Unlimited"),
+            TimeControl::SuddenDeath { minutes } => write!(f, "{} min", minutes),
+            TimeControl::Increment { minutes, seconds } => write!(f, "{}+{}", minutes, seconds),
+            TimeControl::Delay { minutes, seconds } => write!(f, "{} min d{}", minutes, seconds),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+pub enum Variant {
+    Standard,
+    /// Back ranks are shuffled, see `GameDescriptor::new`.
+    Chess960,
+}
+
+impl Variant {
+    pub const ALL: [Variant; 2] = [Variant::Standard, Variant::Chess960];
+}
+
+/// A game as advertised by the host to other clients. Started games can no longer be joined, only spectated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LobbyGame {
+    pub id: Id,
+    pub host: PlayerInfo,
+    pub options: GameOptions,
+    pub started: bool,
+}
+
+
+/// ==========================================================================
+/// Events
+/// ==========================================================================
+/// Request the list of open games from the host at `server_addr`.
+#[derive(Debug, Clone)]
+pub struct RefreshLobbyEvent {
+    pub server_addr: SocketAddr,
+}
+
+/// Fired when a host responds with its list of open games.
+#[derive(Debug, Clone)]
+pub struct LobbyGamesReceived {
+    pub server_addr: SocketAddr,
+    pub games: Vec<LobbyGame>,
+}
+
+/// Fired when a host refuses a `JoinRequest`.
+#[derive(Debug, Clone)]
+pub struct JoinRejectedEvent {
+    pub server_addr: SocketAddr,
+    pub reason: String,
+}
+
+/// Leave the current game. Hosts stop advertising the game, clients notify the host.
+#[derive(Debug, Clone)]
+pub struct LeaveGameEvent;
+
+
+/// ==========================================================================
+/// Lobby Resource
+/// ==========================================================================
+/// Games currently hosted by this process.
+#[derive(Debug, Default)]
+pub struct Lobby {
+    games: Vec<LobbyGame>,
+}
+
+impl Lobby {
+    pub fn games(&self) -> &Vec<LobbyGame> {
+        &self.games
+    }
+
+    pub fn get(&self, id: &Id) -> Option<&LobbyGame> {
+        self.games.iter().find(|game| game.id == *id)
+    }
+
+    pub fn open_game(&mut self, game: LobbyGame) {
+        debug!("Lobby::open_game() {:?}", game);
+        self.games.push(game);
+    }
+
+    /// Marks an open game as started, returns false if the game does not exist or has already started.
+    pub fn start_game(&mut self, id: &Id) -> bool {
+        match self.games.iter_mut().find(|game| game.id == *id) {
+            Some(game) if !game.started => {
+                game.started = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn close_game(&mut self, id: &Id) -> Option<LobbyGame> {
+        let index = self.games.iter().position(|game| game.id == *id)?;
+        Some(self.games.remove(index))
+    }
+}
+
+
+/// ==========================================================================
+/// Systems
+/// ==========================================================================
+pub fn handle_refresh_lobby_event(
+    mut reader: Local<EventReader<RefreshLobbyEvent>>,
+    events: Res<Events<RefreshLobbyEvent>>,
+    mut protocol: ResMut<ProtocolState>,
+    mut outbox: ResMut<Outbox>,
+) {
+    for event in reader.iter(&events) {
+        debug!("handle_refresh_lobby_event() - requesting games from {:?}", event.server_addr);
+
+        protocol.send(&mut outbox, event.server_addr, Message::ListGamesRequest);
+    }
+}
+
+pub fn handle_leave_game_event(
+    mut reader: Local<EventReader<LeaveGameEvent>>,
+    events: Res<Events<LeaveGameEvent>>,
+    mut state: ResMut<GameState>,
+    mut lobby: ResMut<Lobby>,
+    mut outbox: ResMut<Outbox>,
+) {
+    for _event in reader.iter(&events) {
+        let game_id = match state.game_id.take() {
+            Some(id) => id,
+            None => continue,
+        };
+
+        info!("handle_leave_game_event() - leaving game {:?}", game_id);
+
+        lobby.close_game(&game_id);
+
+        let message = Message::LeaveGame(game_id);
+
+        for addr in state.remote_addrs() {
+            outbox.send(addr, message.clone());
+        }
+
+        let local_player_info = state.local_player_info.clone();
+        state.init_local_player(local_player_info);
+    }
+}
+
+
+// ==========================================================================
+// --- Message Handlers
+// ==========================================================================
+pub(super) fn handle_list_games_request(lobby: &Lobby, outbox: &mut Outbox, from: SocketAddr) {
+    debug!("handle_list_games_request() - sending {} games to {:?}", lobby.games().len(), from);
+
+    let message = Message::ListGamesResponse(lobby.games().clone());
+    outbox.send(from, message);
+}
+
+pub(super) fn handle_leave_game(state: &mut GameState, lobby: &mut Lobby, from: SocketAddr, id: Id) {
+    if state.game_id != Some(id) {
+        warn!("handle_leave_game() - {:?} left unknown game {:?}", from, id);
+        return;
+    }
+
+    info!("handle_leave_game() - {:?} left game {:?}", from, id);
+
+    if state.spectators.contains(&from) {
+        state.spectators.retain(|addr| *addr != from);
+        return;
+    }
+
+    // Only the opponent can forfeit, anyone else merely knows the advertised id
+    if !state.remote_addrs().contains(&from) {
+        warn!("handle_leave_game() - ignoring {:?}, it does not play game {:?}", from, id);
+        return;
+    }
+
+    state.remove_remote_player(from);
+    state.offer = None;
+    lobby.close_game(&id);
+
+    // Whoever stays at the board wins
+    let winner = state.local_player_info.team;
+    state.end_game(GameResult {
+        winner: Some(winner),
+        reason: GameOverReason::OpponentLeft,
+    });
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::PlayerType;
+
+    const GUEST: &str = "127.0.0.1:12350";
+    const STRANGER: &str = "127.0.0.1:12352";
+
+    #[test]
+    fn opponent_leaving_ends_the_game() {
+        let (mut state, mut lobby, id) = hosted_game();
+
+        handle_leave_game(&mut state, &mut lobby, GUEST.parse().unwrap(), id);
+
+        assert_eq!(state.result.map(|result| result.reason), Some(GameOverReason::OpponentLeft));
+        assert!(lobby.get(&id).is_none());
+    }
+
+    #[test]
+    fn others_cannot_leave_for_the_opponent() {
+        let (mut state, mut lobby, id) = hosted_game();
+
+        handle_leave_game(&mut state, &mut lobby, STRANGER.parse().unwrap(), id);
+
+        assert!(state.result.is_none());
+        assert_eq!(state.remote_addrs(), vec![GUEST.parse().unwrap()]);
+        assert!(lobby.get(&id).is_some());
+    }
+
+    #[test]
+    fn spectators_leave_without_ending_the_game() {
+        let (mut state, mut lobby, id) = hosted_game();
+        state.spectators.push(STRANGER.parse().unwrap());
+
+        handle_leave_game(&mut state, &mut lobby, STRANGER.parse().unwrap(), id);
+
+        assert!(state.result.is_none());
+        assert!(state.spectators.is_empty());
+    }
+
+
+    // ==========================================================================
+    // --- Helpers
+    // ==========================================================================
+    /// A started game hosted as White against `GUEST`.
+    fn hosted_game() -> (GameState, Lobby, Id) {
+        let id = Id::new();
+        let host = PlayerInfo {
+            name: "Host".into(),
+            team: Team::White,
+        };
+        let guest = PlayerInfo {
+            name: "Guest".into(),
+            team: Team::Black,
+        };
+
+        let mut state = GameState::default();
+        state.game_id = Some(id);
+        state.local_player_info = host.clone();
+        state.players = vec![(PlayerType::Local, host.clone()), (PlayerType::Remote(GUEST.parse().unwrap()), guest)];
+
+        let mut lobby = Lobby::default();
+        lobby.open_game(LobbyGame {
+            id,
+            host,
+            options: GameOptions::default(),
+            started: true,
+        });
+
+        (state, lobby, id)
+    }
+}
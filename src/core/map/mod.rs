@@ -39,6 +39,19 @@ impl SpawnWithCommands for MapDescriptor {
     }
 }
 
+impl MapDescriptor {
+    /// The map on the board, to spawn it again as it is.
+    pub fn from_query(map_query: &Query<With<Map, &Dimensions>>, tile_query: &Query<(&Tile, &Position)>) -> Self {
+        match map_query.iter().next() {
+            Some(&dimensions) => MapDescriptor {
+                dimensions,
+                tiles: tile_query.iter().map(|(tile, &position)| (tile.clone(), position)).collect(),
+            },
+            None => MapDescriptor::default(),
+        }
+    }
+}
+
 impl Default for MapDescriptor {
     fn default() -> Self {
         let dimensions = Dimensions { width: 8, height: 8 };
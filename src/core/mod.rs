@@ -1,20 +1,58 @@
 use bevy::prelude::*;
-use bevy_networking::{events::MessageReceived, NetworkDelivery, NetworkResource, NetworkingPlugin};
-use log::{debug, info, trace};
+use bevy_networking::{
+    events::NetworkMessage, AddNetworkMessage, EncryptionConfig, LinkConditionerConfig, NetworkDelivery,
+    NetworkingPlugin,
+};
+use log::{debug, info, trace, warn};
 use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use strum::Display;
 
 
 use crate::{prelude::*, units::*};
 
+pub mod chat;
+pub mod clock;
 mod game;
+pub mod game_cmd;
+pub mod history;
+pub mod lobby;
 pub mod map;
+pub mod outbox;
+pub mod phase;
+pub mod protocol;
+pub mod replay;
+pub mod ruleset;
+pub mod save;
+pub mod settings;
+pub mod spectator;
 pub mod unit;
 
 pub use map::{Map, MapComponents, Tile, TileComponents};
 pub use unit::{Action, ActionExecuted, Actions, Health, Team, Unit, UnitCmd, UnitComponents};
 
+use chat::{ChatHistory, ChatLimiter, ChatMessage, SendChatEvent};
+use clock::Clocks;
 use game::GameDescriptor;
+use game_cmd::{GameCmd, Offer, PendingOffer};
+use history::{MoveHistory, UndoMoveEvent};
+use lobby::{
+    assign_teams, GameOptions, JoinRejectedEvent, LeaveGameEvent, Lobby, LobbyGame, LobbyGamesReceived,
+    RefreshLobbyEvent, TeamChoice, TimeControl,
+};
+use outbox::{ConnectionProblemEvent, Outbox};
+use phase::{AppPhase, PhaseChangedEvent, PhaseState};
+use protocol::{Handshake, ProtocolState, CHAT_CHANNEL, GAME_CHANNEL, HANDSHAKE_CHANNEL};
+use replay::{LoadReplayEvent, Replay, ReplayCmd, WatchReplayEvent};
+use ruleset::Ruleset;
+use unit::is_action_valid;
+use save::{LoadFailedEvent, LoadGameEvent, SaveGameEvent};
+use settings::{SaveSettingsEvent, Settings};
+use spectator::SpectateGameEvent;
 use unit::UnitPlugin;
 
 
@@ -25,25 +63,90 @@ use unit::UnitPlugin;
 pub struct CorePlugin;
 impl Plugin for CorePlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_entity_map::<Id>()
+        // Requires the NetworkingPlugin
+        app.add_network_message::<Handshake>(HANDSHAKE_CHANNEL, NetworkDelivery::ReliableOrdered(Some(0)))
+            .add_network_message::<Message>(GAME_CHANNEL, NetworkDelivery::ReliableOrdered(Some(1)))
+            .add_network_message::<ChatMessage>(CHAT_CHANNEL, NetworkDelivery::ReliableOrdered(Some(2)))
+            .add_entity_map::<Id>()
             // .add_startup_system(init_networking.system())
             .add_position_map::<Tile>()
             .add_event::<CreateGameEvent>()
+            .add_event::<CreateHotseatGameEvent>()
             .add_event::<JoinGameEvent>()
             .add_event::<GameStartedEvent>()
+            .add_event::<GameCmd>()
+            .add_event::<UndoMoveEvent>()
+            .add_event::<SaveGameEvent>()
+            .add_event::<LoadGameEvent>()
+            .add_event::<LoadReplayEvent>()
+            .add_event::<LoadFailedEvent>()
+            .add_event::<WatchReplayEvent>()
+            .add_event::<ReplayCmd>()
+            .add_event::<RefreshLobbyEvent>()
+            .add_event::<LobbyGamesReceived>()
+            .add_event::<JoinRejectedEvent>()
+            .add_event::<LeaveGameEvent>()
+            .add_event::<SpectateGameEvent>()
+            .add_event::<SendChatEvent>()
+            .add_event::<ConnectionProblemEvent>()
+            .add_event::<PhaseChangedEvent>()
+            .add_event::<SaveSettingsEvent>()
+            .add_resource(ChatHistory::default())
+            .add_resource(ChatLimiter::default())
+            .add_resource(ProtocolState::default())
+            .add_resource(Outbox::default())
+            .add_resource(Lobby::default())
+            .add_resource(PhaseState::default())
+            .add_resource(Replay::default())
+            .init_resource::<Settings>()
+            .init_resource::<Ruleset>()
             .add_plugin(UnitPlugin)
 
             .init_resource::<map::TileMaterials>()
             .add_system(map::handle_tile_spawned.system())
             .add_system(map::handle_map_spawned.system())
             .add_system_to_stage(stage::POST_UPDATE, map::handle_tile_overlay_state_change.system())
-s
             .add_system_to_stage(stage::POST_UPDATE, handle_position_update.system())
             .add_startup_system(setup.system())
 
             .add_system_to_stage(bevy::scene::SCENE_STAGE, Game::handle_create_game_event.system())
+            .add_system_to_stage(bevy::scene::SCENE_STAGE, Game::handle_create_hotseat_game_event.system())
             .add_system_to_stage(bevy::scene::SCENE_STAGE, Game::handle_join_game_event.system())
             .add_system_to_stage(bevy::scene::SCENE_STAGE, Game::handle_network_events.system())
+            .add_system_to_stage(bevy::scene::SCENE_STAGE, protocol::handle_handshakes.system())
+            .add_system_to_stage(bevy::scene::SCENE_STAGE, protocol::handle_client_disconnected.system())
+            .add_system_to_stage(bevy::scene::SCENE_STAGE, game_cmd::handle_game_cmd.system())
+            .add_system_to_stage(bevy::scene::SCENE_STAGE, game_cmd::handle_network_events.system())
+            .add_system(history::handle_undo_move_event.system())
+            .add_system(save::handle_save_game_event.system())
+            .add_system_to_stage(bevy::scene::SCENE_STAGE, save::handle_load_game_event.system())
+            .add_system(replay::record_finished_game.system())
+            .add_system(replay::handle_load_replay_event.system())
+            .add_system(replay::handle_watch_replay_event.system())
+            .add_system(replay::handle_phase_changed_event.system())
+            .add_system_to_stage(bevy::scene::SCENE_STAGE, replay::handle_replay_cmd.system())
+            .add_system_to_stage(bevy::scene::SCENE_STAGE, replay::auto_play_replay.system())
+            .add_system(replay::update_replay_board.system())
+            .add_system(settings::persist_settings.system())
+            .add_system(settings::apply_theme.system())
+            .add_system(lobby::handle_refresh_lobby_event.system())
+            .add_system(lobby::handle_leave_game_event.system())
+            .add_system(spectator::handle_spectate_game_event.system())
+            .add_system(spectator::forward_actions_to_spectators.system())
+            .add_system(spectator::forward_undo_to_spectators.system())
+            .add_system(clock::handle_game_started_event.system())
+            .add_system(clock::handle_flag_fall.system())
+            .add_system(ruleset::handle_game_started_event.system())
+            // Ahead of the phase systems, which end the game in the same frame it is decided
+            .add_system(ruleset::detect_checkmate.system())
+            .add_system(chat::handle_send_chat_event.system())
+            .add_system(chat::handle_chat_messages.system())
+            .add_system(outbox::handle_send_errors.system())
+            .add_system_to_stage(stage::LAST, outbox::flush_outbox.system())
+            .add_system_to_stage(stage::FIRST, phase::apply_phase_transition.system())
+            .add_system(phase::handle_game_started_event.system())
+            .add_system(phase::handle_game_state_changed.system())
+            .add_system(phase::handle_phase_changed_event.system())
             .add_resource(GameState::default())
             .add_system(GameState::handle_unit_cmd.system());
     }
@@ -60,18 +163,52 @@ fn setup(mut commands: Commands) {
 /// ==========================================================================
 #[derive(Debug, Clone)]
 pub struct CreateGameEvent {
-    pub player_info: PlayerInfo,
+    pub player_name: String,
+    pub options: GameOptions,
+}
+
+/// Start a game on this machine where both players take turns at the same screen.
+#[derive(Debug, Clone)]
+pub struct CreateHotseatGameEvent {
+    pub white_name: String,
+    pub black_name: String,
 }
 
 #[derive(Debug, Clone)]
 pub struct JoinGameEvent {
-    pub player_info: PlayerInfo,
+    pub player_name: String,
+    /// Only honored when the host left their team to chance.
+    pub team: TeamChoice,
     pub server_addr: SocketAddr,
+    pub game_id: Id,
 }
 
+/// Fired once the board is up & both seats are taken.
 #[derive(Debug, Clone)]
 pub struct GameStartedEvent;
 
+/// How a game ended, `winner` is `None` for games ending without one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GameResult {
+    pub winner: Option<Team>,
+    pub reason: GameOverReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+pub enum GameOverReason {
+    #[strum(serialize = "King captured")]
+    KingCaptured,
+    #[strum(serialize = "Opponent left")]
+    OpponentLeft,
+    Resignation,
+    #[strum(serialize = "Draw by agreement")]
+    DrawAgreed,
+    #[strum(serialize = "Out of time")]
+    Timeout,
+    Checkmate,
+    Stalemate,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerInfo {
     pub name: String,
@@ -84,6 +221,15 @@ pub enum PlayerType {
     Remote(SocketAddr),
 }
 
+impl PlayerType {
+    pub fn is_local(&self) -> bool {
+        match self {
+            PlayerType::Local => true,
+            PlayerType::Remote(_) => false,
+        }
+    }
+}
+
 
 fn handle_position_update(mut query: Query<(Changed<Position>, &mut Transform, Option<&TransformOffset>)>) {
     for (position, mut transform, transform_offset) in query.iter_mut() {
@@ -112,15 +258,66 @@ impl Game {
     fn handle_create_game_event(
         mut reader: Local<EventReader<CreateGameEvent>>,
         events: Res<Events<CreateGameEvent>>,
-        mut game_started_events: ResMut<Events<GameStartedEvent>>,
         mut state: ResMut<GameState>,
+        mut lobby: ResMut<Lobby>,
+        mut phase: ResMut<PhaseState>,
     ) {
         for event in reader.iter(&events) {
             debug!("handle_create_game_event() - create game: {:?}", event);
 
-            let player_info = event.player_info.clone();
-            state.init_local_player(player_info);
+            // A random team is settled once someone joins
+            let player_info = PlayerInfo {
+                name: event.player_name.clone(),
+                team: event.options.host_team.team().unwrap_or(Team::White),
+            };
+            state.init_local_player(player_info.clone());
+            state.connection_info = ConnectionInfo::Server;
+            state.options = event.options.clone();
+
+            let game_id = Id::new();
+            state.game_id = Some(game_id);
 
+            lobby.open_game(LobbyGame {
+                id: game_id,
+                host: player_info,
+                options: event.options.clone(),
+                started: false,
+            });
+
+            // The game starts once someone joins, see `handle_join_request()`
+            phase.set_next(AppPhase::WaitingForOpponent);
+        }
+    }
+
+    fn handle_create_hotseat_game_event(
+        mut commands: Commands,
+        mut reader: Local<EventReader<CreateHotseatGameEvent>>,
+        events: Res<Events<CreateHotseatGameEvent>>,
+        mut game_started_events: ResMut<Events<GameStartedEvent>>,
+        mut state: ResMut<GameState>,
+    ) {
+        for event in reader.iter(&events) {
+            debug!("handle_create_hotseat_game_event() - create game: {:?}", event);
+
+            let white = PlayerInfo {
+                name: event.white_name.clone(),
+                team: Team::White,
+            };
+            let black = PlayerInfo {
+                name: event.black_name.clone(),
+                team: Team::Black,
+            };
+
+            // Both players are local, the game is never advertised in the lobby or sent anywhere
+            state.init_local_player(white);
+            state.players.push((PlayerType::Local, black));
+            state.connection_info = ConnectionInfo::Server;
+            state.game_type = GameType::Hotseat;
+            state.game_id = Some(Id::new());
+            state.options = GameOptions::default();
+            state.active_team = Team::White;
+
+            GameDescriptor::default().spawn_with_commands(&mut commands);
             game_started_events.send(GameStartedEvent);
         }
     }
@@ -128,122 +325,270 @@ impl Game {
     fn handle_join_game_event(
         mut reader: Local<EventReader<JoinGameEvent>>,
         events: Res<Events<JoinGameEvent>>,
-        mut game_started_events: ResMut<Events<GameStartedEvent>>,
         mut state: ResMut<GameState>,
-        net: Res<NetworkResource>,
+        mut protocol: ResMut<ProtocolState>,
+        mut outbox: ResMut<Outbox>,
     ) {
         for event in reader.iter(&events) {
             debug!("handle_join_game_event() - joining game: {:?}", event);
 
-            let player_info = event.player_info.clone();
+            // The host seats us, see `handle_join_response()`
+            let player_info = PlayerInfo {
+                name: event.player_name.clone(),
+                team: event.team.team().unwrap_or(Team::Black),
+            };
 
-            state.init_local_player(player_info.clone());
+            state.init_local_player(player_info);
             state.connection_info = ConnectionInfo::Client;
+            state.game_id = Some(event.game_id);
 
-            let delivery = NetworkDelivery::ReliableSequenced(Some(1));
-            let message = Message::JoinRequest(player_info).to_bytes().unwrap();
-            net.send(event.server_addr, &message, delivery).unwrap();
-
-            game_started_events.send(GameStartedEvent);
+            let message = Message::JoinRequest(event.game_id, event.player_name.clone(), event.team);
+            protocol.send(&mut outbox, event.server_addr, message);
         }
     }
 
     fn handle_network_events(
         mut commands: Commands,
-        mut reader: Local<EventReader<MessageReceived>>,
-        events: Res<Events<MessageReceived>>,
+        mut reader: Local<EventReader<NetworkMessage<Message>>>,
+        events: Res<Events<NetworkMessage<Message>>>,
         mut state: ResMut<GameState>,
-        mut net: ResMut<NetworkResource>,
+        mut lobby: ResMut<Lobby>,
+        mut outbox: ResMut<Outbox>,
         mut action_executed_events: ResMut<Events<ActionExecuted>>,
-        entity_id_map: Res<EntityMap<Id>>,
+        mut game_started_events: ResMut<Events<GameStartedEvent>>,
+        // Grouped, systems take at most 10 resources
+        (mut lobby_games_events, mut join_rejected_events): (
+            ResMut<Events<LobbyGamesReceived>>,
+            ResMut<Events<JoinRejectedEvent>>,
+        ),
+        protocol: Res<ProtocolState>,
+        (entity_id_map, store, ruleset): (Res<EntityMap<Id>>, Res<PositionMap<Unit>>, Res<Ruleset>),
+        unit_query: Query<(&Team, &Unit, &Position, &Id, &Health)>,
+        action_query: Query<(&Unit, &Position, &Team, &Health, &Actions)>,
     ) {
-        for event in reader.iter(&events) {
-            let MessageReceived(conn, data) = event;
-
+        for NetworkMessage { conn, message } in reader.iter(&events) {
             println!("Connection! {:?}", conn);
 
             let from = conn.addr;
-            let message = Message::from_bytes(&*data).unwrap();
 
-            match message {
-                Message::JoinRequest(player_info) => {
-                    Self::handle_join_request(&mut commands, &mut state, &mut net, from, player_info);
+            if !protocol.is_accepted(&from) {
+                warn!("handle_network_events() - ignoring message from {:?} before its handshake", from);
+                continue;
+            }
+
+            match message.clone() {
+                Message::ListGamesRequest => {
+                    lobby::handle_list_games_request(&lobby, &mut outbox, from);
+                }
+                Message::ListGamesResponse(games) => {
+                    lobby_games_events.send(LobbyGamesReceived { server_addr: from, games });
+                }
+                Message::JoinRequest(game_id, name, team) => {
+                    let is_joined = Self::handle_join_request(
+                        &mut commands,
+                        &mut state,
+                        &mut lobby,
+                        &mut outbox,
+                        from,
+                        game_id,
+                        name,
+                        team,
+                    );
+
+                    if is_joined {
+                        game_started_events.send(GameStartedEvent);
+                    }
+                }
+                Message::JoinResponse(host, guest, options, game_descriptor) => {
+                    Self::handle_join_response(&mut commands, &mut state, from, host, guest, options, game_descriptor);
+                    game_started_events.send(GameStartedEvent);
+                }
+                Message::JoinRejected(reason) => {
+                    info!("handle_network_events() - join rejected: {}", reason);
+                    state.game_id = None;
+                    join_rejected_events.send(JoinRejectedEvent { server_addr: from, reason });
+                }
+                Message::LeaveGame(game_id) => {
+                    lobby::handle_leave_game(&mut state, &mut lobby, from, game_id);
+                }
+                Message::SpectateRequest(game_id) => {
+                    spectator::handle_spectate_request(&mut state, &lobby, &mut outbox, from, game_id, &unit_query);
+                }
+                Message::SpectateResponse(players, active_team, options, game_descriptor) => {
+                    spectator::handle_spectate_response(
+                        &mut commands,
+                        &mut state,
+                        from,
+                        players,
+                        active_team,
+                        options,
+                        game_descriptor,
+                    );
+                    game_started_events.send(GameStartedEvent);
                 }
-                Message::JoinResponse(player_info, game_descriptor) => {
-                    Self::handle_join_response(&mut commands, &mut state, from, player_info, game_descriptor);
+                // Resignations, offers & rematches, see `game_cmd::handle_network_events()`
+                Message::Resign(_)
+                | Message::Offer(..)
+                | Message::OfferResponse(..)
+                | Message::Rematch(..)
+                | Message::UndoMove(_) => {}
+                Message::ClockSync(game_id, time_control, white, black) => {
+                    clock::handle_clock_sync(&mut state, from, game_id, time_control, white, black);
+                }
+                Message::FlagFell(game_id, team) => {
+                    clock::handle_flag_fell(&mut state, from, game_id, team);
                 }
                 Message::MoveRequest(id, position) => {
-                    println!("RECEIVED MOVE REQUEST: {:?} {:?}", id, position);
-
-                    let entity = entity_id_map.get(&id).unwrap();
-
-                    println!("Entity!: {:?}", entity);
+                    debug!("handle_network_events() - move request {:?} to {:?} from {:?}", id, position, from);
+
+                    // Spectators & unknown peers may not move units, nobody may once the game is decided
+                    if !state.remote_addrs().contains(&from) || state.result.is_some() {
+                        warn!("handle_network_events() - ignoring move request from {:?}", from);
+                        continue;
+                    }
+
+                    let entity = match entity_id_map.get(&id) {
+                        Some(entity) => entity,
+                        None => {
+                            warn!("handle_network_events() - move request for unknown unit {:?}", id);
+                            continue;
+                        }
+                    };
+
+                    // The sender's client checked the move, but only the receiving side can be trusted to
+                    let team = match action_query.get_component::<Team>(*entity) {
+                        Ok(&team) => team,
+                        Err(_) => continue,
+                    };
+
+                    let is_senders_turn = team == state.active_team
+                        && state.players.iter().any(|(player_type, player_info)| match player_type {
+                            PlayerType::Remote(addr) => *addr == from && player_info.team == team,
+                            PlayerType::Local => false,
+                        });
+
+                    if !is_senders_turn {
+                        warn!("handle_network_events() - {:?} may not move {} units now", from, team);
+                        continue;
+                    }
+
+                    let is_legal = match action_query.get_component::<Actions>(*entity) {
+                        Ok(actions) => actions.get(0).map_or(false, |action| {
+                            is_action_valid(action, entity, &position, &store, &action_query, &ruleset, &state.history)
+                        }),
+                        Err(_) => false,
+                    };
+
+                    if !is_legal {
+                        warn!("handle_network_events() - illegal move {:?} to {:?} from {:?}", id, position, from);
+                        continue;
+                    }
+
+                    debug!("handle_network_events() - executing move request for {:?}", entity);
                     action_executed_events.send(ActionExecuted(entity.clone(), 0, position));
                 }
             }
         }
     }
 
+    /// Returns whether the player joined & the game started.
     fn handle_join_request(
         commands: &mut Commands,
         state: &mut ResMut<GameState>,
-        net: &mut ResMut<NetworkResource>,
+        lobby: &mut ResMut<Lobby>,
+        outbox: &mut Outbox,
         from: SocketAddr,
-        player_info: PlayerInfo,
-    ) {
+        game_id: Id,
+        name: String,
+        team: TeamChoice,
+    ) -> bool {
         info!("handle_join_request()");
 
-        let game_descriptor = GameDescriptor::default();
+        // Only games still open in the lobby may be joined
+        if !lobby.start_game(&game_id) {
+            info!("handle_join_request() - game {:?} is not open", game_id);
+
+            outbox.send(from, Message::JoinRejected("Game is no longer open".into()));
+            return false;
+        }
+
+        let options = match lobby.get(&game_id) {
+            Some(game) => game.options.clone(),
+            None => return false,
+        };
+
+        let (host_team, guest_team) = assign_teams(options.host_team, team);
+        info!("handle_join_request() - host plays {}, {} plays {}", host_team, name, guest_team);
 
-        // Add remote player to player list
-        state.players.push((PlayerType::Remote(from), player_info));
+        let host = PlayerInfo {
+            team: host_team,
+            ..state.local_player_info.clone()
+        };
+        let guest = PlayerInfo { name, team: guest_team };
 
-        let local_player_info = state.local_player_info.clone();
+        state.local_player_info = host.clone();
+        state.players = vec![(PlayerType::Local, host.clone()), (PlayerType::Remote(from), guest.clone())];
+        state.options = options.clone();
 
-        // Send response with local player info & game descriptor
-        let delivery = NetworkDelivery::ReliableSequenced(Some(1));
-        let message = Message::JoinResponse(local_player_info, game_descriptor.clone())
-            .to_bytes()
-            .unwrap();
+        let game_descriptor = GameDescriptor::new(options.variant, &options.ruleset);
 
-        net.send(from, &message, delivery).unwrap();
+        // Send response with both players, the options & game descriptor
+        let message = Message::JoinResponse(host, guest, options, game_descriptor.clone());
+        outbox.send(from, message);
 
         info!("handle_join_request() - join response sent");
         game_descriptor.spawn_with_commands(commands);
+
+        true
     }
 
     fn handle_join_response(
         commands: &mut Commands,
         state: &mut ResMut<GameState>,
         from: SocketAddr,
-        player_info: PlayerInfo,
+        host: PlayerInfo,
+        guest: PlayerInfo,
+        options: GameOptions,
         game_descriptor: GameDescriptor,
     ) {
         info!("handle_join_response()");
 
-        // Add remote player to list
-        state.players.push((PlayerType::Remote(from), player_info));
+        // The host decides who plays which team
+        state.local_player_info = guest.clone();
+        state.players = vec![(PlayerType::Local, guest), (PlayerType::Remote(from), host)];
+        state.options = options;
 
         // Spawn game & move starting state to complete
         game_descriptor.spawn_with_commands(commands);
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Message {
-    JoinRequest(PlayerInfo),
-    JoinResponse(PlayerInfo, GameDescriptor),
+    ListGamesRequest,
+    ListGamesResponse(Vec<LobbyGame>),
+    /// The joining player's name & preferred team.
+    JoinRequest(Id, String, TeamChoice),
+    /// The host & the joining player as seated by the host, followed by the game's options.
+    JoinResponse(PlayerInfo, PlayerInfo, GameOptions, GameDescriptor),
+    JoinRejected(String),
+    LeaveGame(Id),
+    SpectateRequest(Id),
+    /// The players, the team to move, the game's options & the board as it stands.
+    SpectateResponse(Vec<PlayerInfo>, Team, GameOptions, GameDescriptor),
     MoveRequest(Id, Position),
-}
-
-impl Message {
-    fn to_bytes(&self) -> bincode::Result<Vec<u8>> {
-        bincode::serialize(self)
-    }
-
-    fn from_bytes(data: &[u8]) -> bincode::Result<Self> {
-        bincode::deserialize(data)
-    }
+    Resign(Id),
+    Offer(Id, Offer),
+    /// Whether the pending offer was accepted.
+    OfferResponse(Id, bool),
+    /// Sent by the host once a rematch is agreed: the host & the guest with swapped teams & the new board.
+    Rematch(Id, PlayerInfo, PlayerInfo, GameDescriptor),
+    /// Sent by the host to its spectators when the players agreed to take back the last move.
+    UndoMove(Id),
+    /// The host's clocks: the time control & the time left to White & Black.
+    ClockSync(Id, TimeControl, Duration, Duration),
+    /// Sent by the host when the team to move ran out of time.
+    FlagFell(Id, Team),
 }
 
 
@@ -257,6 +602,16 @@ pub struct GameState {
     pub active_team: Team,
     pub connection_info: ConnectionInfo,
     pub game_type: GameType,
+    pub game_id: Option<Id>,
+    pub options: GameOptions,
+    pub spectators: Vec<SocketAddr>,
+    /// Set once the game is decided.
+    pub result: Option<GameResult>,
+    /// A draw, takeback or rematch offer waiting for an answer.
+    pub offer: Option<PendingOffer>,
+    pub history: MoveHistory,
+    /// `None` for untimed games.
+    pub clocks: Option<Clocks>,
 }
 
 impl Default for GameState {
@@ -270,6 +625,13 @@ impl Default for GameState {
             active_team: Team::White,
             connection_info: ConnectionInfo::Server,
             game_type: GameType::Networked,
+            game_id: None,
+            options: GameOptions::default(),
+            spectators: vec![],
+            result: None,
+            offer: None,
+            history: MoveHistory::default(),
+            clocks: None,
         }
     }
 }
@@ -278,6 +640,109 @@ impl GameState {
     fn init_local_player(&mut self, player_info: PlayerInfo) {
         self.local_player_info = player_info.clone();
         self.players = vec![(PlayerType::Local, player_info)];
+        self.spectators = vec![];
+        self.game_type = GameType::Networked;
+        self.active_team = Team::White;
+        self.result = None;
+        self.offer = None;
+        self.history = MoveHistory::default();
+        self.clocks = None;
+    }
+
+    /// Decides the game, unless it already is.
+    pub fn end_game(&mut self, result: GameResult) {
+        if self.result.is_none() {
+            info!("GameState::end_game() - {:?}", result);
+            self.result = Some(result);
+            self.offer = None;
+
+            let active_team = self.active_team;
+            if let Some(clocks) = &mut self.clocks {
+                clocks.stop(active_team, Instant::now());
+            }
+        }
+    }
+
+    /// Seats the players for another game in place of the decided one.
+    fn restart(&mut self, players: Vec<(PlayerType, PlayerInfo)>) {
+        // In hotseat games both players are local, White moves first
+        let local_player_info = players
+            .iter()
+            .filter(|(player_type, _)| player_type.is_local())
+            .min_by_key(|(_, player_info)| player_info.team != Team::White)
+            .map(|(_, player_info)| player_info.clone());
+
+        if let Some(player_info) = local_player_info {
+            self.local_player_info = player_info;
+        }
+
+        self.players = players;
+        self.active_team = Team::White;
+        self.result = None;
+        self.offer = None;
+        self.history = MoveHistory::default();
+        // Fresh clocks come with the `GameStartedEvent`, see `clock::handle_game_started_event()`
+        self.clocks = None;
+    }
+
+    /// Hands the turn back to the team whose move was taken back.
+    fn undo_turn(&mut self, team: Team) {
+        // Thinking about the takeback still counts
+        let active_team = self.active_team;
+        if let Some(clocks) = &mut self.clocks {
+            clocks.restart_turn(active_team, Instant::now());
+        }
+
+        self.active_team = team;
+        self.offer = None;
+        self.hand_over_hotseat();
+    }
+
+    /// In hotseat games the local player is whoever plays the team now to move.
+    fn hand_over_hotseat(&mut self) {
+        if !self.game_type.is_hotseat() {
+            return;
+        }
+
+        let active_team = self.active_team;
+        let active_player = self
+            .players
+            .iter()
+            .find(|(_, player_info)| player_info.team == active_team)
+            .map(|(_, player_info)| player_info.clone());
+
+        if let Some(player_info) = active_player {
+            self.local_player_info = player_info;
+        }
+    }
+
+    /// Whether the team is played at this machine, which for hotseat games is both teams.
+    pub fn is_local_team(&self, team: Team) -> bool {
+        self.players
+            .iter()
+            .any(|(player_type, player_info)| player_info.team == team && player_type.is_local())
+    }
+
+    /// Addresses of every remote player in the current game.
+    pub fn remote_addrs(&self) -> Vec<SocketAddr> {
+        let mut addrs: Vec<SocketAddr> = vec![];
+
+        for (player_type, _) in self.players.iter() {
+            if let PlayerType::Remote(addr) = player_type {
+                if !addrs.contains(addr) {
+                    addrs.push(*addr);
+                }
+            }
+        }
+
+        addrs
+    }
+
+    fn remove_remote_player(&mut self, addr: SocketAddr) {
+        self.players.retain(|(player_type, _)| match player_type {
+            PlayerType::Remote(remote_addr) => *remote_addr != addr,
+            PlayerType::Local => true,
+        });
     }
 }
 
@@ -287,29 +752,74 @@ impl GameState {
         mut reader: Local<EventReader<ActionExecuted>>,
         events: Res<Events<ActionExecuted>>,
         mut state: ResMut<GameState>,
+        mut outbox: ResMut<Outbox>,
     ) {
         for _event in reader.iter(&events) {
-            state.active_team = match state.active_team {
-                Team::White => Team::Black,
-                Team::Black => Team::White,
+            let active_team = state.active_team;
+            if let Some(clocks) = &mut state.clocks {
+                clocks.complete_move(active_team, Instant::now());
+            }
+
+            state.active_team = active_team.opponent();
+            state.hand_over_hotseat();
+
+            // A takeback asked for before this move would take back the wrong move
+            if let Some(PendingOffer { offer: Offer::Takeback, .. }) = state.offer {
+                state.offer = None;
             }
+
+            clock::send_sync(&state, &mut outbox);
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub enum GameType {
-    // Hotseat,
+    Hotseat,
     Local,
     Networked,
 }
 
+impl GameType {
+    pub fn is_hotseat(&self) -> bool {
+        match self {
+            GameType::Hotseat => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_networked(&self) -> bool {
+        match self {
+            GameType::Networked => true,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct AppConfig {
     pub port: String,
     pub remote_addr: Option<String>,
 
     pub scale: f32,
+
+    /// Use the TCP transport instead of laminar
+    pub tcp: bool,
+
+    /// Use the WebSocket transport instead of laminar
+    pub websocket: bool,
+
+    /// Simulated network conditions, used when testing bad connections locally
+    pub link_conditioner: Option<LinkConditionerConfig>,
+
+    /// Encrypts all traffic, optionally restricted to players knowing the room password
+    pub encryption: Option<EncryptionConfig>,
+
+    /// Saved game to resume on startup instead of showing the main menu
+    pub load: Option<PathBuf>,
+
+    /// Recorded game to watch on startup instead of showing the main menu
+    pub replay: Option<PathBuf>,
 }
 
 /// ==========================================================================
@@ -319,6 +829,7 @@ pub struct AppConfig {
 pub enum ConnectionInfo {
     Server,
     Client,
+    Spectator,
 }
 
 impl ConnectionInfo {
@@ -332,4 +843,11 @@ impl ConnectionInfo {
     pub fn is_client(&self) -> bool {
         !self.is_server()
     }
+
+    pub fn is_spectator(&self) -> bool {
+        match &self {
+            ConnectionInfo::Spectator => true,
+            _ => false,
+        }
+    }
 }
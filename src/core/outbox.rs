@@ -0,0 +1,218 @@
+use bevy::prelude::*;
+use bevy_networking::{
+    events::{MessageReceived, SendError},
+    MessageId, NetworkError, NetworkResource,
+};
+use log::{error, info, warn};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use super::{chat::ChatMessage, protocol::Handshake, Message};
+
+/// Attempts at sending a message before it is dropped.
+pub const MAX_SEND_ATTEMPTS: u32 = 5;
+
+/// Delay before the first resend, doubled for every further attempt.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// How long sent messages are kept for resending. Most failures are reported within a frame, but a connection
+/// attempt may take a while to time out.
+const SENT_RETENTION: Duration = Duration::from_secs(30);
+
+
+/// ==========================================================================
+/// Events
+/// ==========================================================================
+#[derive(Debug, Clone)]
+pub enum ConnectionProblemEvent {
+    /// A message to the peer failed & will be resent.
+    Retrying { addr: SocketAddr, attempt: u32, reason: String },
+    /// A message to the peer failed `MAX_SEND_ATTEMPTS` times & was dropped.
+    GaveUp { addr: SocketAddr, reason: String },
+    /// A peer with failed messages was heard from again.
+    Resolved { addr: SocketAddr },
+}
+
+
+/// ==========================================================================
+/// Outbox
+/// ==========================================================================
+/// Every message type the game sends.
+#[derive(Debug, Clone)]
+pub enum OutgoingMessage {
+    Handshake(Handshake),
+    Game(Message),
+    Chat(ChatMessage),
+}
+
+impl From<Handshake> for OutgoingMessage {
+    fn from(message: Handshake) -> Self {
+        OutgoingMessage::Handshake(message)
+    }
+}
+
+impl From<Message> for OutgoingMessage {
+    fn from(message: Message) -> Self {
+        OutgoingMessage::Game(message)
+    }
+}
+
+impl From<ChatMessage> for OutgoingMessage {
+    fn from(message: ChatMessage) -> Self {
+        OutgoingMessage::Chat(message)
+    }
+}
+
+#[derive(Debug)]
+struct Envelope {
+    addr: SocketAddr,
+    message: OutgoingMessage,
+    attempts: u32,
+}
+
+/// Every message the game sends goes through the outbox. Messages are sent at the end of the frame & kept for a
+/// while afterwards, so those the network reports as failed can be resent with a backoff instead of being lost.
+#[derive(Debug, Default)]
+pub struct Outbox {
+    queue: Vec<(Instant, Envelope)>,
+    sent: HashMap<MessageId, (Instant, Envelope)>,
+    problems: HashSet<SocketAddr>,
+}
+
+impl Outbox {
+    pub fn send(&mut self, addr: SocketAddr, message: impl Into<OutgoingMessage>) {
+        let envelope = Envelope {
+            addr,
+            message: message.into(),
+            attempts: 0,
+        };
+
+        self.queue.push((Instant::now(), envelope));
+    }
+
+    /// Peers with messages that failed to send, until they are heard from again.
+    pub fn problems(&self) -> &HashSet<SocketAddr> {
+        &self.problems
+    }
+
+    fn on_failure(
+        &mut self,
+        envelope: Envelope,
+        err: &NetworkError,
+        now: Instant,
+        problem_events: &mut Events<ConnectionProblemEvent>,
+    ) {
+        let addr = envelope.addr;
+
+        // Resending won't fix a message that can't be encoded
+        if !is_retryable(err) {
+            error!("Outbox::on_failure() - dropping {:?} to {:?}: {}", envelope.message, addr, err);
+            return;
+        }
+
+        self.problems.insert(addr);
+
+        if envelope.attempts >= MAX_SEND_ATTEMPTS {
+            warn!("Outbox::on_failure() - giving up on {:?} to {:?}: {}", envelope.message, addr, err);
+
+            problem_events.send(ConnectionProblemEvent::GaveUp {
+                addr,
+                reason: err.to_string(),
+            });
+            return;
+        }
+
+        let delay = RETRY_DELAY * 2u32.pow(envelope.attempts.saturating_sub(1));
+        info!("Outbox::on_failure() - resending to {:?} in {:?}: {}", addr, delay, err);
+
+        problem_events.send(ConnectionProblemEvent::Retrying {
+            addr,
+            attempt: envelope.attempts,
+            reason: err.to_string(),
+        });
+
+        self.queue.push((now + delay, envelope));
+    }
+}
+
+
+/// ==========================================================================
+/// Systems
+/// ==========================================================================
+/// Sends every queued message that is due.
+pub fn flush_outbox(
+    mut outbox: ResMut<Outbox>,
+    mut problem_events: ResMut<Events<ConnectionProblemEvent>>,
+    net: Res<NetworkResource>,
+) {
+    let now = Instant::now();
+
+    outbox.sent.retain(|_, (sent_at, _)| now.duration_since(*sent_at) < SENT_RETENTION);
+
+    let (due, pending): (Vec<_>, Vec<_>) = outbox.queue.drain(..).partition(|(send_at, _)| *send_at <= now);
+    outbox.queue = pending;
+
+    for (_, mut envelope) in due {
+        envelope.attempts += 1;
+
+        let addr = envelope.addr;
+        let result = match &envelope.message {
+            OutgoingMessage::Handshake(message) => net.send(addr, message),
+            OutgoingMessage::Game(message) => net.send(addr, message),
+            OutgoingMessage::Chat(message) => net.send(addr, message),
+        };
+
+        match result {
+            Ok(id) => {
+                outbox.sent.insert(id, (now, envelope));
+            }
+            Err(err) => outbox.on_failure(envelope, &err, now, &mut problem_events),
+        }
+    }
+}
+
+/// Resends messages the network failed to deliver & notices when a troubled peer is reachable again.
+pub fn handle_send_errors(
+    mut error_reader: Local<EventReader<SendError>>,
+    mut message_reader: Local<EventReader<MessageReceived>>,
+    error_events: Res<Events<SendError>>,
+    message_events: Res<Events<MessageReceived>>,
+    mut outbox: ResMut<Outbox>,
+    mut problem_events: ResMut<Events<ConnectionProblemEvent>>,
+) {
+    let now = Instant::now();
+
+    for SendError { messages, addr, error } in error_reader.iter(&error_events) {
+        if messages.is_empty() {
+            warn!("handle_send_errors() - network error for {:?}: {}", addr, error);
+            continue;
+        }
+
+        for id in messages.iter() {
+            if let Some((_, envelope)) = outbox.sent.remove(id) {
+                outbox.on_failure(envelope, error, now, &mut problem_events);
+            }
+        }
+    }
+
+    for MessageReceived(conn, _) in message_reader.iter(&message_events) {
+        if outbox.problems.remove(&conn.addr) {
+            info!("handle_send_errors() - {:?} is reachable again", conn.addr);
+            problem_events.send(ConnectionProblemEvent::Resolved { addr: conn.addr });
+        }
+    }
+}
+
+
+// ==========================================================================
+// --- Helpers
+// ==========================================================================
+fn is_retryable(err: &NetworkError) -> bool {
+    match err {
+        NetworkError::UnregisteredMessage(_) | NetworkError::SerializationError(_) => false,
+        _ => true,
+    }
+}
@@ -0,0 +1,169 @@
+use bevy::prelude::*;
+use log::{debug, info};
+use strum::Display;
+
+use super::{chat::ChatHistory, lobby::LeaveGameEvent, GameStartedEvent, GameState, Tile, Unit};
+use crate::prelude::*;
+
+
+/// ==========================================================================
+/// Phases
+/// ==========================================================================
+/// The phases the app moves through, from the main menu to the end of a game & back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum AppPhase {
+    /// The main menu & the game options screen.
+    MainMenu,
+    /// Browsing the games of a remote host.
+    Lobby,
+    /// Hosting a game nobody has joined yet.
+    WaitingForOpponent,
+    /// Playing or spectating a game.
+    Playing,
+    /// The game is decided, the final board stays up until the player leaves.
+    GameOver,
+    /// Watching a recorded game, see `replay::Replay`.
+    Replay,
+}
+
+impl AppPhase {
+    /// Whether the phase belongs to a game, leaving these phases leaves the game.
+    pub fn is_in_game(&self) -> bool {
+        match self {
+            AppPhase::WaitingForOpponent | AppPhase::Playing | AppPhase::GameOver => true,
+            AppPhase::MainMenu | AppPhase::Lobby | AppPhase::Replay => false,
+        }
+    }
+}
+
+/// The current phase & the phase requested for the next frame.
+///
+/// Transitions requested with `set_next()` are applied at the start of the next frame, so every system sees the same
+/// phase for the whole of a frame.
+#[derive(Debug)]
+pub struct PhaseState {
+    current: AppPhase,
+    next: Option<AppPhase>,
+}
+
+impl Default for PhaseState {
+    fn default() -> Self {
+        PhaseState {
+            current: AppPhase::MainMenu,
+            next: None,
+        }
+    }
+}
+
+impl PhaseState {
+    pub fn current(&self) -> AppPhase {
+        self.current
+    }
+
+    pub fn is(&self, phase: AppPhase) -> bool {
+        self.current == phase
+    }
+
+    /// Requests a transition, replacing any transition requested earlier in the frame.
+    pub fn set_next(&mut self, phase: AppPhase) {
+        debug!("PhaseState::set_next() - {} -> {}", self.current, phase);
+        self.next = Some(phase);
+    }
+}
+
+
+/// ==========================================================================
+/// Events & Components
+/// ==========================================================================
+/// Fired once a transition has been applied & the entities of the old phase despawned.
+#[derive(Debug, Clone, Copy)]
+pub struct PhaseChangedEvent {
+    pub from: AppPhase,
+    pub to: AppPhase,
+}
+
+/// Despawns the entity & its children when the app moves to a phase outside the listed ones.
+#[derive(Debug, Clone, Copy)]
+pub struct PhaseScoped(pub &'static [AppPhase]);
+
+impl PhaseScoped {
+    /// The board & the panels around it, which stay up after the game is decided.
+    pub const GAME: PhaseScoped = PhaseScoped(&[AppPhase::Playing, AppPhase::GameOver]);
+}
+
+
+/// ==========================================================================
+/// Systems
+/// ==========================================================================
+/// Applies the transition requested during the previous frame.
+pub fn apply_phase_transition(
+    mut commands: Commands,
+    mut phase: ResMut<PhaseState>,
+    mut phase_changed_events: ResMut<Events<PhaseChangedEvent>>,
+    scoped_query: Query<(Entity, &PhaseScoped)>,
+) {
+    let next = match phase.next.take() {
+        Some(next) if next != phase.current => next,
+        _ => return,
+    };
+
+    let from = phase.current;
+    info!("apply_phase_transition() - {} -> {}", from, next);
+
+    for (entity, scoped) in scoped_query.iter() {
+        if !scoped.0.contains(&next) {
+            commands.despawn_recursive(entity);
+        }
+    }
+
+    phase.current = next;
+    phase_changed_events.send(PhaseChangedEvent { from, to: next });
+}
+
+/// The game is on once the board is up, which for hosts is when someone joins.
+pub fn handle_game_started_event(
+    mut reader: Local<EventReader<GameStartedEvent>>,
+    events: Res<Events<GameStartedEvent>>,
+    mut phase: ResMut<PhaseState>,
+) {
+    for _event in reader.iter(&events) {
+        phase.set_next(AppPhase::Playing);
+    }
+}
+
+pub fn handle_game_state_changed(state: ChangedRes<GameState>, mut phase: ResMut<PhaseState>) {
+    if phase.is(AppPhase::Playing) && state.result.is_some() {
+        phase.set_next(AppPhase::GameOver);
+    }
+}
+
+/// Leaving the game phases leaves the game & forgets everything about it, leaving a replay forgets its board.
+pub fn handle_phase_changed_event(
+    mut reader: Local<EventReader<PhaseChangedEvent>>,
+    events: Res<Events<PhaseChangedEvent>>,
+    mut leave_game_events: ResMut<Events<LeaveGameEvent>>,
+    mut chat_history: ResMut<ChatHistory>,
+    mut id_map: ResMut<EntityMap<Id>>,
+    mut unit_position_map: ResMut<PositionMap<Unit>>,
+    mut tile_position_map: ResMut<PositionMap<Tile>>,
+) {
+    for event in reader.iter(&events) {
+        let is_leaving_game = event.from.is_in_game() && !event.to.is_in_game();
+
+        if is_leaving_game {
+            debug!("handle_phase_changed_event() - leaving game for {}", event.to);
+
+            leave_game_events.send(LeaveGameEvent);
+            chat_history.clear();
+        }
+
+        if !is_leaving_game && event.from != AppPhase::Replay {
+            continue;
+        }
+
+        // The board was despawned with the old phase
+        id_map.clear();
+        unit_position_map.clear();
+        tile_position_map.clear();
+    }
+}
@@ -0,0 +1,299 @@
+use bevy::prelude::*;
+use bevy_networking::{
+    events::{ClientDisconnected, MessageDecodeError, NetworkMessage},
+    ChannelId,
+};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, net::SocketAddr};
+
+use super::{lobby::JoinRejectedEvent, outbox::Outbox, Message};
+
+
+/// Version of the `Message` wire format. Bump whenever `Message` or any type it contains changes.
+pub const PROTOCOL_VERSION: u32 = 6;
+
+/// Optional features this build supports, exchanged during the handshake.
+pub const CAPABILITIES: &[&str] = &["lobby", "spectate", "chat"];
+
+/// Channels every message type is sent on. Each channel gets its own ordering stream.
+pub const HANDSHAKE_CHANNEL: ChannelId = ChannelId(0);
+pub const GAME_CHANNEL: ChannelId = ChannelId(1);
+pub const CHAT_CHANNEL: ChannelId = ChannelId(2);
+
+
+/// ==========================================================================
+/// Wire Format
+/// ==========================================================================
+/// Sent on `HANDSHAKE_CHANNEL` before any other message.
+///
+/// Handshakes must stay decodable by every version, so `Handshake` may only ever be appended to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Handshake {
+    Hello { version: u32, capabilities: Vec<String> },
+    Accepted { version: u32, capabilities: Vec<String> },
+    Rejected { reason: String },
+}
+
+/// ==========================================================================
+/// Protocol State
+/// ==========================================================================
+#[derive(Debug, Clone)]
+pub struct PeerProtocol {
+    pub version: u32,
+    pub capabilities: Vec<String>,
+}
+
+#[derive(Debug)]
+enum PeerState {
+    Pending(Vec<Message>),
+    Accepted(PeerProtocol),
+    /// Either side refused the handshake, until the peer says hello again or disconnects.
+    Rejected,
+}
+
+/// Tracks the handshake with every peer. Messages to a peer we haven't completed the handshake with
+/// are queued & sent once the peer accepts. Peers are forgotten once they disconnect, so a reconnecting peer
+/// goes through the handshake again.
+#[derive(Debug, Default)]
+pub struct ProtocolState {
+    peers: HashMap<SocketAddr, PeerState>,
+}
+
+impl ProtocolState {
+    pub fn peer(&self, addr: &SocketAddr) -> Option<&PeerProtocol> {
+        match self.peers.get(addr) {
+            Some(PeerState::Accepted(peer)) => Some(peer),
+            _ => None,
+        }
+    }
+
+    /// Whether the peer completed the handshake on our version. Anything else a peer sends before that is dropped, an
+    /// incompatible peer could otherwise still move units or join games.
+    pub fn is_accepted(&self, addr: &SocketAddr) -> bool {
+        self.peer(addr).map_or(false, |peer| peer.version == PROTOCOL_VERSION)
+    }
+
+    /// Returns true if the peer advertised the capability, which peers without a completed handshake have not.
+    pub fn supports(&self, addr: &SocketAddr, capability: &str) -> bool {
+        self.peer(addr)
+            .map_or(false, |peer| peer.capabilities.iter().any(|c| c == capability))
+    }
+
+    /// Sends a message, starting the handshake first if the peer is unknown.
+    pub fn send(&mut self, outbox: &mut Outbox, addr: SocketAddr, message: Message) {
+        match self.peers.get_mut(&addr) {
+            Some(PeerState::Accepted(_)) => outbox.send(addr, message),
+            Some(PeerState::Pending(queue)) => queue.push(message),
+            Some(PeerState::Rejected) | None => {
+                debug!("ProtocolState::send() - starting handshake with {:?}", addr);
+
+                self.peers.insert(addr, PeerState::Pending(vec![message]));
+
+                let hello = Handshake::Hello {
+                    version: PROTOCOL_VERSION,
+                    capabilities: local_capabilities(),
+                };
+                outbox.send(addr, hello);
+            }
+        }
+    }
+
+    fn is_pending(&self, addr: &SocketAddr) -> bool {
+        match self.peers.get(addr) {
+            Some(PeerState::Pending(_)) => true,
+            _ => false,
+        }
+    }
+
+    fn is_rejected(&self, addr: &SocketAddr) -> bool {
+        match self.peers.get(addr) {
+            Some(PeerState::Rejected) => true,
+            _ => false,
+        }
+    }
+
+    fn accept(&mut self, outbox: &mut Outbox, addr: SocketAddr, peer: PeerProtocol) {
+        if let Some(PeerState::Pending(queue)) = self.peers.insert(addr, PeerState::Accepted(peer)) {
+            for message in queue {
+                outbox.send(addr, message);
+            }
+        }
+    }
+
+    /// Drops the messages waiting for the peer, nothing it sends is handled until it says hello again.
+    fn reject(&mut self, addr: SocketAddr) {
+        self.peers.insert(addr, PeerState::Rejected);
+    }
+
+    fn forget(&mut self, addr: &SocketAddr) {
+        self.peers.remove(addr);
+    }
+}
+
+
+/// ==========================================================================
+/// Systems
+/// ==========================================================================
+/// Answers handshakes & rejects peers whose game messages could not be decoded.
+pub fn handle_handshakes(
+    mut reader: Local<EventReader<NetworkMessage<Handshake>>>,
+    mut decode_error_reader: Local<EventReader<MessageDecodeError>>,
+    events: Res<Events<NetworkMessage<Handshake>>>,
+    decode_error_events: Res<Events<MessageDecodeError>>,
+    mut protocol: ResMut<ProtocolState>,
+    mut join_rejected_events: ResMut<Events<JoinRejectedEvent>>,
+    mut outbox: ResMut<Outbox>,
+) {
+    for NetworkMessage { conn, message } in reader.iter(&events) {
+        let from = conn.addr;
+
+        match message.clone() {
+            Handshake::Hello { version, capabilities } => {
+                let response = match version == PROTOCOL_VERSION {
+                    true => {
+                        info!("handle_handshakes() - accepted {:?} {:?}", from, capabilities);
+
+                        protocol.accept(&mut outbox, from, PeerProtocol { version, capabilities });
+                        Handshake::Accepted {
+                            version: PROTOCOL_VERSION,
+                            capabilities: local_capabilities(),
+                        }
+                    }
+                    false => {
+                        info!("handle_handshakes() - rejected {:?}, version {}", from, version);
+
+                        protocol.reject(from);
+                        Handshake::Rejected {
+                            reason: version_mismatch(Some(version)),
+                        }
+                    }
+                };
+
+                outbox.send(from, response);
+            }
+            // Only answers to our own hello count, an unsolicited one would skip the version check
+            Handshake::Accepted { .. } if !protocol.is_pending(&from) => {
+                warn!("handle_handshakes() - ignoring unsolicited handshake from {:?}", from);
+            }
+            Handshake::Accepted { version, capabilities } => {
+                info!("handle_handshakes() - accepted by {:?} {:?}", from, capabilities);
+                protocol.accept(&mut outbox, from, PeerProtocol { version, capabilities });
+            }
+            Handshake::Rejected { reason } => {
+                info!("handle_handshakes() - rejected by {:?}: {}", from, reason);
+
+                protocol.reject(from);
+                join_rejected_events.send(JoinRejectedEvent { server_addr: from, reason });
+            }
+        }
+    }
+
+    for MessageDecodeError(conn, channel, err) in decode_error_reader.iter(&decode_error_events) {
+        warn!("handle_handshakes() - dropping {:?} message from {:?}: {}", channel, conn.addr, err);
+
+        // An undecodable game message means the peer skipped the handshake or runs another version, it is told once
+        if *channel == GAME_CHANNEL && !protocol.is_rejected(&conn.addr) {
+            let remote = protocol.peer(&conn.addr).map(|peer| peer.version);
+            let reason = version_mismatch(remote);
+
+            protocol.reject(conn.addr);
+            outbox.send(conn.addr, Handshake::Rejected { reason });
+        }
+    }
+}
+
+/// A peer connecting again, e.g. after a restart, may run another version & has to go through the handshake again.
+pub fn handle_client_disconnected(
+    mut reader: Local<EventReader<ClientDisconnected>>,
+    events: Res<Events<ClientDisconnected>>,
+    mut protocol: ResMut<ProtocolState>,
+) {
+    for ClientDisconnected(conn) in reader.iter(&events) {
+        debug!("handle_client_disconnected() - forgetting {:?}", conn.addr);
+        protocol.forget(&conn.addr);
+    }
+}
+
+
+// ==========================================================================
+// --- Helpers
+// ==========================================================================
+fn local_capabilities() -> Vec<String> {
+    CAPABILITIES.iter().map(|c| c.to_string()).collect()
+}
+
+fn version_mismatch(remote: Option<u32>) -> String {
+    match remote {
+        Some(remote) => format!(
+            "Peer uses protocol version {} but this game uses version {}",
+            remote, PROTOCOL_VERSION
+        ),
+        None => format!("Peer does not use protocol version {}", PROTOCOL_VERSION),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEER: &str = "127.0.0.1:12350";
+
+    #[test]
+    fn only_accepted_peers_support_capabilities() {
+        let mut protocol = ProtocolState::default();
+        let addr = PEER.parse().unwrap();
+
+        assert!(!protocol.supports(&addr, "chat"));
+
+        protocol.send(&mut Outbox::default(), addr, Message::ListGamesRequest);
+        assert!(!protocol.supports(&addr, "chat"));
+
+        protocol.accept(&mut Outbox::default(), addr, peer(PROTOCOL_VERSION));
+        assert!(protocol.supports(&addr, "chat"));
+        assert!(!protocol.supports(&addr, "teleport"));
+    }
+
+    #[test]
+    fn rejected_peers_are_no_longer_accepted() {
+        let mut protocol = ProtocolState::default();
+        let addr = PEER.parse().unwrap();
+
+        protocol.accept(&mut Outbox::default(), addr, peer(PROTOCOL_VERSION));
+        protocol.reject(addr);
+
+        assert!(!protocol.is_accepted(&addr));
+        assert!(protocol.is_rejected(&addr));
+        assert!(!protocol.supports(&addr, "chat"));
+
+        // Until the peer says hello again
+        protocol.accept(&mut Outbox::default(), addr, peer(PROTOCOL_VERSION));
+        assert!(protocol.is_accepted(&addr));
+    }
+
+    #[test]
+    fn disconnected_peers_handshake_again() {
+        let mut protocol = ProtocolState::default();
+        let addr = PEER.parse().unwrap();
+
+        protocol.accept(&mut Outbox::default(), addr, peer(PROTOCOL_VERSION));
+        protocol.forget(&addr);
+
+        assert!(!protocol.is_accepted(&addr));
+
+        protocol.send(&mut Outbox::default(), addr, Message::ListGamesRequest);
+        assert!(protocol.is_pending(&addr));
+    }
+
+
+    // ==========================================================================
+    // --- Helpers
+    // ==========================================================================
+    fn peer(version: u32) -> PeerProtocol {
+        PeerProtocol {
+            version,
+            capabilities: local_capabilities(),
+        }
+    }
+}
@@ -0,0 +1,456 @@
+use bevy::prelude::*;
+use log::{debug, info, warn};
+use std::path::PathBuf;
+
+use super::{
+    chat::{ChatHistory, ChatLine},
+    history::UnitSnapshot,
+    map::{Map, MapDescriptor, Tile},
+    phase::{AppPhase, PhaseChangedEvent, PhaseScoped, PhaseState},
+    save::{LoadFailedEvent, SavedGame},
+    Actions, GameResult, GameState, Health, PlayerInfo, Team, Unit,
+};
+use crate::prelude::*;
+
+
+/// Where finished games are recorded to, the main menu replays the last one.
+pub const DEFAULT_REPLAY_PATH: &str = "replay.save";
+
+/// Seconds between moves when auto-playing at 1x speed.
+const SECONDS_PER_MOVE: f32 = 1.0;
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 8.0;
+
+
+/// ==========================================================================
+/// Replay
+/// ==========================================================================
+/// A recorded game, stepped through one move at a time.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    map: MapDescriptor,
+    /// The board before every move, followed by the final board.
+    boards: Vec<Vec<UnitSnapshot>>,
+    /// The team making each move.
+    movers: Vec<Team>,
+    /// The team to move once the game ended.
+    final_team: Team,
+    players: Vec<PlayerInfo>,
+    result: Option<GameResult>,
+    step: usize,
+    is_playing: bool,
+    speed: f32,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Replay {
+            map: MapDescriptor::default(),
+            boards: vec![vec![]],
+            movers: vec![],
+            final_team: Team::White,
+            players: vec![],
+            result: None,
+            step: 0,
+            is_playing: false,
+            speed: 1.0,
+        }
+    }
+}
+
+impl From<SavedGame> for Replay {
+    /// Works back from the final board, every move knows how the units it changed were before it.
+    fn from(saved: SavedGame) -> Self {
+        let moves = saved.history.moves();
+        let mut boards = vec![saved.units.clone()];
+
+        for record in moves.iter().rev() {
+            let mut board = boards.last().cloned().unwrap_or_default();
+
+            for snapshot in record.units.iter() {
+                board.retain(|unit| unit.id != snapshot.id);
+                board.push(*snapshot);
+            }
+
+            boards.push(board);
+        }
+
+        boards.reverse();
+
+        Replay {
+            map: saved.map,
+            boards,
+            movers: moves.iter().map(|record| record.team).collect(),
+            final_team: saved.active_team,
+            players: saved.players,
+            result: saved.result,
+            ..Default::default()
+        }
+    }
+}
+
+impl Replay {
+    /// The number of moves, the replay steps from 0 to `len()`.
+    pub fn len(&self) -> usize {
+        self.movers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.movers.is_empty()
+    }
+
+    /// The number of moves made on the board shown.
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    pub fn board(&self) -> &[UnitSnapshot] {
+        &self.boards[self.step]
+    }
+
+    pub fn active_team(&self) -> Team {
+        self.movers.get(self.step).copied().unwrap_or(self.final_team)
+    }
+
+    pub fn players(&self) -> &[PlayerInfo] {
+        &self.players
+    }
+
+    pub fn result(&self) -> Option<GameResult> {
+        self.result
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.is_playing
+    }
+
+    /// Auto-play speed, 1.0 plays a move a second.
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    fn jump_to(&mut self, step: usize) {
+        self.step = step.min(self.len());
+    }
+}
+
+
+/// ==========================================================================
+/// Events
+/// ==========================================================================
+/// Watch a recorded game from the main menu.
+#[derive(Debug, Clone)]
+pub struct LoadReplayEvent {
+    pub path: PathBuf,
+}
+
+/// Watch the game which just ended from the game over screen.
+#[derive(Debug, Clone)]
+pub struct WatchReplayEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayCmd {
+    First,
+    Back,
+    Forward,
+    Last,
+    JumpTo(usize),
+    /// Auto-plays from the shown move, or from the start once at the end.
+    TogglePlay,
+    Slower,
+    Faster,
+}
+
+/// The map of the replay, see `handle_phase_changed_event()`.
+pub struct ReplayBoard;
+
+
+/// ==========================================================================
+/// Systems
+/// ==========================================================================
+/// Every finished game is recorded, both to watch straight away & to watch again later from the main menu.
+pub fn record_finished_game(
+    mut reader: Local<EventReader<PhaseChangedEvent>>,
+    events: Res<Events<PhaseChangedEvent>>,
+    state: Res<GameState>,
+    mut replay: ResMut<Replay>,
+    mut history: ResMut<ChatHistory>,
+    map_query: Query<With<Map, &Dimensions>>,
+    tile_query: Query<(&Tile, &Position)>,
+    unit_query: Query<(&Unit, &Position, &Team, &Health, &Id)>,
+) {
+    for event in reader.iter(&events) {
+        if event.to != AppPhase::GameOver {
+            continue;
+        }
+
+        let map = MapDescriptor::from_query(&map_query, &tile_query);
+        let saved = SavedGame::capture(&state, map, &unit_query);
+        let path = PathBuf::from(DEFAULT_REPLAY_PATH);
+
+        match saved.write(&path) {
+            Ok(()) => {
+                info!("record_finished_game() - recorded to {:?}", path);
+                history.push(ChatLine::System(format!("Replay saved to {}", path.display())));
+            }
+            Err(error) => warn!("record_finished_game() - unable to record to {:?}: {}", path, error),
+        }
+
+        *replay = Replay::from(saved);
+    }
+}
+
+pub fn handle_load_replay_event(
+    mut reader: Local<EventReader<LoadReplayEvent>>,
+    events: Res<Events<LoadReplayEvent>>,
+    mut replay: ResMut<Replay>,
+    mut phase: ResMut<PhaseState>,
+    mut load_failed_events: ResMut<Events<LoadFailedEvent>>,
+) {
+    for event in reader.iter(&events) {
+        if !phase.is(AppPhase::MainMenu) {
+            warn!("handle_load_replay_event() - replays are loaded from the main menu");
+            continue;
+        }
+
+        match SavedGame::read(&event.path) {
+            Ok(saved) => {
+                info!("handle_load_replay_event() - watching {:?}", event.path);
+                *replay = Replay::from(saved);
+                phase.set_next(AppPhase::Replay);
+            }
+            Err(error) => {
+                warn!("handle_load_replay_event() - unable to load {:?}: {}", event.path, error);
+                load_failed_events.send(LoadFailedEvent {
+                    path: event.path.clone(),
+                    reason: error.to_string(),
+                });
+            }
+        }
+    }
+}
+
+/// The game was recorded as it ended, see `record_finished_game()`.
+pub fn handle_watch_replay_event(
+    mut reader: Local<EventReader<WatchReplayEvent>>,
+    events: Res<Events<WatchReplayEvent>>,
+    mut replay: ResMut<Replay>,
+    mut phase: ResMut<PhaseState>,
+) {
+    for _event in reader.iter(&events) {
+        if !phase.is(AppPhase::GameOver) {
+            continue;
+        }
+
+        replay.step = 0;
+        replay.is_playing = false;
+        phase.set_next(AppPhase::Replay);
+    }
+}
+
+/// The replay gets a board of its own, the board of the game went with the game.
+pub fn handle_phase_changed_event(
+    mut commands: Commands,
+    mut reader: Local<EventReader<PhaseChangedEvent>>,
+    events: Res<Events<PhaseChangedEvent>>,
+    replay: Res<Replay>,
+) {
+    for event in reader.iter(&events) {
+        if event.to != AppPhase::Replay {
+            continue;
+        }
+
+        debug!("handle_phase_changed_event() - spawning replay of {} moves", replay.len());
+
+        replay
+            .map
+            .clone()
+            .spawn_with_commands(&mut commands)
+            .with(ReplayBoard)
+            .with(PhaseScoped(&[AppPhase::Replay]))
+            .with_children(|commands| {
+                for snapshot in replay.board().iter() {
+                    commands.spawn(snapshot.components());
+                }
+            });
+    }
+}
+
+pub fn handle_replay_cmd(
+    mut reader: Local<EventReader<ReplayCmd>>,
+    events: Res<Events<ReplayCmd>>,
+    mut replay: ResMut<Replay>,
+    phase: Res<PhaseState>,
+) {
+    for cmd in reader.iter(&events) {
+        if !phase.is(AppPhase::Replay) {
+            continue;
+        }
+
+        debug!("handle_replay_cmd() - {:?}", cmd);
+
+        // Stepping by hand pauses auto-play
+        let step = replay.step;
+        match cmd {
+            ReplayCmd::First => replay.jump_to(0),
+            ReplayCmd::Back => replay.jump_to(step.saturating_sub(1)),
+            ReplayCmd::Forward => replay.jump_to(step + 1),
+            ReplayCmd::Last => replay.jump_to(usize::MAX),
+            ReplayCmd::JumpTo(step) => replay.jump_to(*step),
+            ReplayCmd::TogglePlay => {
+                if !replay.is_playing && step == replay.len() {
+                    replay.jump_to(0);
+                }
+
+                replay.is_playing = !replay.is_playing;
+                continue;
+            }
+            ReplayCmd::Slower => {
+                replay.speed = (replay.speed / 2.0).max(MIN_SPEED);
+                continue;
+            }
+            ReplayCmd::Faster => {
+                replay.speed = (replay.speed * 2.0).min(MAX_SPEED);
+                continue;
+            }
+        }
+
+        replay.is_playing = false;
+    }
+}
+
+/// Steps forward while auto-playing, stopping at the end of the game.
+pub fn auto_play_replay(time: Res<Time>, mut elapsed: Local<f32>, mut replay: ResMut<Replay>, phase: Res<PhaseState>) {
+    // Only borrowed mutably on a step, so the replay does not change every frame
+    if !phase.is(AppPhase::Replay) || !replay.is_playing {
+        *elapsed = 0.0;
+        return;
+    }
+
+    *elapsed += time.delta_seconds * replay.speed;
+
+    if *elapsed < SECONDS_PER_MOVE {
+        return;
+    }
+
+    *elapsed -= SECONDS_PER_MOVE;
+
+    let step = replay.step + 1;
+    replay.jump_to(step);
+
+    if replay.step == replay.len() {
+        replay.is_playing = false;
+    }
+}
+
+/// Moves the units of the replay board to the move shown, bringing back captured units & removing captures.
+pub fn update_replay_board(
+    mut commands: Commands,
+    replay: ChangedRes<Replay>,
+    phase: Res<PhaseState>,
+    mut unit_position_map: ResMut<PositionMap<Unit>>,
+    map_query: Query<With<ReplayBoard, Entity>>,
+    mut unit_query: Query<(Entity, &Id, &mut Unit, &mut Position, &mut Health, &mut Actions)>,
+) {
+    // The board is spawned with the move shown on entering the replay
+    let map_entity = match map_query.iter().next() {
+        Some(entity) if phase.is(AppPhase::Replay) => entity,
+        _ => return,
+    };
+
+    let board = replay.board();
+    let mut spawned: Vec<Id> = vec![];
+
+    for (entity, id, mut unit, mut position, mut health, mut actions) in unit_query.iter_mut() {
+        match board.iter().find(|snapshot| snapshot.id == *id) {
+            Some(snapshot) => {
+                spawned.push(*id);
+
+                // Only touch units which changed, moving a unit updates the position map
+                if *position != snapshot.position {
+                    *position = snapshot.position;
+                }
+
+                if health.0 != snapshot.health.0 {
+                    *health = snapshot.health;
+                }
+
+                // Stepping back past a promotion turns the queen back into a pawn
+                if *unit != snapshot.unit {
+                    *unit = snapshot.unit;
+                    *actions = snapshot.components().actions;
+                }
+            }
+            None => {
+                commands.despawn_recursive(entity);
+                unit_position_map.remove_entity(&entity);
+            }
+        }
+    }
+
+    for snapshot in board.iter() {
+        if spawned.contains(&snapshot.id) {
+            continue;
+        }
+
+        commands.spawn(snapshot.components());
+
+        if let Some(unit_entity) = commands.current_entity() {
+            commands.push_children(map_entity, &[unit_entity]);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::history::{MoveHistory, MoveRecord};
+
+    #[test]
+    fn steps_back_past_a_promotion() {
+        let pawn_id = Id::new();
+        let king = snapshot(Id::new(), Unit::King, Team::Black, (7, 7));
+        let pawn = snapshot(pawn_id, Unit::Pawn, Team::White, (0, 6));
+        let queen = snapshot(pawn_id, Unit::Queen, Team::White, (0, 7));
+
+        // The history only knows the units as they were before each move
+        let history = MoveHistory::from(vec![MoveRecord {
+            team: Team::White,
+            units: vec![pawn],
+        }]);
+        let mut replay = Replay::from(SavedGame::finished(vec![king, queen], history));
+
+        assert_eq!(replay.len(), 1);
+        assert_eq!(replay.board().len(), 2);
+        assert_eq!(unit_of(&replay, pawn_id), Some((Unit::Pawn, Position::new(0, 6))));
+
+        replay.jump_to(1);
+        assert_eq!(unit_of(&replay, pawn_id), Some((Unit::Queen, Position::new(0, 7))));
+
+        replay.jump_to(0);
+        assert_eq!(unit_of(&replay, pawn_id), Some((Unit::Pawn, Position::new(0, 6))));
+    }
+
+
+    // ==========================================================================
+    // --- Helpers
+    // ==========================================================================
+    fn snapshot(id: Id, unit: Unit, team: Team, position: (i32, i32)) -> UnitSnapshot {
+        UnitSnapshot {
+            id,
+            unit,
+            team,
+            position: position.into(),
+            health: Health(1),
+        }
+    }
+
+    fn unit_of(replay: &Replay, id: Id) -> Option<(Unit, Position)> {
+        replay
+            .board()
+            .iter()
+            .find(|snapshot| snapshot.id == id)
+            .map(|snapshot| (snapshot.unit, snapshot.position))
+    }
+}
@@ -0,0 +1,529 @@
+use bevy::prelude::*;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt};
+use strum::Display;
+
+use super::{
+    history::MoveHistory,
+    phase::{AppPhase, PhaseState},
+    unit::{Action, Actions, Health, Team, Unit},
+    GameOverReason, GameResult, GameStartedEvent, GameState,
+};
+use crate::{
+    prelude::*,
+    units::{castling_rook, is_castling, promotion_row, unit_targets},
+};
+
+
+/// ==========================================================================
+/// Ruleset
+/// ==========================================================================
+/// The rules a game is played by, picked by the host along with the other `GameOptions`. The rules of the game in
+/// play are also a resource of their own, see `handle_game_started_event()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ruleset {
+    pub win_condition: WinCondition,
+    /// Kings & rooks which have not moved may castle.
+    pub castling: bool,
+    /// Every unit starts with this much health & loses a point per hit, only leaving the board once out of health.
+    pub starting_health: u32,
+}
+
+impl Default for Ruleset {
+    /// The rules the game has always been played by.
+    fn default() -> Self {
+        Ruleset::KING_CAPTURE
+    }
+}
+
+impl Ruleset {
+    pub const STANDARD: Ruleset = Ruleset {
+        win_condition: WinCondition::Checkmate,
+        castling: true,
+        starting_health: 1,
+    };
+
+    pub const KING_CAPTURE: Ruleset = Ruleset {
+        win_condition: WinCondition::KingCapture,
+        castling: false,
+        starting_health: 1,
+    };
+
+    /// Offered on the game options screen.
+    pub const STARTING_HEALTH: [u32; 3] = [1, 2, 3];
+}
+
+impl fmt::Display for Ruleset {
+    /// E.g. "Standard rules" or "King capture, no castling, 2 health".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if *self == Ruleset::STANDARD {
+            return write!(f, "Standard rules");
+        }
+
+        let castling = if self.castling { "castling" } else { "no castling" };
+        write!(f, "{}, {}", self.win_condition, castling)?;
+
+        if self.starting_health > 1 {
+            write!(f, ", {} health", self.starting_health)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+pub enum WinCondition {
+    /// Moves leaving the own king attacked are not allowed, a team without moves is checkmated or stalemated.
+    Checkmate,
+    /// Any move goes, the game is won by taking the king.
+    #[strum(serialize = "King capture")]
+    KingCapture,
+}
+
+impl WinCondition {
+    pub const ALL: [WinCondition; 2] = [WinCondition::Checkmate, WinCondition::KingCapture];
+}
+
+
+/// ==========================================================================
+/// Board
+/// ==========================================================================
+/// The units by square, for trying moves out without touching the board in play.
+#[derive(Debug, Clone, Default)]
+pub struct Board {
+    units: HashMap<Position, (Unit, Team, Health)>,
+}
+
+impl Board {
+    pub fn from_query(query: &Query<(&Unit, &Position, &Team, &Health, &Actions)>) -> Self {
+        // Captured units linger for a frame before they are despawned
+        let units = query
+            .iter()
+            .filter(|(_, _, _, health, _)| health.0 > 0)
+            .map(|(&unit, &position, &team, &health, _)| (position, (unit, team, health)))
+            .collect();
+
+        Board { units }
+    }
+
+    pub fn occupant(&self, position: &Position) -> Option<Team> {
+        self.units.get(position).map(|(_, team, _)| *team)
+    }
+
+    /// The board once the unit on `from` moved to `to`, as its action would leave it.
+    pub fn with_move(&self, from: &Position, to: &Position) -> Board {
+        let mut board = self.clone();
+
+        let (unit, team, health) = match board.units.remove(from) {
+            Some(unit) => unit,
+            None => return board,
+        };
+
+        // A unit hit without being taken stays, & so does the attacker
+        let is_hit = match board.units.get_mut(to) {
+            Some((_, target_team, target_health)) if *target_team != team && target_health.0 > 1 => {
+                target_health.0 -= 1;
+                true
+            }
+            _ => false,
+        };
+
+        if is_hit {
+            board.units.insert(*from, (unit, team, health));
+            return board;
+        }
+
+        if unit == Unit::King && is_castling(from, to) {
+            let (rook_from, rook_to) = castling_rook(from, to);
+
+            if let Some(rook) = board.units.remove(&rook_from) {
+                board.units.insert(rook_to, rook);
+            }
+        }
+
+        let unit = match unit {
+            Unit::Pawn if to.y == promotion_row(&team) => Unit::Queen,
+            unit => unit,
+        };
+
+        board.units.insert(*to, (unit, team, health));
+        board
+    }
+
+    /// Whether any unit of the opponent could move onto the king of `team`.
+    pub fn is_in_check(&self, team: Team) -> bool {
+        let occupant = |position: &Position| self.occupant(position);
+
+        let kings: Vec<Position> = self
+            .units
+            .iter()
+            .filter(|(_, (unit, unit_team, _))| *unit == Unit::King && *unit_team == team)
+            .map(|(position, _)| *position)
+            .collect();
+
+        self.units
+            .iter()
+            .filter(|(_, (_, unit_team, _))| *unit_team != team)
+            .any(|(position, (unit, unit_team, _))| {
+                unit_targets(*unit, position, unit_team, &occupant)
+                    .iter()
+                    .any(|target| kings.contains(target))
+            })
+    }
+
+    /// Squares the king on `position` may castle to. Only kings & rooks on their standard starting squares castle, & a
+    /// rook hit without being taken can no longer castle either.
+    pub fn castling_targets(&self, position: &Position, history: &MoveHistory) -> Vec<Position> {
+        let team = match self.units.get(position) {
+            Some((Unit::King, team, _)) => *team,
+            _ => return vec![],
+        };
+
+        let home_row = match team {
+            Team::White => 0,
+            Team::Black => 7,
+        };
+
+        if *position != Position::new(4, home_row) || !history.is_untouched(position) || self.is_in_check(team) {
+            return vec![];
+        }
+
+        [0, 7]
+            .iter()
+            .filter_map(|&rook_x| {
+                let rook_position = Position::new(rook_x, home_row);
+
+                let is_own_rook = match self.units.get(&rook_position) {
+                    Some((Unit::Rook, rook_team, _)) => *rook_team == team,
+                    _ => false,
+                };
+
+                let (left, right) = (rook_x.min(position.x), rook_x.max(position.x));
+                let is_clear = (left + 1..right).all(|x| self.occupant(&Position::new(x, home_row)).is_none());
+
+                if !is_own_rook || !is_clear || !history.is_untouched(&rook_position) {
+                    return None;
+                }
+
+                // The king may not pass through an attack either
+                let direction = if rook_x > position.x { 1 } else { -1 };
+                let passed = Position::new(position.x + direction, home_row);
+                let target = Position::new(position.x + 2 * direction, home_row);
+
+                let is_safe = !self.with_move(position, &passed).is_in_check(team)
+                    && !self.with_move(position, &target).is_in_check(team);
+
+                if is_safe {
+                    Some(target)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// The squares the unit on `position` may move to under the rules.
+    pub fn legal_targets(&self, position: &Position, ruleset: &Ruleset, history: &MoveHistory) -> Vec<Position> {
+        let (unit, team) = match self.units.get(position) {
+            Some((unit, team, _)) => (*unit, *team),
+            None => return vec![],
+        };
+
+        let occupant = |position: &Position| self.occupant(position);
+        let targets = unit_targets(unit, position, &team, &occupant);
+
+        self.apply_rules(position, targets, ruleset, history)
+    }
+
+    pub fn has_legal_move(&self, team: Team, ruleset: &Ruleset, history: &MoveHistory) -> bool {
+        self.units
+            .iter()
+            .filter(|(_, (_, unit_team, _))| *unit_team == team)
+            .any(|(position, _)| !self.legal_targets(position, ruleset, history).is_empty())
+    }
+
+    /// Adds castling & with checkmate rules drops the moves which leave the own king attacked.
+    fn apply_rules(
+        &self,
+        position: &Position,
+        mut targets: Vec<Position>,
+        ruleset: &Ruleset,
+        history: &MoveHistory,
+    ) -> Vec<Position> {
+        let team = match self.occupant(position) {
+            Some(team) => team,
+            None => return targets,
+        };
+
+        if ruleset.castling {
+            targets.extend(self.castling_targets(position, history));
+        }
+
+        if ruleset.win_condition == WinCondition::Checkmate {
+            targets.retain(|target| !self.with_move(position, target).is_in_check(team));
+        }
+
+        targets
+    }
+}
+
+/// The targets of a unit's action under the rules in play, what the unit may do rather than what it can do.
+pub fn legal_targets(
+    action: &Box<dyn Action + Send + Sync>,
+    entity: &Entity,
+    store: &Res<PositionMap<Unit>>,
+    query: &Query<(&Unit, &Position, &Team, &Health, &Actions)>,
+    ruleset: &Ruleset,
+    history: &MoveHistory,
+) -> Vec<Position> {
+    let position = match query.get_component::<Position>(*entity) {
+        Ok(position) => *position,
+        Err(_) => return vec![],
+    };
+
+    let targets = action.list_targets(entity, store, query).collect();
+
+    Board::from_query(query).apply_rules(&position, targets, ruleset, history)
+}
+
+
+/// ==========================================================================
+/// Systems
+/// ==========================================================================
+/// The rules of a game apply from its start, for players & spectators alike.
+pub fn handle_game_started_event(
+    mut reader: Local<EventReader<GameStartedEvent>>,
+    events: Res<Events<GameStartedEvent>>,
+    state: Res<GameState>,
+    mut ruleset: ResMut<Ruleset>,
+) {
+    for _event in reader.iter(&events) {
+        debug!("handle_game_started_event() - playing by {}", state.options.ruleset);
+        *ruleset = state.options.ruleset;
+    }
+}
+
+/// With checkmate rules the game ends once the team to move has no legal move left, in checkmate if its king is
+/// attacked & in stalemate otherwise. Looked at whenever units move, takebacks included.
+pub fn detect_checkmate(
+    ruleset: Res<Ruleset>,
+    mut state: ResMut<GameState>,
+    phase: Res<PhaseState>,
+    moved_query: Query<With<Unit, Mutated<Position>>>,
+    unit_query: Query<(&Unit, &Position, &Team, &Health, &Actions)>,
+) {
+    // Only borrowed mutably once the game is decided, so the state does not change every move
+    let is_undecided = phase.is(AppPhase::Playing) && state.result.is_none();
+    if ruleset.win_condition != WinCondition::Checkmate || !is_undecided || moved_query.iter().next().is_none() {
+        return;
+    }
+
+    // The turn may pass a frame after the units moved, while the history is recorded along with the move
+    let team = state.history.last().map_or(Team::White, |record| record.team.opponent());
+    let board = Board::from_query(&unit_query);
+
+    if board.has_legal_move(team, &ruleset, &state.history) {
+        return;
+    }
+
+    let result = match board.is_in_check(team) {
+        true => GameResult {
+            winner: Some(team.opponent()),
+            reason: GameOverReason::Checkmate,
+        },
+        false => GameResult {
+            winner: None,
+            reason: GameOverReason::Stalemate,
+        },
+    };
+
+    info!("detect_checkmate() - {} has no legal move: {}", team, result.reason);
+    state.end_game(result);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::history::{MoveRecord, UnitSnapshot};
+
+    use Team::{Black, White};
+    use Unit::{King, Knight, Pawn, Queen, Rook};
+
+    #[test]
+    fn attacked_king_is_in_check() {
+        let board = board(&[(King, White, (4, 0)), (Rook, Black, (4, 7)), (King, Black, (0, 7))]);
+
+        assert!(board.is_in_check(White));
+        assert!(!board.is_in_check(Black));
+    }
+
+    #[test]
+    fn blocked_attack_is_no_check() {
+        let board = board(&[
+            (King, White, (4, 0)),
+            (Knight, White, (4, 3)),
+            (Rook, Black, (4, 7)),
+            (King, Black, (0, 7)),
+        ]);
+
+        assert!(!board.is_in_check(White));
+    }
+
+    #[test]
+    fn back_rank_mate_has_no_legal_move() {
+        let board = board(&[
+            (King, White, (6, 0)),
+            (Pawn, White, (5, 1)),
+            (Pawn, White, (6, 1)),
+            (Pawn, White, (7, 1)),
+            (Rook, Black, (0, 0)),
+            (King, Black, (4, 7)),
+        ]);
+
+        assert!(board.is_in_check(White));
+        assert!(!board.has_legal_move(White, &Ruleset::STANDARD, &MoveHistory::default()));
+    }
+
+    #[test]
+    fn blocking_the_check_is_a_legal_move() {
+        let board = board(&[
+            (King, White, (6, 0)),
+            (Pawn, White, (5, 1)),
+            (Pawn, White, (6, 1)),
+            (Pawn, White, (7, 1)),
+            (Knight, White, (3, 1)),
+            (Rook, Black, (0, 0)),
+            (King, Black, (4, 7)),
+        ]);
+
+        let history = MoveHistory::default();
+        let knight_targets = board.legal_targets(&Position::new(3, 1), &Ruleset::STANDARD, &history);
+
+        // Only the knight may move, onto the back rank between the rook & the king
+        assert!(board.has_legal_move(White, &Ruleset::STANDARD, &history));
+        assert_eq!(knight_targets.len(), 2);
+        assert!(knight_targets.contains(&Position::new(1, 0)));
+        assert!(knight_targets.contains(&Position::new(5, 0)));
+        assert!(board.legal_targets(&Position::new(6, 1), &Ruleset::STANDARD, &history).is_empty());
+    }
+
+    #[test]
+    fn stalemate_has_no_legal_move_without_check() {
+        let board = board(&[(King, Black, (7, 7)), (Queen, White, (5, 6)), (King, White, (6, 5))]);
+
+        assert!(!board.is_in_check(Black));
+        assert!(!board.has_legal_move(Black, &Ruleset::STANDARD, &MoveHistory::default()));
+    }
+
+    #[test]
+    fn king_capture_allows_moves_into_check() {
+        let board = board(&[(King, White, (4, 0)), (Rook, Black, (3, 7)), (King, Black, (0, 7))]);
+        let history = MoveHistory::default();
+
+        let standard = board.legal_targets(&Position::new(4, 0), &Ruleset::STANDARD, &history);
+        let king_capture = board.legal_targets(&Position::new(4, 0), &Ruleset::KING_CAPTURE, &history);
+
+        assert!(!standard.contains(&Position::new(3, 0)));
+        assert!(king_capture.contains(&Position::new(3, 0)));
+    }
+
+    #[test]
+    fn castles_to_both_sides() {
+        let board = castling_board(&[]);
+
+        let targets = board.castling_targets(&Position::new(4, 0), &MoveHistory::default());
+
+        assert_eq!(targets, vec![Position::new(2, 0), Position::new(6, 0)]);
+    }
+
+    #[test]
+    fn does_not_castle_out_of_or_through_check() {
+        let history = MoveHistory::default();
+
+        let in_check = castling_board(&[(Rook, Black, (4, 7))]);
+        assert!(in_check.castling_targets(&Position::new(4, 0), &history).is_empty());
+
+        let through_check = castling_board(&[(Rook, Black, (5, 7))]);
+        assert_eq!(through_check.castling_targets(&Position::new(4, 0), &history), vec![Position::new(2, 0)]);
+
+        let into_check = castling_board(&[(Rook, Black, (2, 7))]);
+        assert_eq!(into_check.castling_targets(&Position::new(4, 0), &history), vec![Position::new(6, 0)]);
+    }
+
+    #[test]
+    fn does_not_castle_with_a_moved_or_hit_rook() {
+        let board = castling_board(&[]);
+
+        let moved = history(&[(Rook, White, (7, 0))]);
+        assert_eq!(board.castling_targets(&Position::new(4, 0), &moved), vec![Position::new(2, 0)]);
+
+        // Hit without being taken, the rook stays on its square with less health
+        let hit = history(&[(Rook, White, (0, 0))]);
+        assert_eq!(board.castling_targets(&Position::new(4, 0), &hit), vec![Position::new(6, 0)]);
+
+        let king_moved = history(&[(King, White, (4, 0))]);
+        assert!(board.castling_targets(&Position::new(4, 0), &king_moved).is_empty());
+    }
+
+    #[test]
+    fn castling_moves_the_rook() {
+        let board = castling_board(&[]).with_move(&Position::new(4, 0), &Position::new(6, 0));
+
+        assert_eq!(board.units.get(&Position::new(5, 0)).map(|(unit, _, _)| *unit), Some(Rook));
+        assert_eq!(board.occupant(&Position::new(7, 0)), None);
+    }
+
+    #[test]
+    fn pawns_promote_on_the_far_rank() {
+        let board = board(&[(Pawn, White, (0, 6)), (King, White, (4, 0)), (King, Black, (7, 7))]);
+
+        let promoted = board.with_move(&Position::new(0, 6), &Position::new(0, 7));
+
+        assert_eq!(promoted.units.get(&Position::new(0, 7)).map(|(unit, _, _)| *unit), Some(Queen));
+        assert!(promoted.is_in_check(Black));
+    }
+
+
+    // ==========================================================================
+    // --- Helpers
+    // ==========================================================================
+    fn board(units: &[(Unit, Team, (i32, i32))]) -> Board {
+        let units = units
+            .iter()
+            .map(|&(unit, team, position)| (position.into(), (unit, team, Health(1))))
+            .collect();
+
+        Board { units }
+    }
+
+    /// White's king & rooks on their starting squares, with `others` & a black king out of the way.
+    fn castling_board(others: &[(Unit, Team, (i32, i32))]) -> Board {
+        let mut units = vec![
+            (King, White, (4, 0)),
+            (Rook, White, (0, 0)),
+            (Rook, White, (7, 0)),
+            (King, Black, (0, 7)),
+        ];
+        units.extend_from_slice(others);
+
+        board(&units)
+    }
+
+    /// A history of one move, touching the squares of `units`.
+    fn history(units: &[(Unit, Team, (i32, i32))]) -> MoveHistory {
+        let units = units
+            .iter()
+            .map(|&(unit, team, position)| UnitSnapshot {
+                id: Id::new(),
+                unit,
+                team,
+                position: position.into(),
+                health: Health(1),
+            })
+            .collect();
+
+        MoveHistory::from(vec![MoveRecord { team: Black, units }])
+    }
+}
@@ -0,0 +1,305 @@
+use bevy::prelude::*;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use super::{
+    chat::{ChatHistory, ChatLine},
+    clock::Clocks,
+    history::{MoveHistory, UnitSnapshot},
+    lobby::GameOptions,
+    map::{Map, MapDescriptor, Tile},
+    phase::{AppPhase, PhaseScoped, PhaseState},
+    ConnectionInfo, GameResult, GameStartedEvent, GameState, GameType, Health, PlayerInfo, PlayerType, Team, Unit,
+};
+use crate::prelude::*;
+
+
+/// Where games are saved to & loaded from unless told otherwise.
+pub const DEFAULT_SAVE_PATH: &str = "chess.save";
+
+/// Bumped whenever `SavedGame` changes, saves of other versions are refused.
+const SAVE_VERSION: u32 = 5;
+
+
+/// ==========================================================================
+/// Saved Game
+/// ==========================================================================
+/// Everything needed to pick a game up where it was left.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedGame {
+    version: u32,
+    pub map: MapDescriptor,
+    pub units: Vec<UnitSnapshot>,
+    pub players: Vec<PlayerInfo>,
+    pub active_team: Team,
+    pub options: GameOptions,
+    pub history: MoveHistory,
+    /// The time left to White & Black, `None` for untimed games.
+    pub clocks: Option<(Duration, Duration)>,
+    /// Set for finished games, which are watched as replays rather than resumed.
+    pub result: Option<GameResult>,
+}
+
+impl SavedGame {
+    /// The game in progress with the board as it stands.
+    pub fn capture(
+        state: &GameState,
+        map: MapDescriptor,
+        unit_query: &Query<(&Unit, &Position, &Team, &Health, &Id)>,
+    ) -> Self {
+        let now = Instant::now();
+
+        SavedGame {
+            version: SAVE_VERSION,
+            map,
+            units: unit_query
+                .iter()
+                .map(|(&unit, &position, &team, &health, &id)| UnitSnapshot {
+                    id,
+                    unit,
+                    team,
+                    position,
+                    health,
+                })
+                .collect(),
+            players: state.players.iter().map(|(_, player_info)| player_info.clone()).collect(),
+            active_team: state.active_team,
+            options: state.options.clone(),
+            history: state.history.clone(),
+            clocks: state.clocks.as_ref().map(|clocks| {
+                (
+                    clocks.remaining(Team::White, state.active_team, now),
+                    clocks.remaining(Team::Black, state.active_team, now),
+                )
+            }),
+            result: state.result,
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SaveError> {
+        let bytes = bincode::serialize(self)?;
+        fs::write(path, bytes)?;
+
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, SaveError> {
+        let bytes = fs::read(path)?;
+        let saved: SavedGame = bincode::deserialize(&bytes)?;
+
+        match saved.version {
+            SAVE_VERSION => Ok(saved),
+            version => Err(SaveError::Version(version)),
+        }
+    }
+}
+
+#[cfg(test)]
+impl SavedGame {
+    /// A decided hotseat game, ending with `units` on the board.
+    pub(super) fn finished(units: Vec<UnitSnapshot>, history: MoveHistory) -> Self {
+        SavedGame {
+            version: SAVE_VERSION,
+            map: MapDescriptor::default(),
+            units,
+            players: vec![],
+            active_team: Team::Black,
+            options: GameOptions::default(),
+            history,
+            clocks: None,
+            result: Some(GameResult {
+                winner: None,
+                reason: super::GameOverReason::Stalemate,
+            }),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Encoding(bincode::Error),
+    /// Saved by another version of the game.
+    Version(u32),
+}
+
+impl From<io::Error> for SaveError {
+    fn from(error: io::Error) -> Self {
+        SaveError::Io(error)
+    }
+}
+
+impl From<bincode::Error> for SaveError {
+    fn from(error: bincode::Error) -> Self {
+        SaveError::Encoding(error)
+    }
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "{}", error),
+            SaveError::Encoding(error) => write!(f, "not a saved game: {}", error),
+            SaveError::Version(version) => write!(f, "saved with version {}, expected {}", version, SAVE_VERSION),
+        }
+    }
+}
+
+
+/// ==========================================================================
+/// Events
+/// ==========================================================================
+/// Save the game in progress.
+#[derive(Debug, Clone)]
+pub struct SaveGameEvent {
+    pub path: PathBuf,
+}
+
+/// Resume a saved game. Only hotseat games are saved, so both players sit at this machine.
+#[derive(Debug, Clone)]
+pub struct LoadGameEvent {
+    pub path: PathBuf,
+}
+
+/// Fired when a saved game or replay could not be loaded, for the main menu to tell the player.
+#[derive(Debug, Clone)]
+pub struct LoadFailedEvent {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+
+/// ==========================================================================
+/// Systems
+/// ==========================================================================
+pub fn handle_save_game_event(
+    mut reader: Local<EventReader<SaveGameEvent>>,
+    events: Res<Events<SaveGameEvent>>,
+    state: Res<GameState>,
+    phase: Res<PhaseState>,
+    mut history: ResMut<ChatHistory>,
+    map_query: Query<With<Map, &Dimensions>>,
+    tile_query: Query<(&Tile, &Position)>,
+    unit_query: Query<(&Unit, &Position, &Team, &Health, &Id)>,
+) {
+    for event in reader.iter(&events) {
+        // Only games still being played are worth resuming
+        if !phase.is(AppPhase::Playing) || state.connection_info.is_spectator() {
+            warn!("handle_save_game_event() - no game to save");
+            continue;
+        }
+
+        // Loaded games have nobody to play against but the players at this machine
+        if !state.game_type.is_hotseat() {
+            warn!("handle_save_game_event() - only hotseat games are saved");
+            history.push(ChatLine::System("Only hotseat games can be saved".into()));
+            continue;
+        }
+
+        let map = MapDescriptor::from_query(&map_query, &tile_query);
+        let saved = SavedGame::capture(&state, map, &unit_query);
+
+        let line = match saved.write(&event.path) {
+            Ok(()) => {
+                info!("handle_save_game_event() - saved to {:?}", event.path);
+                format!("Game saved to {}", event.path.display())
+            }
+            Err(error) => {
+                warn!("handle_save_game_event() - unable to save to {:?}: {}", event.path, error);
+                format!("Unable to save the game: {}", error)
+            }
+        };
+
+        history.push(ChatLine::System(line));
+    }
+}
+
+/// Loaded games are resumed as the hotseat games they were saved as, the board & clocks as they were saved.
+pub fn handle_load_game_event(
+    mut commands: Commands,
+    mut reader: Local<EventReader<LoadGameEvent>>,
+    events: Res<Events<LoadGameEvent>>,
+    mut state: ResMut<GameState>,
+    phase: Res<PhaseState>,
+    mut game_started_events: ResMut<Events<GameStartedEvent>>,
+    mut load_failed_events: ResMut<Events<LoadFailedEvent>>,
+) {
+    for event in reader.iter(&events) {
+        if !phase.is(AppPhase::MainMenu) {
+            warn!("handle_load_game_event() - games are loaded from the main menu");
+            continue;
+        }
+
+        let mut fail = |reason: String| {
+            warn!("handle_load_game_event() - unable to load {:?}: {}", event.path, reason);
+            load_failed_events.send(LoadFailedEvent {
+                path: event.path.clone(),
+                reason,
+            });
+        };
+
+        let saved = match SavedGame::read(&event.path) {
+            Ok(saved) => saved,
+            Err(error) => {
+                fail(error.to_string());
+                continue;
+            }
+        };
+
+        if saved.result.is_some() {
+            fail("the game is over, watch its replay instead".into());
+            continue;
+        }
+
+        info!("handle_load_game_event() - resuming {:?}", event.path);
+
+        let SavedGame {
+            map,
+            units,
+            players,
+            active_team,
+            options,
+            history,
+            clocks,
+            ..
+        } = saved;
+
+        let mut players = players.into_iter();
+        let first = match players.next() {
+            Some(player_info) => player_info,
+            None => {
+                fail("the game has no players".into());
+                continue;
+            }
+        };
+
+        state.init_local_player(first);
+        state.players.extend(players.map(|player_info| (PlayerType::Local, player_info)));
+        state.connection_info = ConnectionInfo::Server;
+        state.game_type = GameType::Hotseat;
+        state.game_id = Some(Id::new());
+        state.active_team = active_team;
+        state.history = history;
+        state.clocks = clocks
+            .and_then(|(white, black)| Clocks::resume(options.time_control, white, black, Instant::now()));
+        state.options = options;
+        state.hand_over_hotseat();
+
+        debug!("handle_load_game_event() - spawning {} units", units.len());
+
+        map.spawn_with_commands(&mut commands)
+            .with(PhaseScoped::GAME)
+            .with_children(|commands| {
+                for snapshot in units.iter() {
+                    commands.spawn(snapshot.components());
+                }
+            });
+
+        game_started_events.send(GameStartedEvent);
+    }
+}
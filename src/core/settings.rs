@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+use log::{info, warn};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, io, path::Path};
+use strum::Display;
+
+
+/// Where settings are read from on startup & written to whenever a change is committed.
+pub const SETTINGS_PATH: &str = "settings.ron";
+
+pub const DEFAULT_PORT: &str = "12351";
+
+
+/// ==========================================================================
+/// Settings
+/// ==========================================================================
+/// Preferences kept between launches. The `--port` & `--scale` flags override them for a single launch, while `--name`
+/// & `--remote` are remembered.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub player_name: String,
+    pub port: String,
+    pub remote_addr: Option<String>,
+    /// Window size scale, applied on the next launch.
+    pub scale: f32,
+    /// Master volume, between 0 and 1. Nothing plays sound yet.
+    pub volume: f32,
+    pub theme: Theme,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            player_name: "Player".into(),
+            port: DEFAULT_PORT.into(),
+            remote_addr: None,
+            scale: 1.0,
+            volume: 1.0,
+            theme: Theme::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn read(path: &Path) -> Result<Self, SettingsError> {
+        let text = fs::read_to_string(path)?;
+        let settings = ron::de::from_str(&text)?;
+
+        Ok(settings)
+    }
+
+    /// A missing file is a first launch, any other problem is logged & the defaults used.
+    pub fn read_or_default(path: &Path) -> Self {
+        match Self::read(path) {
+            Ok(settings) => settings,
+            Err(SettingsError::Io(error)) if error.kind() == io::ErrorKind::NotFound => Settings::default(),
+            Err(error) => {
+                warn!("Settings::read_or_default() - unable to read {:?}: {}", path, error);
+                Settings::default()
+            }
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SettingsError> {
+        let text = ron::ser::to_string_pretty(self, PrettyConfig::new())?;
+        fs::write(path, text)?;
+
+        Ok(())
+    }
+}
+
+/// Asks for the settings to be written, sent once a change is committed rather than on every keystroke.
+#[derive(Debug, Clone, Copy)]
+pub struct SaveSettingsEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq, Display, Serialize, Deserialize)]
+pub enum Theme {
+    Classic,
+    Dark,
+    Light,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::Classic
+    }
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Classic, Theme::Dark, Theme::Light];
+
+    /// Background behind the board & menus.
+    pub fn clear_color(&self) -> Color {
+        match self {
+            Theme::Classic => Color::rgb(0.4, 0.4, 0.4),
+            Theme::Dark => Color::rgb(0.08, 0.08, 0.1),
+            Theme::Light => Color::rgb(0.85, 0.85, 0.8),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(io::Error),
+    Format(ron::Error),
+}
+
+impl From<io::Error> for SettingsError {
+    fn from(error: io::Error) -> Self {
+        SettingsError::Io(error)
+    }
+}
+
+impl From<ron::Error> for SettingsError {
+    fn from(error: ron::Error) -> Self {
+        SettingsError::Format(error)
+    }
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Io(error) => write!(f, "{}", error),
+            SettingsError::Format(error) => write!(f, "not a settings file: {}", error),
+        }
+    }
+}
+
+
+/// ==========================================================================
+/// Systems
+/// ==========================================================================
+/// Text settings change with every key, so they are only written once their field is left.
+pub fn persist_settings(
+    mut reader: Local<EventReader<SaveSettingsEvent>>,
+    events: Res<Events<SaveSettingsEvent>>,
+    settings: Res<Settings>,
+) {
+    // Any number of requests in a frame is a single write
+    if reader.iter(&events).count() == 0 {
+        return;
+    }
+
+    match settings.write(Path::new(SETTINGS_PATH)) {
+        Ok(()) => info!("persist_settings() - saved to {}", SETTINGS_PATH),
+        Err(error) => warn!("persist_settings() - unable to save to {}: {}", SETTINGS_PATH, error),
+    }
+}
+
+pub fn apply_theme(settings: ChangedRes<Settings>, mut clear_color: ResMut<ClearColor>) {
+    let color = settings.theme.clear_color();
+
+    if clear_color.0 != color {
+        clear_color.0 = color;
+    }
+}
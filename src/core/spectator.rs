@@ -0,0 +1,177 @@
+use bevy::prelude::*;
+use log::{debug, info};
+use std::net::SocketAddr;
+
+use super::{
+    clock,
+    game::GameDescriptor,
+    history::{MoveHistory, UndoMoveEvent, UnitSnapshot},
+    lobby::{GameOptions, Lobby},
+    map::MapDescriptor,
+    outbox::Outbox,
+    protocol::ProtocolState,
+    unit::{ActionExecuted, Health},
+    ConnectionInfo, GameState, GameType, Message, PlayerInfo, PlayerType, Team, Unit,
+};
+use crate::prelude::*;
+
+
+/// ==========================================================================
+/// Events
+/// ==========================================================================
+/// Watch a started game on the host at `server_addr` without taking part in it.
+#[derive(Debug, Clone)]
+pub struct SpectateGameEvent {
+    pub server_addr: SocketAddr,
+    pub game_id: Id,
+}
+
+
+/// ==========================================================================
+/// Systems
+/// ==========================================================================
+pub fn handle_spectate_game_event(
+    mut reader: Local<EventReader<SpectateGameEvent>>,
+    events: Res<Events<SpectateGameEvent>>,
+    mut state: ResMut<GameState>,
+    mut protocol: ResMut<ProtocolState>,
+    mut outbox: ResMut<Outbox>,
+) {
+    for event in reader.iter(&events) {
+        debug!("handle_spectate_game_event() - spectating game: {:?}", event);
+
+        state.local_player_info = PlayerInfo {
+            name: "Spectator".into(),
+            team: Team::White,
+        };
+        state.players = vec![];
+        state.connection_info = ConnectionInfo::Spectator;
+        state.game_type = GameType::Networked;
+        state.game_id = Some(event.game_id);
+        state.result = None;
+        state.offer = None;
+        state.history = MoveHistory::default();
+        state.clocks = None;
+
+        protocol.send(&mut outbox, event.server_addr, Message::SpectateRequest(event.game_id));
+    }
+}
+
+/// Hosts relay every executed action to their spectators.
+pub fn forward_actions_to_spectators(
+    mut reader: Local<EventReader<ActionExecuted>>,
+    events: Res<Events<ActionExecuted>>,
+    state: Res<GameState>,
+    mut outbox: ResMut<Outbox>,
+    id_query: Query<(Entity, &Id)>,
+) {
+    for ActionExecuted(entity, _index, position) in reader.iter(&events) {
+        if state.spectators.is_empty() {
+            continue;
+        }
+
+        let id = match id_query.get_component::<Id>(*entity) {
+            Ok(id) => *id,
+            Err(_) => continue,
+        };
+
+        debug!("forward_actions_to_spectators() - {:?} to {:?}", id, position);
+
+        let message = Message::MoveRequest(id, *position);
+
+        for addr in state.spectators.iter() {
+            outbox.send(*addr, message.clone());
+        }
+    }
+}
+
+/// Hosts relay takebacks to their spectators as well.
+pub fn forward_undo_to_spectators(
+    mut reader: Local<EventReader<UndoMoveEvent>>,
+    events: Res<Events<UndoMoveEvent>>,
+    state: Res<GameState>,
+    mut outbox: ResMut<Outbox>,
+) {
+    for _event in reader.iter(&events) {
+        let game_id = match state.game_id {
+            Some(game_id) if state.connection_info.is_server() => game_id,
+            _ => continue,
+        };
+
+        for addr in state.spectators.iter() {
+            outbox.send(*addr, Message::UndoMove(game_id));
+        }
+    }
+}
+
+
+// ==========================================================================
+// --- Message Handlers
+// ==========================================================================
+pub(super) fn handle_spectate_request(
+    state: &mut ResMut<GameState>,
+    lobby: &Lobby,
+    outbox: &mut Outbox,
+    from: SocketAddr,
+    game_id: Id,
+    unit_query: &Query<(&Team, &Unit, &Position, &Id, &Health)>,
+) {
+    info!("handle_spectate_request() - {:?} for game {:?}", from, game_id);
+
+    let is_started = lobby.get(&game_id).map_or(false, |game| game.started);
+
+    if state.game_id != Some(game_id) || !is_started {
+        outbox.send(from, Message::JoinRejected("Game is not in progress".into()));
+        return;
+    }
+
+    // Send the current position rather than the starting one, units hit along the way included
+    let units = unit_query
+        .iter()
+        .map(|(&team, &unit, &position, &id, &health)| UnitSnapshot {
+            id,
+            unit,
+            team,
+            position,
+            health,
+        })
+        .collect();
+
+    let game_descriptor = GameDescriptor {
+        map: MapDescriptor::default(),
+        units,
+    };
+
+    let players = state.players.iter().map(|(_, player_info)| player_info.clone()).collect();
+
+    let message = Message::SpectateResponse(players, state.active_team, state.options.clone(), game_descriptor);
+    outbox.send(from, message);
+
+    if let Some(message) = clock::sync_message(state) {
+        outbox.send(from, message);
+    }
+
+    state.spectators.push(from);
+}
+
+pub(super) fn handle_spectate_response(
+    commands: &mut Commands,
+    state: &mut ResMut<GameState>,
+    from: SocketAddr,
+    players: Vec<PlayerInfo>,
+    active_team: Team,
+    options: GameOptions,
+    game_descriptor: GameDescriptor,
+) {
+    info!("handle_spectate_response()");
+
+    // Both players are reached through the host
+    state.players = players
+        .into_iter()
+        .map(|player_info| (PlayerType::Remote(from), player_info))
+        .collect();
+    state.active_team = active_team;
+    state.options = options;
+
+    game_descriptor.spawn_with_commands(commands);
+}
@@ -1,4 +1,10 @@
-use crate::prelude::*;
+use crate::{
+    core::{
+        history::MoveHistory,
+        ruleset::{legal_targets, Ruleset},
+    },
+    prelude::*,
+};
 use bevy::prelude::*;
 use derive_more::{Deref, From, Into};
 use serde::{Deserialize, Serialize};
@@ -19,7 +25,7 @@ pub struct UnitComponents {
 // ==============================================================================
 // --- Components
 // ==============================================================================
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, Display)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Display, PartialEq, Eq)]
 pub enum Unit {
     Pawn,
     Bishop,
@@ -35,6 +41,15 @@ pub enum Team {
     Black,
 }
 
+impl Team {
+    pub fn opponent(&self) -> Team {
+        match self {
+            Team::White => Team::Black,
+            Team::Black => Team::White,
+        }
+    }
+}
+
 
 #[derive(Debug, Copy, Clone, From, Into, Deref, Serialize, Deserialize)]
 pub struct Health(pub u32);
@@ -67,20 +82,25 @@ pub trait Action {
 }
 
 
+/// Whether the rules in play allow the action onto `target`, see `ruleset::legal_targets()`.
 pub fn is_action_valid(
     action: &Box<dyn Action + Send + Sync>,
     entity: &Entity,
     target: &Position,
     store: &Res<PositionMap<Unit>>,
     query: &Query<(&Unit, &Position, &Team, &Health, &Actions)>,
+    ruleset: &Ruleset,
+    history: &MoveHistory,
 ) -> bool {
-    action.list_targets(entity, store, query).any(|p| p == *target)
+    legal_targets(action, entity, store, query, ruleset, history).contains(target)
 }
 
 #[derive(Debug, Copy, Clone)]
 pub enum ActionResult {
     SetPosition(Entity, Position),
     SetHealth(Entity, Health),
+    /// The unit becomes another, as a pawn reaching the far rank does.
+    Promote(Entity, Unit),
 }
 
 
@@ -1,13 +1,17 @@
 mod components;
 pub use components::*;
 
-use super::{GameState, Message, PlayerType};
+use super::{
+    outbox::Outbox,
+    phase::{AppPhase, PhaseState},
+    ruleset::Ruleset,
+    GameOverReason, GameResult, GameState, Message, PlayerType,
+};
 use crate::prelude::*;
 use bevy::prelude::*;
 use std::ops::Deref;
 
-use bevy_networking::{NetworkDelivery, NetworkResource};
-use log::debug;
+use log::{debug, warn};
 use std::net::SocketAddr;
 
 
@@ -24,7 +28,8 @@ impl Plugin for UnitPlugin {
             .add_system(handle_unit_cmd_system.system())
             .add_system(handle_health_changed.system())
             .add_system(handle_action_executed_system.system())
-            .add_system(handle_unit_spawned.system());
+            .add_system(handle_unit_spawned.system())
+            .add_system(handle_unit_changed.system());
     }
 }
 
@@ -37,19 +42,26 @@ fn handle_unit_cmd_system(
     mut action_events: ResMut<Events<ActionExecuted>>,
     store: Res<PositionMap<Unit>>,
     game_state: Res<GameState>,
-    mut net: ResMut<NetworkResource>,
+    ruleset: Res<Ruleset>,
+    phase: Res<PhaseState>,
+    mut outbox: ResMut<Outbox>,
     action_query: Query<(&Unit, &Position, &Team, &Health, &Actions)>,
     id_query: Query<(Entity, &Id)>,
 ) {
     for cmd in reader.iter(&events) {
         debug!("handle_unit_cmd() {:?}", cmd);
 
+        if !phase.is(AppPhase::Playing) {
+            warn!("handle_unit_cmd() - ignoring {:?} during {}", cmd, phase.current());
+            continue;
+        }
+
         match cmd {
             UnitCmd::ExecuteAction(entity, index, pos) => {
                 let actions = action_query.get_component::<Actions>(*entity).unwrap();
                 let action = actions.get(*index).unwrap();
 
-                if !is_action_valid(action, &entity, &pos, &store, &action_query) {
+                if !is_action_valid(action, &entity, &pos, &store, &action_query, &ruleset, &game_state.history) {
                     debug!("handle_unit_cmd() - target position is invalid: {:?}", pos);
                     return;
                 }
@@ -58,22 +70,27 @@ fn handle_unit_cmd_system(
 
                 debug!("handle_unit_cmd() - unit id: {:?}", id);
 
-                let delivery = NetworkDelivery::ReliableSequenced(Some(1));
-                let message = Message::MoveRequest(id, pos.clone()).to_bytes().unwrap();
+                // Hotseat games have no one to tell, both players watch the same board
+                if !game_state.game_type.is_hotseat() {
+                    let message = Message::MoveRequest(id, pos.clone());
 
-                let remote_addr = game_state
-                    .players
-                    .iter()
-                    .find_map(|(player_type, player_info)| -> Option<SocketAddr> {
-                        if let PlayerType::Remote(addr) = player_type {
-                            return Some(addr.clone());
-                        }
+                    let remote_addr = game_state
+                        .players
+                        .iter()
+                        .find_map(|(player_type, player_info)| -> Option<SocketAddr> {
+                            if let PlayerType::Remote(addr) = player_type {
+                                return Some(addr.clone());
+                            }
 
-                        None
-                    })
-                    .unwrap();
+                            None
+                        });
+
+                    match remote_addr {
+                        Some(remote_addr) => outbox.send(remote_addr, message),
+                        None => warn!("handle_unit_cmd() - no remote player to send the move to"),
+                    }
+                }
 
-                net.send(remote_addr, &message, delivery).unwrap();
                 action_events.send(ActionExecuted(*entity, *index, *pos));
             }
         }
@@ -85,8 +102,10 @@ fn handle_action_executed_system(
     mut reader: Local<EventReader<ActionExecuted>>,
     events: Res<Events<ActionExecuted>>,
     mut action_events: ResMut<Events<ActionResult>>,
+    mut game_state: ResMut<GameState>,
     store: Res<PositionMap<Unit>>,
     action_query: Query<(&Unit, &Position, &Team, &Health, &Actions)>,
+    snapshot_query: Query<(&Unit, &Position, &Team, &Health, &Id)>,
 ) {
     for event in reader.iter(&events) {
         debug!("handle_action_executed() {:?}", event);
@@ -95,8 +114,14 @@ fn handle_action_executed_system(
 
         let actions = action_query.get_component::<Actions>(*entity).unwrap();
         let action = actions.get(*index).unwrap();
+        let &team = action_query.get_component::<Team>(*entity).unwrap();
 
-        for result in action.execute(&entity, &pos, &store, &action_query) {
+        let results: Vec<ActionResult> = action.execute(&entity, &pos, &store, &action_query).collect();
+
+        // The units are snapshotted before the results apply, so the move can be taken back
+        game_state.history.record(team, &results, &snapshot_query);
+
+        for result in results {
             action_events.send(result);
         }
     }
@@ -105,7 +130,7 @@ fn handle_action_executed_system(
 fn handle_action_result(
     mut reader: Local<EventReader<ActionResult>>,
     events: Res<Events<ActionResult>>,
-    mut query: Query<With<Unit, (&mut Position, &mut Health)>>,
+    mut query: Query<(&mut Unit, &mut Position, &mut Health, &mut Actions, &Team, &Id)>,
 ) {
     for result in reader.iter(&events) {
         debug!("handle_action_result() {:?}", result);
@@ -117,6 +142,12 @@ fn handle_action_result(
             ActionResult::SetHealth(entity, health) => {
                 query.set(*entity, *health).unwrap();
             }
+            ActionResult::Promote(entity, promoted) => {
+                let (mut unit, position, _, mut actions, &team, &id) = query.get_mut(*entity).unwrap();
+
+                *actions = UnitComponents::from((team, *promoted, *position, id)).actions;
+                *unit = *promoted;
+            }
         }
     }
 }
@@ -124,15 +155,23 @@ fn handle_action_result(
 fn handle_health_changed(
     mut commands: Commands,
     mut unit_position_map: ResMut<PositionMap<Unit>>,
-    query: Query<With<Unit, (Entity, Mutated<Health>)>>,
+    mut game_state: ResMut<GameState>,
+    query: Query<(Entity, &Unit, &Team, Mutated<Health>)>,
 ) {
-    for (entity, health) in query.iter() {
+    for (entity, unit, team, health) in query.iter() {
         debug!("handle_health_changed() {:?} {:?}", entity, *health);
 
         if health.0 == 0 {
             debug!("!!!Unit reduced to 0 health: {:?}", entity);
             commands.despawn(entity);
             unit_position_map.remove_entity(&entity);
+
+            if let Unit::King = unit {
+                game_state.end_game(GameResult {
+                    winner: Some(team.opponent()),
+                    reason: GameOverReason::KingCaptured,
+                });
+            }
         }
     }
 }
@@ -152,6 +191,16 @@ fn handle_unit_spawned(
     }
 }
 
+/// Promoted units, & units a takeback turned back into pawns, look the part.
+fn handle_unit_changed(
+    materials: Res<UnitMaterials>,
+    mut query: Query<(Mutated<Unit>, &Team, &mut Handle<ColorMaterial>)>,
+) {
+    for (unit, team, mut material) in query.iter_mut() {
+        *material = materials.get_unit_material(&*unit, team);
+    }
+}
+
 
 /// ==========================================================================
 /// Resources
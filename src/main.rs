@@ -1,12 +1,24 @@
 use bevy::prelude::*;
-use bevy_networking::{NetworkResource, NetworkingPlugin};
+use bevy_networking::{
+    EncryptionConfig, LaminarConfig, LinkConditionerConfig, NetworkResource, NetworkingPlugin, RateLimitConfig,
+    TcpConfig, Transport, ViolationPolicy, WebSocketConfig,
+};
 use clap::Clap;
 
-use log::info;
-use std::net::SocketAddr;
+use log::{info, warn};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use chess::{
-    core::{CorePlugin, AppConfig},
+    core::{
+        replay::LoadReplayEvent,
+        save::LoadGameEvent,
+        settings::{Settings, SETTINGS_PATH},
+        AppConfig, CorePlugin,
+    },
     ui::UIPlugin,
 };
 
@@ -15,13 +27,19 @@ use chess::{
 #[clap(version = "3.0", author = "Alec McCormick <alecs.mccormick@gmail.com>")]
 struct Opts {
     /// Local server port to use. Multiple clients running on the same machine must each use a unique port.
-    #[clap(short, long, default_value = "12351")]
-    pub port: String,
+    /// Defaults to the port in the settings file, 12351 unless changed.
+    #[clap(short, long)]
+    pub port: Option<String>,
 
     /// Remote address for other client, Ex: 127.0.0.1:12350 to connect to a client running locally on port 12350.
+    /// Remembered in the settings file for later launches.
     #[clap(short, long)]
     pub remote: Option<String>,
 
+    /// Player name, remembered in the settings file for later launches.
+    #[clap(short, long)]
+    pub name: Option<String>,
+
     /// Window Width
     #[clap(short, long, default_value = "1680")]
     pub width: u32,
@@ -34,9 +52,78 @@ struct Opts {
     #[clap(long, default_value = "Chess!")]
     pub title: String,
 
-    /// Window Size Scale
-    #[clap(long, default_value = "1.0")]
-    pub scale: f32,
+    /// Window Size Scale, defaults to the scale in the settings file.
+    #[clap(long)]
+    pub scale: Option<f32>,
+
+    /// Connect over TCP instead of UDP, for networks which block UDP. Both players must use the same transport.
+    #[clap(long)]
+    pub tcp: bool,
+
+    /// Connect over WebSockets, e.g. through an HTTP reverse proxy. Both players must use the same transport.
+    #[clap(long, conflicts_with = "tcp")]
+    pub websocket: bool,
+
+    /// Simulated packet loss, between 0 and 1. Use with the other --sim flags to test bad connections locally.
+    #[clap(long, default_value = "0.0")]
+    pub sim_loss: f32,
+
+    /// Simulated latency in milliseconds.
+    #[clap(long, default_value = "0")]
+    pub sim_latency: u64,
+
+    /// Simulated jitter in milliseconds, added to or taken from the latency of each packet.
+    #[clap(long, default_value = "0")]
+    pub sim_jitter: u64,
+
+    /// Simulated chance, between 0 and 1, of an unreliable packet arriving twice.
+    #[clap(long, default_value = "0.0")]
+    pub sim_duplication: f32,
+
+    /// Encrypt & authenticate all traffic. Both players must enable it.
+    #[clap(long)]
+    pub encrypt: bool,
+
+    /// Room password, only players with the same password can connect. Implies --encrypt.
+    #[clap(long)]
+    pub password: Option<String>,
+
+    /// Resume a saved game, Ex: chess.save. Both players take turns at this machine.
+    #[clap(long, parse(from_os_str))]
+    pub load: Option<PathBuf>,
+
+    /// Watch a recorded game, Ex: replay.save. Finished games are recorded automatically.
+    #[clap(long, parse(from_os_str), conflicts_with = "load")]
+    pub replay: Option<PathBuf>,
+}
+
+impl Opts {
+    fn link_conditioner(&self) -> Option<LinkConditionerConfig> {
+        let is_enabled =
+            self.sim_loss > 0.0 || self.sim_latency > 0 || self.sim_jitter > 0 || self.sim_duplication > 0.0;
+
+        if !is_enabled {
+            return None;
+        }
+
+        Some(LinkConditionerConfig {
+            packet_loss: self.sim_loss,
+            latency: Duration::from_millis(self.sim_latency),
+            jitter: Duration::from_millis(self.sim_jitter),
+            duplication: self.sim_duplication,
+            ..Default::default()
+        })
+    }
+
+    fn encryption(&self) -> Option<EncryptionConfig> {
+        if !self.encrypt && self.password.is_none() {
+            return None;
+        }
+
+        Some(EncryptionConfig {
+            room_password: self.password.clone(),
+        })
+    }
 }
 
 
@@ -44,11 +131,35 @@ fn main() {
     env_logger::init();
 
     let opts: Opts = Opts::parse();
+    let mut settings = Settings::read_or_default(Path::new(SETTINGS_PATH));
+
+    // The port & scale flags override the settings for this launch only, the name & remote are remembered
+    if let Some(name) = &opts.name {
+        settings.player_name = name.clone();
+    }
+
+    if let Some(remote) = &opts.remote {
+        settings.remote_addr = Some(remote.clone());
+    }
+
+    if opts.name.is_some() || opts.remote.is_some() {
+        if let Err(error) = settings.write(Path::new(SETTINGS_PATH)) {
+            warn!("Unable to save to {}: {}", SETTINGS_PATH, error);
+        }
+    }
+
+    let scale = opts.scale.unwrap_or(settings.scale);
 
     let config = AppConfig {
-        port: opts.port,
-        remote_addr: opts.remote,
-        scale: opts.scale,
+        link_conditioner: opts.link_conditioner(),
+        encryption: opts.encryption(),
+        tcp: opts.tcp,
+        websocket: opts.websocket,
+        port: opts.port.clone().unwrap_or_else(|| settings.port.clone()),
+        remote_addr: settings.remote_addr.clone(),
+        scale,
+        load: opts.load,
+        replay: opts.replay,
     };
 
     println!("App Config is: {:?}", config);
@@ -56,10 +167,12 @@ fn main() {
 
     App::build()
         .add_resource(config)
+        .add_resource(ClearColor(settings.theme.clear_color()))
+        .add_resource(settings)
         .add_resource(WindowDescriptor {
             title: opts.title,
-            width: ((opts.width as f32) * opts.scale) as u32,
-            height: ((opts.height as f32) * opts.scale) as u32,
+            width: ((opts.width as f32) * scale) as u32,
+            height: ((opts.height as f32) * scale) as u32,
             vsync: false,
             resizable: false,
             ..Default::default()
@@ -76,10 +189,51 @@ fn setup(
     config: Res<AppConfig>,
     mut net: ResMut<NetworkResource>,
     mut events: ResMut<Events<chess::ui::CreateMainMenuEvent>>,
+    mut load_game_events: ResMut<Events<LoadGameEvent>>,
+    mut load_replay_events: ResMut<Events<LoadReplayEvent>>,
 ) {
-    net.bind(format!("0.0.0.0:{}", config.port)).unwrap();
+    let link_conditioner = config.link_conditioner.clone();
+    let encryption = config.encryption.clone();
+
+    // A game of chess is a trickle of messages, a peer anywhere near the limit is up to no good
+    let rate_limit = Some(RateLimitConfig {
+        policy: ViolationPolicy::Ban(Duration::from_secs(60)),
+        ..Default::default()
+    });
+
+    let transport = match (config.tcp, config.websocket) {
+        (true, _) => Transport::Tcp(TcpConfig {
+            link_conditioner,
+            encryption,
+            rate_limit,
+            ..Default::default()
+        }),
+        (_, true) => Transport::WebSocket(WebSocketConfig {
+            link_conditioner,
+            encryption,
+            rate_limit,
+            ..Default::default()
+        }),
+        _ => Transport::Laminar(LaminarConfig {
+            link_conditioner,
+            encryption,
+            rate_limit,
+            ..Default::default()
+        }),
+    };
+
+    net.bind_with_transport(format!("0.0.0.0:{}", config.port), transport).unwrap();
     info!("App Setup - Spawning Main Menu");
 
     events.send(chess::ui::CreateMainMenuEvent);
+
+    // The main menu stays up should the save fail to load
+    if let Some(path) = &config.load {
+        load_game_events.send(LoadGameEvent { path: path.clone() });
+    }
+
+    if let Some(path) = &config.replay {
+        load_replay_events.send(LoadReplayEvent { path: path.clone() });
+    }
     // commands.spawn((chess::ui::MainMenu,));
 }
@@ -73,6 +73,11 @@ impl<K> EntityMap<K> where K: Eq + Hash + Clone {
         self.entity_to_key.remove(entity);
         Some(())
     }
+
+    pub fn clear(&mut self) {
+        self.key_to_entity.clear();
+        self.entity_to_key.clear();
+    }
 }
 
 // ==========================================================================
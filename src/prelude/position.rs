@@ -77,6 +77,10 @@ impl<T> PositionMap<T> {
     pub fn remove_entity(&mut self, entity: &Entity) -> Option<()> {
         self.entity_map.remove_entity(entity)
     }
+
+    pub fn clear(&mut self) {
+        self.entity_map.clear()
+    }
 }
 
 // ==========================================================================
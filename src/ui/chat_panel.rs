@@ -0,0 +1,200 @@
+use bevy::{input::keyboard::KeyboardInput, prelude::*};
+use log::debug;
+
+use super::text_input::{edit_text, is_shift_pressed, pressed_key};
+use crate::core::{
+    chat::{ChatHistory, ChatLine, SendChatEvent, MAX_CHAT_MESSAGE_LENGTH},
+    phase::PhaseScoped,
+    GameStartedEvent,
+};
+
+/// Number of scrollback lines shown at once.
+const CHAT_VISIBLE_LINES: usize = 8;
+
+
+pub struct ChatPanelPlugin;
+impl Plugin for ChatPanelPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ChatPanelState>()
+            .add_system(handle_game_started_event.system())
+            .add_system(handle_keyboard_input.system())
+            .add_system(ChatLineView::handle_history_changed.system())
+            .add_system(ChatLineView::handle_panel_state_changed.system());
+    }
+}
+
+/// Text being typed & how far the scrollback is scrolled up, in lines.
+#[derive(Debug, Default)]
+pub struct ChatPanelState {
+    input: String,
+    scroll: usize,
+}
+
+
+fn handle_game_started_event(
+    mut commands: Commands,
+    mut reader: Local<EventReader<GameStartedEvent>>,
+    events: Res<Events<GameStartedEvent>>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    panel_query: Query<With<ChatPanelView, Entity>>,
+) {
+    for _event in reader.iter(&events) {
+        // Rematches keep the panel & the scrollback of the previous game
+        if panel_query.iter().next().is_some() {
+            continue;
+        }
+
+        debug!("handle_game_started_event() - spawn chat panel");
+
+        let font = asset_server.load("fonts/FiraMono-Medium.ttf");
+
+        commands
+            .spawn(ChatPanelView::bundle(materials.add(Color::rgba(0.0, 0.0, 0.0, 0.5).into())))
+            .with(ChatPanelView)
+            .with(PhaseScoped::GAME);
+
+        commands.with_children(|children| {
+            for index in 0..CHAT_VISIBLE_LINES {
+                children
+                    .spawn(TextComponents {
+                        text: text(String::new(), font.clone()),
+                        ..Default::default()
+                    })
+                    .with(ChatLineView(index));
+            }
+
+            children
+                .spawn(TextComponents {
+                    text: text("> ".into(), font.clone()),
+                    ..Default::default()
+                })
+                .with(ChatInputView);
+        });
+    }
+}
+
+/// Typing goes to the chat input while the panel is open, `Return` sends & `PageUp`/`PageDown` scroll.
+fn handle_keyboard_input(
+    mut reader: Local<EventReader<KeyboardInput>>,
+    events: Res<Events<KeyboardInput>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut panel_state: ResMut<ChatPanelState>,
+    history: Res<ChatHistory>,
+    mut send_chat_events: ResMut<Events<SendChatEvent>>,
+    panel_query: Query<With<ChatPanelView, Entity>>,
+) {
+    let is_open = panel_query.iter().next().is_some();
+
+    for event in reader.iter(&events) {
+        let key_code = match pressed_key(event) {
+            Some(key_code) if is_open => key_code,
+            _ => continue,
+        };
+
+        match key_code {
+            KeyCode::Return => {
+                if panel_state.input.is_empty() {
+                    continue;
+                }
+
+                let text = std::mem::take(&mut panel_state.input);
+                send_chat_events.send(SendChatEvent(text));
+                panel_state.scroll = 0;
+            }
+            KeyCode::PageUp => {
+                let max_scroll = history.lines().len().saturating_sub(CHAT_VISIBLE_LINES);
+                panel_state.scroll = (panel_state.scroll + CHAT_VISIBLE_LINES).min(max_scroll);
+            }
+            KeyCode::PageDown => {
+                panel_state.scroll = panel_state.scroll.saturating_sub(CHAT_VISIBLE_LINES);
+            }
+            _ => {
+                let shift = is_shift_pressed(&keyboard_input);
+                edit_text(&mut panel_state.input, key_code, shift, MAX_CHAT_MESSAGE_LENGTH);
+            }
+        }
+    }
+}
+
+
+struct ChatPanelView;
+impl ChatPanelView {
+    fn bundle(material: Handle<ColorMaterial>) -> NodeComponents {
+        NodeComponents {
+            style: Style {
+                size: Size::new(Val::Px(560.0), Val::Auto),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::FlexStart,
+                margin: Rect {
+                    left: Val::Auto,
+                    right: Val::Px(0.0),
+                    top: Val::Px(0.0),
+                    bottom: Val::Auto,
+                },
+                ..Default::default()
+            },
+            material,
+            ..Default::default()
+        }
+    }
+}
+
+struct ChatInputView;
+
+/// A single visible line of the scrollback, indexed from the top of the panel.
+struct ChatLineView(usize);
+impl ChatLineView {
+    fn handle_history_changed(
+        history: ChangedRes<ChatHistory>,
+        panel_state: Res<ChatPanelState>,
+        mut line_query: Query<(&ChatLineView, &mut Text)>,
+    ) {
+        Self::render(&history, panel_state.scroll, &mut line_query);
+    }
+
+    fn handle_panel_state_changed(
+        panel_state: ChangedRes<ChatPanelState>,
+        history: Res<ChatHistory>,
+        mut line_query: Query<(&ChatLineView, &mut Text)>,
+        mut input_query: Query<With<ChatInputView, &mut Text>>,
+    ) {
+        Self::render(&history, panel_state.scroll, &mut line_query);
+
+        for mut text in input_query.iter_mut() {
+            (*text).value = format!("> {}", panel_state.input);
+        }
+    }
+
+    fn render(history: &ChatHistory, scroll: usize, line_query: &mut Query<(&ChatLineView, &mut Text)>) {
+        let lines = history.lines();
+        let end = lines.len().saturating_sub(scroll);
+        let start = end.saturating_sub(CHAT_VISIBLE_LINES);
+
+        for (view, mut text) in line_query.iter_mut() {
+            let index = start + view.0;
+
+            (*text).value = match lines.get(index) {
+                Some(ChatLine::Message { name, text: message }) if index < end => format!("{}: {}", name, message),
+                Some(ChatLine::System(message)) if index < end => format!("* {}", message),
+                _ => String::new(),
+            };
+        }
+    }
+}
+
+
+// ==============================================================================
+// --- Helpers
+// ==============================================================================
+fn text(value: String, font: Handle<Font>) -> Text {
+    Text {
+        value,
+        font,
+        style: TextStyle {
+            font_size: 24.0,
+            color: Color::rgb(0.9, 0.9, 0.9),
+            ..Default::default()
+        },
+    }
+}
@@ -0,0 +1,113 @@
+use bevy::prelude::*;
+use log::debug;
+use std::{collections::HashMap, net::SocketAddr};
+
+use crate::core::outbox::{ConnectionProblemEvent, MAX_SEND_ATTEMPTS};
+
+
+pub struct ConnectionBannerPlugin;
+impl Plugin for ConnectionBannerPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup.system())
+            .add_system(ConnectionBannerView::handle_connection_problem_event.system());
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    commands
+        .spawn(ConnectionBannerView::bundle(materials.add(Color::NONE.into())))
+        .with_children(|children| {
+            children
+                .spawn(TextComponents {
+                    text: text(String::new(), font),
+                    ..Default::default()
+                })
+                .with(ConnectionBannerView);
+        });
+}
+
+
+/// Shown across the top of the screen while messages to a peer are failing, whichever screen is open.
+struct ConnectionBannerView;
+impl ConnectionBannerView {
+    fn bundle(material: Handle<ColorMaterial>) -> NodeComponents {
+        NodeComponents {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(0.0),
+                    right: Val::Px(0.0),
+                    top: Val::Px(0.0),
+                    bottom: Val::Auto,
+                },
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            material,
+            ..Default::default()
+        }
+    }
+
+    fn handle_connection_problem_event(
+        mut reader: Local<EventReader<ConnectionProblemEvent>>,
+        mut problems: Local<HashMap<SocketAddr, String>>,
+        events: Res<Events<ConnectionProblemEvent>>,
+        mut query: Query<With<ConnectionBannerView, &mut Text>>,
+    ) {
+        let mut is_changed = false;
+
+        for event in reader.iter(&events) {
+            debug!("handle_connection_problem_event() - {:?}", event);
+
+            match event {
+                ConnectionProblemEvent::Retrying { addr, attempt, .. } => {
+                    let value = format!(
+                        "Connection problem with {}, retrying ({}/{})...",
+                        addr, attempt, MAX_SEND_ATTEMPTS
+                    );
+                    problems.insert(*addr, value);
+                }
+                ConnectionProblemEvent::GaveUp { addr, reason } => {
+                    problems.insert(*addr, format!("Lost connection to {}: {}", addr, reason));
+                }
+                ConnectionProblemEvent::Resolved { addr } => {
+                    problems.remove(addr);
+                }
+            }
+
+            is_changed = true;
+        }
+
+        if !is_changed {
+            return;
+        }
+
+        let value = problems.values().next().cloned().unwrap_or_default();
+
+        for mut text in query.iter_mut() {
+            (*text).value = value.clone();
+        }
+    }
+}
+
+
+// ==============================================================================
+// --- Helpers
+// ==============================================================================
+fn text(value: String, font: Handle<Font>) -> Text {
+    Text {
+        value,
+        font,
+        style: TextStyle {
+            font_size: 32.0,
+            color: Color::rgb(1.0, 0.4, 0.3),
+            ..Default::default()
+        },
+    }
+}
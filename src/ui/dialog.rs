@@ -0,0 +1,142 @@
+use bevy::prelude::*;
+use log::debug;
+
+use super::main_menu::{MainMenuButtonSpawner, MainMenuMaterials};
+use crate::{
+    core::{game_cmd::GameCmd, phase::PhaseScoped},
+    prelude::*,
+};
+
+
+// ==========================================================================
+// Components
+// ==========================================================================
+pub struct Dialog;
+
+/// Closes its dialog & fires the `GameCmd`, if any.
+pub struct DialogButton(Option<GameCmd>);
+
+
+// ==========================================================================
+// Dialog Bundle Spawner
+// ==========================================================================
+/// A question over the board, answered with one of its buttons.
+pub(super) struct DialogSpawner {
+    material: Handle<ColorMaterial>,
+    text: TextComponents,
+    buttons: Vec<(MainMenuButtonSpawner, DialogButton)>,
+    scope: PhaseScoped,
+}
+
+impl SpawnWithCommands for DialogSpawner {
+    fn spawn_with_commands(self, commands: &mut Commands) -> &mut Commands {
+        let DialogSpawner {
+            material,
+            text,
+            buttons,
+            scope,
+        } = self;
+
+        commands
+            .spawn(Self::node_components(material))
+            .with(Dialog)
+            .with(scope)
+            .with_children(|commands| {
+                // Flex column direction is bottom to top, spawn the text last to place it on top
+                for (button, dialog_button) in buttons.into_iter().rev() {
+                    button.spawn_with_child_builder(commands).with(dialog_button);
+                }
+
+                commands.spawn(text);
+            })
+    }
+}
+
+impl DialogSpawner {
+    /// A yes or no question, only "yes" fires the `GameCmd`.
+    pub(super) fn confirm(materials: &Res<MainMenuMaterials>, question: impl Into<String>, cmd: GameCmd) -> Self {
+        Self::new(materials, question, vec![("Yes", Some(cmd)), ("No", None)])
+    }
+
+    pub(super) fn new(
+        materials: &Res<MainMenuMaterials>,
+        text: impl Into<String>,
+        buttons: Vec<(&str, Option<GameCmd>)>,
+    ) -> Self {
+        Self {
+            material: materials.panel.as_weak(),
+            text: Self::text_components(materials, text.into()),
+            buttons: buttons
+                .into_iter()
+                .map(|(label, cmd)| {
+                    let button = MainMenuButtonSpawner::from_materials(materials, label).with_width(300.0);
+                    (button, DialogButton(cmd))
+                })
+                .collect(),
+            scope: PhaseScoped::GAME,
+        }
+    }
+
+    /// Dialogs are shown over the board unless told otherwise.
+    pub(super) fn with_scope(self, scope: PhaseScoped) -> Self {
+        Self { scope, ..self }
+    }
+
+    fn node_components(material: Handle<ColorMaterial>) -> NodeComponents {
+        NodeComponents {
+            style: Style {
+                size: Size::new(Val::Percent(40.0), Val::Px(320.0)),
+                flex_direction: FlexDirection::Column,
+                align_content: AlignContent::Center,
+                justify_content: JustifyContent::Center,
+                margin: Rect::all(Val::Auto),
+                ..Default::default()
+            },
+            material,
+            ..Default::default()
+        }
+    }
+
+    fn text_components(materials: &Res<MainMenuMaterials>, value: String) -> TextComponents {
+        TextComponents {
+            style: Style {
+                margin: Rect::all(Val::Auto),
+                ..Default::default()
+            },
+            text: Text {
+                value,
+                font: materials.font.as_weak(),
+                style: TextStyle {
+                    font_size: 40.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        }
+    }
+}
+
+
+// ==========================================================================
+// Systems
+// ==========================================================================
+pub fn handle_dialog_button_pressed(
+    mut commands: Commands,
+    mut game_cmds: ResMut<Events<GameCmd>>,
+    interaction_query: Query<(Mutated<Interaction>, &DialogButton, &Parent)>,
+) {
+    for (interaction, button, parent) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        debug!("handle_dialog_button_pressed() - {:?}", button.0);
+
+        commands.despawn_recursive(parent.0);
+
+        if let Some(cmd) = button.0 {
+            game_cmds.send(cmd);
+        }
+    }
+}
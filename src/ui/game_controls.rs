@@ -0,0 +1,212 @@
+use bevy::prelude::*;
+use log::debug;
+
+use super::{
+    dialog::{Dialog, DialogSpawner},
+    main_menu::{MainMenuButtonSpawner, MainMenuMaterials},
+};
+use crate::{
+    core::{
+        game_cmd::{GameCmd, PendingOffer},
+        phase::{AppPhase, PhaseChangedEvent, PhaseScoped},
+        save::{SaveGameEvent, DEFAULT_SAVE_PATH},
+        GameState,
+    },
+    prelude::*,
+};
+
+
+// ==========================================================================
+// Components
+// ==========================================================================
+pub struct GameControls;
+
+#[derive(Debug, Clone, Copy)]
+pub enum GameControlButton {
+    Resign,
+    OfferDraw,
+    Takeback,
+    Save,
+}
+
+/// A dialog answering an offer of the opponent.
+pub struct OfferDialog(PendingOffer);
+
+
+// ==========================================================================
+// GameControls Bundle Spawner
+// ==========================================================================
+struct GameControlsSpawner {
+    resign_button: MainMenuButtonSpawner,
+    draw_button: MainMenuButtonSpawner,
+    takeback_button: MainMenuButtonSpawner,
+    /// Only hotseat games are saved, see `save::handle_save_game_event()`.
+    save_button: Option<MainMenuButtonSpawner>,
+}
+
+impl SpawnWithCommands for GameControlsSpawner {
+    fn spawn_with_commands(self, commands: &mut Commands) -> &mut Commands {
+        let GameControlsSpawner {
+            resign_button,
+            draw_button,
+            takeback_button,
+            save_button,
+        } = self;
+
+        commands
+            .spawn(Self::node_components())
+            .with(GameControls)
+            .with(PhaseScoped(&[AppPhase::Playing]))
+            .with_children(|commands| {
+                if let Some(save_button) = save_button {
+                    save_button.spawn_with_child_builder(commands).with(GameControlButton::Save);
+                }
+                takeback_button.spawn_with_child_builder(commands).with(GameControlButton::Takeback);
+                draw_button.spawn_with_child_builder(commands).with(GameControlButton::OfferDraw);
+                resign_button.spawn_with_child_builder(commands).with(GameControlButton::Resign);
+            })
+    }
+}
+
+impl GameControlsSpawner {
+    fn new(materials: &Res<MainMenuMaterials>, state: &GameState) -> Self {
+        // Only networked games ask the opponent first
+        let takeback_label = match state.game_type.is_networked() {
+            true => "Takeback",
+            false => "Undo",
+        };
+
+        let save_button = match state.game_type.is_hotseat() {
+            true => Some(MainMenuButtonSpawner::from_materials(materials, "Save").with_width(240.0)),
+            false => None,
+        };
+
+        Self {
+            resign_button: MainMenuButtonSpawner::from_materials(materials, "Resign").with_width(240.0),
+            draw_button: MainMenuButtonSpawner::from_materials(materials, "Offer Draw").with_width(240.0),
+            takeback_button: MainMenuButtonSpawner::from_materials(materials, takeback_label).with_width(240.0),
+            save_button,
+        }
+    }
+
+    /// Stacked in the top right corner, out of the way of the board.
+    fn node_components() -> NodeComponents {
+        NodeComponents {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::FlexEnd,
+                margin: Rect {
+                    left: Val::Auto,
+                    right: Val::Px(0.0),
+                    top: Val::Px(0.0),
+                    bottom: Val::Auto,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+}
+
+
+// ==========================================================================
+// Systems
+// ==========================================================================
+pub fn handle_phase_changed_event(
+    mut commands: Commands,
+    mut reader: Local<EventReader<PhaseChangedEvent>>,
+    events: Res<Events<PhaseChangedEvent>>,
+    materials: Res<MainMenuMaterials>,
+    state: Res<GameState>,
+) {
+    for event in reader.iter(&events) {
+        // Spectators only watch
+        if event.to != AppPhase::Playing || state.connection_info.is_spectator() {
+            continue;
+        }
+
+        debug!("handle_phase_changed_event() - spawn game controls");
+        GameControlsSpawner::new(&materials, &state).spawn_with_commands(&mut commands);
+    }
+}
+
+/// Resigning & offering a draw are confirmed first, takebacks are easily asked for again & saving is harmless.
+pub fn handle_control_button_pressed(
+    mut commands: Commands,
+    mut game_cmds: ResMut<Events<GameCmd>>,
+    mut save_game_events: ResMut<Events<SaveGameEvent>>,
+    materials: Res<MainMenuMaterials>,
+    dialog_query: Query<With<Dialog, Entity>>,
+    interaction_query: Query<(Mutated<Interaction>, &GameControlButton)>,
+) {
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        // One question at a time
+        if dialog_query.iter().next().is_some() {
+            continue;
+        }
+
+        debug!("handle_control_button_pressed() - {:?}", button);
+
+        let dialog = match button {
+            GameControlButton::Resign => DialogSpawner::confirm(&materials, "Resign the game?", GameCmd::Resign),
+            GameControlButton::OfferDraw => DialogSpawner::confirm(&materials, "Offer a draw?", GameCmd::OfferDraw),
+            GameControlButton::Takeback => {
+                game_cmds.send(GameCmd::Takeback);
+                continue;
+            }
+            GameControlButton::Save => {
+                save_game_events.send(SaveGameEvent {
+                    path: DEFAULT_SAVE_PATH.into(),
+                });
+                continue;
+            }
+        };
+
+        dialog.spawn_with_commands(&mut commands);
+    }
+}
+
+/// Shows a dialog for each offer made to a team played at this machine, for as long as the offer stands.
+pub fn handle_game_state_changed(
+    mut commands: Commands,
+    state: ChangedRes<GameState>,
+    materials: Res<MainMenuMaterials>,
+    dialog_query: Query<(Entity, &OfferDialog)>,
+) {
+    let incoming = state
+        .offer
+        .filter(|pending| state.is_local_team(pending.from.opponent()));
+
+    let mut is_shown = false;
+
+    for (entity, dialog) in dialog_query.iter() {
+        match incoming {
+            Some(pending) if pending == dialog.0 => is_shown = true,
+            _ => {
+                commands.despawn_recursive(entity);
+            }
+        }
+    }
+
+    let pending = match incoming {
+        Some(pending) if !is_shown => pending,
+        _ => return,
+    };
+
+    let name = state
+        .players
+        .iter()
+        .find(|(_, player_info)| player_info.team == pending.from)
+        .map(|(_, player_info)| player_info.name.clone())
+        .unwrap_or_else(|| pending.from.to_string());
+
+    let buttons = vec![("Accept", Some(GameCmd::AcceptOffer)), ("Decline", Some(GameCmd::DeclineOffer))];
+
+    DialogSpawner::new(&materials, format!("{} offers {}", name, pending.offer), buttons)
+        .spawn_with_commands(&mut commands)
+        .with(OfferDialog(pending));
+}
@@ -0,0 +1,359 @@
+use bevy::{input::keyboard::KeyboardInput, prelude::*};
+use log::{debug, info};
+use std::net::SocketAddr;
+
+use super::{
+    lobby::CreateLobbyEvent,
+    main_menu::{CreateMainMenuEvent, MainMenuButtonSpawner, MainMenuMaterials},
+    text_input::{edit_text, is_shift_pressed, pressed_key},
+};
+use crate::{
+    core::{
+        lobby::{GameOptions, TeamChoice, TimeControl, Variant},
+        phase::{AppPhase, PhaseScoped},
+        ruleset::{Ruleset, WinCondition},
+        settings::{SaveSettingsEvent, Settings},
+        CreateGameEvent,
+    },
+    prelude::*,
+};
+
+pub const MAX_PLAYER_NAME_LENGTH: usize = 16;
+
+
+/// Name & options picked on the options screen, kept between games. The name is also kept between launches, see
+/// `Settings`.
+#[derive(Debug, Clone)]
+pub struct PreGameOptions {
+    pub player_name: String,
+    /// When joining, only `host_team` is used, as the preferred team.
+    pub options: GameOptions,
+}
+
+impl Default for PreGameOptions {
+    fn default() -> Self {
+        PreGameOptions {
+            player_name: "Player".into(),
+            options: GameOptions::default(),
+        }
+    }
+}
+
+impl PreGameOptions {
+    /// Blank names fall back to the default.
+    pub fn player_name(&self) -> String {
+        match self.player_name.trim() {
+            "" => PreGameOptions::default().player_name,
+            name => name.into(),
+        }
+    }
+}
+
+/// What the options screen leads to.
+#[derive(Debug, Clone, Copy)]
+pub enum OptionsPurpose {
+    Host,
+    Join(SocketAddr),
+}
+
+
+// ==========================================================================
+// Components
+// ==========================================================================
+pub struct GameOptionsScreen(OptionsPurpose);
+
+#[derive(Debug, Clone, Copy)]
+pub enum OptionButton {
+    Team,
+    TimeControl,
+    Variant,
+    WinCondition,
+    Castling,
+    StartingHealth,
+}
+
+impl OptionButton {
+    fn label(&self, options: &PreGameOptions) -> String {
+        match self {
+            OptionButton::Team => format!("Team: {}", options.options.host_team),
+            OptionButton::TimeControl => format!("Time: {}", options.options.time_control),
+            OptionButton::Variant => format!("Variant: {}", options.options.variant),
+            OptionButton::WinCondition => format!("Win by: {}", options.options.ruleset.win_condition),
+            OptionButton::Castling => match options.options.ruleset.castling {
+                true => "Castling: On".into(),
+                false => "Castling: Off".into(),
+            },
+            OptionButton::StartingHealth => format!("Health: {}", options.options.ruleset.starting_health),
+        }
+    }
+}
+
+pub struct PlayerNameView;
+pub struct ConfirmButton;
+pub struct OptionsBackButton;
+
+
+// ==========================================================================
+// GameOptionsScreen Bundle Spawner
+// ==========================================================================
+pub(super) struct GameOptionsSpawner {
+    purpose: OptionsPurpose,
+    name: TextComponents,
+    option_buttons: Vec<(MainMenuButtonSpawner, OptionButton)>,
+    confirm_button: MainMenuButtonSpawner,
+    back_button: MainMenuButtonSpawner,
+}
+
+impl SpawnWithCommands for GameOptionsSpawner {
+    fn spawn_with_commands(self, commands: &mut Commands) -> &mut Commands {
+        let GameOptionsSpawner {
+            purpose,
+            name,
+            option_buttons,
+            confirm_button,
+            back_button,
+        } = self;
+
+        commands
+            .spawn(Self::node_components())
+            .with(GameOptionsScreen(purpose))
+            .with(PhaseScoped(&[AppPhase::MainMenu]))
+            .with_children(|commands| {
+                // Flex column direction is bottom to top, spawn the name last to place it on top
+                back_button.spawn_with_child_builder(commands).with(OptionsBackButton);
+                confirm_button.spawn_with_child_builder(commands).with(ConfirmButton);
+
+                for (button, option) in option_buttons.into_iter().rev() {
+                    button.spawn_with_child_builder(commands).with(option);
+                }
+
+                commands.spawn(name).with(PlayerNameView);
+            })
+    }
+}
+
+impl GameOptionsSpawner {
+    pub(super) fn new(materials: &Res<MainMenuMaterials>, options: &PreGameOptions, purpose: OptionsPurpose) -> Self {
+        // The host settles everything else, joining players only get a say in their team
+        let option_buttons: &[OptionButton] = match purpose {
+            OptionsPurpose::Host => &[
+                OptionButton::Team,
+                OptionButton::TimeControl,
+                OptionButton::Variant,
+                OptionButton::WinCondition,
+                OptionButton::Castling,
+                OptionButton::StartingHealth,
+            ],
+            OptionsPurpose::Join(_) => &[OptionButton::Team],
+        };
+
+        let confirm = match purpose {
+            OptionsPurpose::Host => "Create",
+            OptionsPurpose::Join(_) => "Find Games",
+        };
+
+        Self {
+            purpose,
+            name: Self::name_components(materials, name_label(options)),
+            option_buttons: option_buttons
+                .iter()
+                .map(|&option| {
+                    let button = MainMenuButtonSpawner::from_materials(materials, option.label(options));
+                    (button.with_width(480.0), option)
+                })
+                .collect(),
+            confirm_button: MainMenuButtonSpawner::from_materials(materials, confirm).with_width(480.0),
+            back_button: MainMenuButtonSpawner::from_materials(materials, "Back"),
+        }
+    }
+
+    fn node_components() -> NodeComponents {
+        NodeComponents {
+            style: Style {
+                size: Size::new(Val::Percent(50.0), Val::Percent(90.0)),
+                flex_direction: FlexDirection::Column,
+                align_content: AlignContent::Center,
+                justify_content: JustifyContent::Center,
+                margin: Rect::all(Val::Auto),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn name_components(materials: &Res<MainMenuMaterials>, value: String) -> TextComponents {
+        TextComponents {
+            style: Style {
+                margin: Rect::all(Val::Auto),
+                ..Default::default()
+            },
+            text: Text {
+                value,
+                font: materials.font.as_weak(),
+                style: TextStyle {
+                    font_size: 40.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        }
+    }
+}
+
+
+// ==========================================================================
+// Systems
+// ==========================================================================
+/// Typing edits the player name while the options screen is open.
+pub fn handle_keyboard_input(
+    mut reader: Local<EventReader<KeyboardInput>>,
+    events: Res<Events<KeyboardInput>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut options: ResMut<PreGameOptions>,
+    screen_query: Query<With<GameOptionsScreen, Entity>>,
+) {
+    let is_open = screen_query.iter().next().is_some();
+
+    for event in reader.iter(&events) {
+        if let (true, Some(key_code)) = (is_open, pressed_key(event)) {
+            let shift = is_shift_pressed(&keyboard_input);
+            edit_text(&mut options.player_name, key_code, shift, MAX_PLAYER_NAME_LENGTH);
+        }
+    }
+}
+
+/// Each option button cycles through its choices.
+pub fn handle_option_button_pressed(
+    mut options: ResMut<PreGameOptions>,
+    interaction_query: Query<(Mutated<Interaction>, &OptionButton)>,
+) {
+    for (interaction, option) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        debug!("handle_option_button_pressed() - {:?}", option);
+
+        let options = &mut options.options;
+
+        match option {
+            OptionButton::Team => options.host_team = next(&TeamChoice::ALL, options.host_team),
+            OptionButton::TimeControl => options.time_control = next(&TimeControl::PRESETS, options.time_control),
+            OptionButton::Variant => options.variant = next(&Variant::ALL, options.variant),
+            OptionButton::WinCondition => {
+                options.ruleset.win_condition = next(&WinCondition::ALL, options.ruleset.win_condition)
+            }
+            OptionButton::Castling => options.ruleset.castling = !options.ruleset.castling,
+            OptionButton::StartingHealth => {
+                options.ruleset.starting_health = next(&Ruleset::STARTING_HEALTH, options.ruleset.starting_health)
+            }
+        }
+    }
+}
+
+/// Keeps the labels of the name & option buttons in sync with the options.
+pub fn handle_options_changed(
+    options: ChangedRes<PreGameOptions>,
+    button_query: Query<(&OptionButton, &Children)>,
+    name_query: Query<With<PlayerNameView, Entity>>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (option, children) in button_query.iter() {
+        for child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                (*text).value = option.label(&options);
+            }
+        }
+    }
+
+    for entity in name_query.iter() {
+        if let Ok(mut text) = text_query.get_mut(entity) {
+            (*text).value = name_label(&options);
+        }
+    }
+}
+
+/// The name comes from the settings, on startup & whenever it is changed on the settings screen.
+pub fn handle_settings_changed(settings: ChangedRes<Settings>, mut options: ResMut<PreGameOptions>) {
+    if options.player_name != settings.player_name {
+        options.player_name = settings.player_name.clone();
+    }
+}
+
+/// Hosts create their game, joining players move on to the lobby of the host. The name is remembered for next time.
+pub fn handle_confirm_button_pressed(
+    mut commands: Commands,
+    options: Res<PreGameOptions>,
+    mut settings: ResMut<Settings>,
+    mut save_settings_events: ResMut<Events<SaveSettingsEvent>>,
+    mut create_game_events: ResMut<Events<CreateGameEvent>>,
+    mut create_lobby_events: ResMut<Events<CreateLobbyEvent>>,
+    screen_query: Query<(Entity, &GameOptionsScreen)>,
+    interaction_query: Query<With<ConfirmButton, Mutated<Interaction>>>,
+) {
+    let clicks = interaction_query
+        .iter()
+        .filter(|interaction| **interaction == Interaction::Clicked)
+        .next();
+
+    if clicks.is_none() {
+        return;
+    }
+
+    let player_name = options.player_name();
+    if settings.player_name != player_name {
+        settings.player_name = player_name;
+        save_settings_events.send(SaveSettingsEvent);
+    }
+
+    for (entity, screen) in screen_query.iter() {
+        commands.despawn_recursive(entity);
+
+        info!("handle_confirm_button_pressed() - {:?} with {:?}", screen.0, *options);
+
+        match screen.0 {
+            OptionsPurpose::Host => create_game_events.send(CreateGameEvent {
+                player_name: options.player_name(),
+                options: options.options.clone(),
+            }),
+            OptionsPurpose::Join(server_addr) => create_lobby_events.send(CreateLobbyEvent { server_addr }),
+        }
+    }
+}
+
+pub fn handle_back_button_pressed(
+    mut commands: Commands,
+    mut create_main_menu_events: ResMut<Events<CreateMainMenuEvent>>,
+    screen_query: Query<With<GameOptionsScreen, Entity>>,
+    interaction_query: Query<With<OptionsBackButton, Mutated<Interaction>>>,
+) {
+    let clicks = interaction_query
+        .iter()
+        .filter(|interaction| **interaction == Interaction::Clicked)
+        .next();
+
+    if clicks.is_none() {
+        return;
+    }
+
+    for entity in screen_query.iter() {
+        commands.despawn_recursive(entity);
+    }
+
+    create_main_menu_events.send(CreateMainMenuEvent);
+}
+
+
+// ==========================================================================
+// --- Helpers
+// ==========================================================================
+fn name_label(options: &PreGameOptions) -> String {
+    format!("Name: {}_", options.player_name)
+}
+
+/// The choice after `current`, wrapping around.
+pub(super) fn next<T: Copy + PartialEq>(choices: &[T], current: T) -> T {
+    let index = choices.iter().position(|choice| *choice == current).unwrap_or(0);
+    choices[(index + 1) % choices.len()]
+}
@@ -0,0 +1,201 @@
+use bevy::prelude::*;
+use log::{debug, info};
+
+use super::main_menu::{MainMenuButtonSpawner, MainMenuMaterials};
+use crate::{
+    core::{
+        game_cmd::GameCmd,
+        phase::{AppPhase, PhaseChangedEvent, PhaseScoped, PhaseState},
+        replay::WatchReplayEvent,
+        GameResult, GameState,
+    },
+    prelude::*,
+};
+
+
+// ==========================================================================
+// Components
+// ==========================================================================
+pub struct GameOverScreen;
+pub struct RematchButton;
+pub struct ReplayButton;
+pub struct LeaveButton;
+
+
+// ==========================================================================
+// GameOverScreen Bundle Spawner
+// ==========================================================================
+struct GameOverSpawner {
+    material: Handle<ColorMaterial>,
+    result: TextComponents,
+    rematch_button: Option<MainMenuButtonSpawner>,
+    replay_button: MainMenuButtonSpawner,
+    leave_button: MainMenuButtonSpawner,
+}
+
+impl SpawnWithCommands for GameOverSpawner {
+    fn spawn_with_commands(self, commands: &mut Commands) -> &mut Commands {
+        let GameOverSpawner {
+            material,
+            result,
+            rematch_button,
+            replay_button,
+            leave_button,
+        } = self;
+
+        commands
+            .spawn(Self::node_components(material))
+            .with(GameOverScreen)
+            .with(PhaseScoped(&[AppPhase::GameOver]))
+            .with_children(|commands| {
+                // Flex column direction is bottom to top, spawn the result last to place it on top
+                leave_button.spawn_with_child_builder(commands).with(LeaveButton);
+                replay_button.spawn_with_child_builder(commands).with(ReplayButton);
+
+                if let Some(rematch_button) = rematch_button {
+                    rematch_button.spawn_with_child_builder(commands).with(RematchButton);
+                }
+
+                commands.spawn(result);
+            })
+    }
+}
+
+impl GameOverSpawner {
+    fn new(materials: &Res<MainMenuMaterials>, state: &GameState) -> Self {
+        let value = match state.result {
+            Some(GameResult { winner: Some(team), reason }) => format!("{} wins - {}", team, reason),
+            Some(GameResult { winner: None, reason }) => reason.to_string(),
+            None => "Game over".into(),
+        };
+
+        // Spectators only watch
+        let rematch_button = match state.connection_info.is_spectator() {
+            true => None,
+            false => Some(MainMenuButtonSpawner::from_materials(materials, "Rematch").with_width(300.0)),
+        };
+
+        Self {
+            material: materials.panel.as_weak(),
+            result: Self::result_components(materials, value),
+            rematch_button,
+            replay_button: MainMenuButtonSpawner::from_materials(materials, "Replay").with_width(300.0),
+            leave_button: MainMenuButtonSpawner::from_materials(materials, "Main Menu").with_width(300.0),
+        }
+    }
+
+    /// A banner along the top of the window, leaving the final board in view.
+    fn node_components(material: Handle<ColorMaterial>) -> NodeComponents {
+        NodeComponents {
+            style: Style {
+                size: Size::new(Val::Percent(40.0), Val::Px(420.0)),
+                flex_direction: FlexDirection::Column,
+                align_content: AlignContent::Center,
+                justify_content: JustifyContent::Center,
+                margin: Rect {
+                    left: Val::Auto,
+                    right: Val::Auto,
+                    top: Val::Px(0.0),
+                    bottom: Val::Auto,
+                },
+                ..Default::default()
+            },
+            material,
+            ..Default::default()
+        }
+    }
+
+    fn result_components(materials: &Res<MainMenuMaterials>, value: String) -> TextComponents {
+        TextComponents {
+            style: Style {
+                margin: Rect::all(Val::Auto),
+                ..Default::default()
+            },
+            text: Text {
+                value,
+                font: materials.font.as_weak(),
+                style: TextStyle {
+                    font_size: 40.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        }
+    }
+}
+
+
+// ==========================================================================
+// Systems
+// ==========================================================================
+pub fn handle_phase_changed_event(
+    mut commands: Commands,
+    mut reader: Local<EventReader<PhaseChangedEvent>>,
+    events: Res<Events<PhaseChangedEvent>>,
+    materials: Res<MainMenuMaterials>,
+    state: Res<GameState>,
+) {
+    for event in reader.iter(&events) {
+        if event.to != AppPhase::GameOver {
+            continue;
+        }
+
+        debug!("handle_phase_changed_event() - spawn game over screen: {:?}", state.result);
+        GameOverSpawner::new(&materials, &state).spawn_with_commands(&mut commands);
+    }
+}
+
+/// Offers the opponent another game with swapped teams.
+pub fn handle_rematch_button_pressed(
+    mut game_cmds: ResMut<Events<GameCmd>>,
+    interaction_query: Query<With<RematchButton, Mutated<Interaction>>>,
+) {
+    let clicks = interaction_query
+        .iter()
+        .filter(|interaction| **interaction == Interaction::Clicked)
+        .next();
+
+    if clicks.is_none() {
+        return;
+    }
+
+    info!("handle_rematch_button_pressed()");
+    game_cmds.send(GameCmd::OfferRematch);
+}
+
+/// Steps through the game which just ended, see `replay::record_finished_game()`.
+pub fn handle_replay_button_pressed(
+    mut watch_replay_events: ResMut<Events<WatchReplayEvent>>,
+    interaction_query: Query<With<ReplayButton, Mutated<Interaction>>>,
+) {
+    let clicks = interaction_query
+        .iter()
+        .filter(|interaction| **interaction == Interaction::Clicked)
+        .next();
+
+    if clicks.is_none() {
+        return;
+    }
+
+    info!("handle_replay_button_pressed()");
+    watch_replay_events.send(WatchReplayEvent);
+}
+
+/// Leaving the game over phase tears down the board & leaves the game.
+pub fn handle_leave_button_pressed(
+    mut phase: ResMut<PhaseState>,
+    interaction_query: Query<With<LeaveButton, Mutated<Interaction>>>,
+) {
+    let clicks = interaction_query
+        .iter()
+        .filter(|interaction| **interaction == Interaction::Clicked)
+        .next();
+
+    if clicks.is_none() {
+        return;
+    }
+
+    info!("handle_leave_button_pressed()");
+    phase.set_next(AppPhase::MainMenu);
+}
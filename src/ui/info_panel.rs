@@ -1,9 +1,11 @@
 use crate::{
-    core::{unit::Team, GameStartedEvent, GameState},
+    core::{phase::PhaseScoped, unit::Team, GameStartedEvent, GameState},
     prelude::*,
 };
 use bevy::prelude::*;
+use bevy_networking::events::ConnectionStatsUpdated;
 use log::debug;
+use std::time::{Duration, Instant};
 
 pub struct InfoPanelPlugin;
 impl Plugin for InfoPanelPlugin {
@@ -11,7 +13,10 @@ impl Plugin for InfoPanelPlugin {
         app
             // .add_startup_system(setup.system())
             .add_system(handle_game_started_event.system())
-            .add_system(ActivePlayerView::handle_game_state_changed.system());
+            .add_system(ActivePlayerView::handle_game_state_changed.system())
+            .add_system(PlayerView::handle_game_state_changed.system())
+            .add_system(ClockView::update.system())
+            .add_system(PingView::handle_connection_stats_updated.system());
     }
 }
 
@@ -21,15 +26,22 @@ fn handle_game_started_event(
     events: Res<Events<GameStartedEvent>>,
     asset_server: Res<AssetServer>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    panel_query: Query<With<InfoPanelView, Entity>>,
 ) {
     for _event in reader.iter(&events) {
+        // Rematches keep the panel of the previous game
+        if panel_query.iter().next().is_some() {
+            continue;
+        }
+
         debug!("handle_create_game_event()");
 
         let font = asset_server.load("fonts/FiraSans-Bold.ttf");
 
         commands
             .spawn(InfoPanelView::bundle(materials.add(Color::NONE.into())))
-            .with(InfoPanelView);
+            .with(InfoPanelView)
+            .with(PhaseScoped::GAME);
 
         commands.with_children(|children| {
             children
@@ -38,6 +50,31 @@ fn handle_game_started_event(
                     ..Default::default()
                 })
                 .with(ActivePlayerView);
+
+            for &team in [Team::White, Team::Black].iter() {
+                children
+                    .spawn(TextComponents {
+                        text: text(format!("{}:", team), font.clone()),
+                        ..Default::default()
+                    })
+                    .with(PlayerView(team));
+            }
+
+            for &team in [Team::White, Team::Black].iter() {
+                children
+                    .spawn(TextComponents {
+                        text: text(format!("{} clock: -", team), font.clone()),
+                        ..Default::default()
+                    })
+                    .with(ClockView(team));
+            }
+
+            children
+                .spawn(TextComponents {
+                    text: text("Ping: -".into(), font.clone()),
+                    ..Default::default()
+                })
+                .with(PingView);
         });
     }
 }
@@ -78,9 +115,88 @@ impl ActivePlayerView {
 }
 
 
+/// Shows the name of the player controlling a team.
+struct PlayerView(Team);
+impl PlayerView {
+    fn handle_game_state_changed(state: ChangedRes<GameState>, mut query: Query<(&PlayerView, &mut Text)>) {
+        for (view, mut text) in query.iter_mut() {
+            let name = state
+                .players
+                .iter()
+                .find(|(_, player_info)| player_info.team == view.0)
+                .map(|(_, player_info)| player_info.name.clone())
+                .unwrap_or_else(|| "Waiting...".into());
+
+            (*text).value = format!("{}: {}", view.0, name);
+        }
+    }
+}
+
+
+/// Shows the time left to a team, the clock of the team to move runs down every frame.
+struct ClockView(Team);
+impl ClockView {
+    fn update(state: Res<GameState>, mut query: Query<(&ClockView, &mut Text)>) {
+        let now = Instant::now();
+
+        for (view, mut text) in query.iter_mut() {
+            let remaining = match &state.clocks {
+                Some(clocks) => format_clock(clocks.remaining(view.0, state.active_team, now)),
+                None => "-".into(),
+            };
+            let value = format!("{} clock: {}", view.0, remaining);
+
+            // Only touch the text when the shown time changes
+            if text.value != value {
+                (*text).value = value;
+            }
+        }
+    }
+}
+
+
+/// Shows the round trip time to the other player, or to the host when spectating.
+struct PingView;
+impl PingView {
+    fn handle_connection_stats_updated(
+        mut reader: Local<EventReader<ConnectionStatsUpdated>>,
+        events: Res<Events<ConnectionStatsUpdated>>,
+        state: Res<GameState>,
+        mut query: Query<With<PingView, &mut Text>>,
+    ) {
+        let remote_addrs = state.remote_addrs();
+
+        for ConnectionStatsUpdated(conn, stats) in reader.iter(&events) {
+            if !remote_addrs.contains(&conn.addr) {
+                continue;
+            }
+
+            let value = match stats.rtt {
+                Some(rtt) => format!("Ping: {} ms", rtt.as_millis()),
+                None => "Ping: -".into(),
+            };
+
+            for mut text in query.iter_mut() {
+                (*text).value = value.clone();
+            }
+        }
+    }
+}
+
+
 // ==============================================================================
 // --- Helpers
 // ==============================================================================
+/// Minutes & seconds, with tenths once a player is low on time.
+fn format_clock(remaining: Duration) -> String {
+    let seconds = remaining.as_secs();
+
+    match seconds {
+        0..=9 => format!("0:0{}.{}", seconds, remaining.subsec_millis() / 100),
+        _ => format!("{}:{:02}", seconds / 60, seconds % 60),
+    }
+}
+
 fn text(value: String, font: Handle<Font>) -> Text {
     Text {
         value,
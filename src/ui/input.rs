@@ -4,6 +4,8 @@ use log::info;
 
 use crate::{
     core::{
+        phase::{AppPhase, PhaseState},
+        ruleset::Ruleset,
         unit::{is_action_valid, Actions, Health, Team, Unit, UnitCmd},
         GameState, Tile,
     },
@@ -25,11 +27,22 @@ impl Default for InputState {
 pub fn handle_tile_interaction(
     mut input_state: ResMut<InputState>,
     game_state: Res<GameState>,
+    ruleset: Res<Ruleset>,
+    phase: Res<PhaseState>,
     unit_position_map: Res<PositionMap<Unit>>,
     mut cmds: ResMut<Events<UnitCmd>>,
     mut interaction_query: Query<With<Tile, (Mutated<Interaction>, &Position)>>,
     action_query: Query<(&Unit, &Position, &Team, &Health, &Actions)>,
 ) {
+    // The board is read-only while spectating & once the game is decided
+    if game_state.connection_info.is_spectator() || !phase.is(AppPhase::Playing) {
+        if let InputState::UnitSelected(_) = *input_state {
+            *input_state = InputState::Idle;
+        }
+
+        return;
+    }
+
     for (interaction, position) in interaction_query.iter_mut() {
         match *interaction {
             Interaction::Clicked => match *input_state {
@@ -39,7 +52,7 @@ pub fn handle_tile_interaction(
 
                         let team = action_query.get_component::<Team>(*entity).unwrap();
 
-                        if team.eq(&game_state.active_team) && team.eq(&game_state.local_player_info.team) {
+                        if team.eq(&game_state.active_team) && game_state.is_local_team(*team) {
                             info!("Unit for active team selected");
                             *input_state = InputState::UnitSelected(*entity);
                         } else {
@@ -51,7 +64,8 @@ pub fn handle_tile_interaction(
                     let actions = action_query.get_component::<Actions>(entity.clone()).unwrap();
                     let action = actions.get(0).unwrap();
 
-                    if is_action_valid(action, &entity, position, &unit_position_map, &action_query) {
+                    let (store, history) = (&unit_position_map, &game_state.history);
+                    if is_action_valid(action, &entity, position, store, &action_query, &ruleset, history) {
                         info!("Execute action {:?}", entity);
                         cmds.send(UnitCmd::ExecuteAction(entity, 0, *position));
                     }
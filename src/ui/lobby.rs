@@ -0,0 +1,283 @@
+use bevy::prelude::*;
+use log::{debug, info};
+use std::net::SocketAddr;
+
+use super::{
+    game_options::PreGameOptions,
+    main_menu::{MainMenuButtonSpawner, MainMenuMaterials},
+};
+use crate::{
+    core::{
+        lobby::{JoinRejectedEvent, LobbyGame, LobbyGamesReceived, RefreshLobbyEvent},
+        phase::{AppPhase, PhaseScoped, PhaseState},
+        spectator::SpectateGameEvent,
+        JoinGameEvent,
+    },
+    prelude::*,
+};
+
+
+pub struct CreateLobbyEvent {
+    pub server_addr: SocketAddr,
+}
+
+// ==========================================================================
+// Components
+// ==========================================================================
+pub struct LobbyScreen {
+    server_addr: SocketAddr,
+}
+
+pub struct LobbyGameButton(LobbyGame);
+pub struct RefreshButton;
+pub struct BackButton;
+
+
+// ==========================================================================
+// Lobby Bundle Spawner
+// ==========================================================================
+struct LobbySpawner {
+    server_addr: SocketAddr,
+    status: TextComponents,
+    game_buttons: Vec<(MainMenuButtonSpawner, LobbyGame)>,
+    refresh_button: MainMenuButtonSpawner,
+    back_button: MainMenuButtonSpawner,
+}
+
+impl SpawnWithCommands for LobbySpawner {
+    fn spawn_with_commands(self, commands: &mut Commands) -> &mut Commands {
+        let LobbySpawner {
+            server_addr,
+            status,
+            game_buttons,
+            refresh_button,
+            back_button,
+        } = self;
+
+        commands
+            .spawn(Self::node_components())
+            .with(LobbyScreen { server_addr })
+            .with(PhaseScoped(&[AppPhase::Lobby]))
+            .with_children(|commands| {
+                // Flex column direction is bottom to top, spawn the status text last to place it on top
+                back_button.spawn_with_child_builder(commands).with(BackButton);
+                refresh_button.spawn_with_child_builder(commands).with(RefreshButton);
+
+                for (button, game) in game_buttons.into_iter().rev() {
+                    button.spawn_with_child_builder(commands).with(LobbyGameButton(game));
+                }
+
+                commands.spawn(status);
+            })
+    }
+}
+
+impl LobbySpawner {
+    fn new(materials: &Res<MainMenuMaterials>, server_addr: SocketAddr, status: String, games: Vec<LobbyGame>) -> Self {
+        let game_buttons = games
+            .into_iter()
+            .map(|game| {
+                let options = &game.options;
+                let text = format!(
+                    "{} - {}, {}, {}, {}",
+                    game.host.name, options.host_team, options.time_control, options.variant, options.ruleset
+                );
+                let text = match game.started {
+                    true => format!("{} (Watch)", text),
+                    false => text,
+                };
+                (MainMenuButtonSpawner::from_materials(materials, text).with_width(960.0), game)
+            })
+            .collect();
+
+        Self {
+            server_addr,
+            status: Self::status_components(materials, status),
+            game_buttons,
+            refresh_button: MainMenuButtonSpawner::from_materials(materials, "Refresh"),
+            back_button: MainMenuButtonSpawner::from_materials(materials, "Back"),
+        }
+    }
+
+    fn node_components() -> NodeComponents {
+        NodeComponents {
+            style: Style {
+                size: Size::new(Val::Percent(50.0), Val::Percent(80.0)),
+                flex_direction: FlexDirection::Column,
+                align_content: AlignContent::Center,
+                justify_content: JustifyContent::Center,
+                margin: Rect::all(Val::Auto),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn status_components(materials: &Res<MainMenuMaterials>, value: String) -> TextComponents {
+        TextComponents {
+            style: Style {
+                margin: Rect::all(Val::Auto),
+                ..Default::default()
+            },
+            text: Text {
+                value,
+                font: materials.font.as_weak(),
+                style: TextStyle {
+                    font_size: 40.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        }
+    }
+}
+
+
+// ==========================================================================
+// Systems
+// ==========================================================================
+pub fn handle_create_lobby_event(
+    mut commands: Commands,
+    mut reader: Local<EventReader<CreateLobbyEvent>>,
+    events: Res<Events<CreateLobbyEvent>>,
+    mut refresh_events: ResMut<Events<RefreshLobbyEvent>>,
+    mut phase: ResMut<PhaseState>,
+    materials: Res<MainMenuMaterials>,
+) {
+    for event in reader.iter(&events) {
+        info!("handle_create_lobby_event() - {:?}", event.server_addr);
+
+        phase.set_next(AppPhase::Lobby);
+
+        LobbySpawner::new(&materials, event.server_addr, "Searching for games...".into(), vec![])
+            .spawn_with_commands(&mut commands);
+
+        refresh_events.send(RefreshLobbyEvent { server_addr: event.server_addr });
+    }
+}
+
+/// Respawns the lobby screen with the games listed by the host.
+pub fn handle_lobby_games_received(
+    mut commands: Commands,
+    mut reader: Local<EventReader<LobbyGamesReceived>>,
+    events: Res<Events<LobbyGamesReceived>>,
+    materials: Res<MainMenuMaterials>,
+    lobby_query: Query<(Entity, &LobbyScreen)>,
+) {
+    for event in reader.iter(&events) {
+        debug!("handle_lobby_games_received() - {} games", event.games.len());
+
+        for (entity, lobby) in lobby_query.iter() {
+            if lobby.server_addr != event.server_addr {
+                continue;
+            }
+
+            let status = match event.games.len() {
+                0 => "No open games".into(),
+                _ => "Open games".into(),
+            };
+
+            commands.despawn_recursive(entity);
+            LobbySpawner::new(&materials, event.server_addr, status, event.games.clone())
+                .spawn_with_commands(&mut commands);
+        }
+    }
+}
+
+/// Returns to the lobby with the host's reason for refusing the join.
+pub fn handle_join_rejected_event(
+    mut commands: Commands,
+    mut reader: Local<EventReader<JoinRejectedEvent>>,
+    events: Res<Events<JoinRejectedEvent>>,
+    materials: Res<MainMenuMaterials>,
+    lobby_query: Query<With<LobbyScreen, Entity>>,
+) {
+    for event in reader.iter(&events) {
+        info!("handle_join_rejected_event() - {}", event.reason);
+
+        for entity in lobby_query.iter() {
+            commands.despawn_recursive(entity);
+        }
+
+        LobbySpawner::new(&materials, event.server_addr, event.reason.clone(), vec![])
+            .spawn_with_commands(&mut commands);
+    }
+}
+
+/// Joins open games & spectates started ones.
+pub fn handle_lobby_game_button_pressed(
+    mut commands: Commands,
+    options: Res<PreGameOptions>,
+    mut join_game_events: ResMut<Events<JoinGameEvent>>,
+    mut spectate_game_events: ResMut<Events<SpectateGameEvent>>,
+    lobby_query: Query<(Entity, &LobbyScreen)>,
+    interaction_query: Query<(Mutated<Interaction>, &LobbyGameButton)>,
+) {
+    let clicked = interaction_query
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, button)| button.0.clone());
+
+    let game = match clicked {
+        Some(game) => game,
+        None => return,
+    };
+
+    debug!("handle_lobby_game_button_pressed() - {:?}", game);
+
+    for (entity, lobby) in lobby_query.iter() {
+        commands.despawn_recursive(entity);
+
+        if game.started {
+            spectate_game_events.send(SpectateGameEvent {
+                server_addr: lobby.server_addr,
+                game_id: game.id,
+            });
+            continue;
+        }
+
+        join_game_events.send(JoinGameEvent {
+            player_name: options.player_name(),
+            team: options.options.host_team,
+            server_addr: lobby.server_addr,
+            game_id: game.id,
+        });
+    }
+}
+
+pub fn handle_refresh_button_pressed(
+    mut refresh_events: ResMut<Events<RefreshLobbyEvent>>,
+    lobby_query: Query<&LobbyScreen>,
+    interaction_query: Query<With<RefreshButton, Mutated<Interaction>>>,
+) {
+    let clicks = interaction_query
+        .iter()
+        .filter(|interaction| **interaction == Interaction::Clicked)
+        .next();
+
+    if clicks.is_none() {
+        return;
+    }
+
+    for lobby in lobby_query.iter() {
+        refresh_events.send(RefreshLobbyEvent { server_addr: lobby.server_addr });
+    }
+}
+
+/// The lobby screen goes with the lobby phase & the main menu comes back with the main menu phase.
+pub fn handle_back_button_pressed(
+    mut phase: ResMut<PhaseState>,
+    interaction_query: Query<With<BackButton, Mutated<Interaction>>>,
+) {
+    let clicks = interaction_query
+        .iter()
+        .filter(|interaction| **interaction == Interaction::Clicked)
+        .next();
+
+    if clicks.is_none() {
+        return;
+    }
+
+    phase.set_next(AppPhase::MainMenu);
+}
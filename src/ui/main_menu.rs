@@ -1,8 +1,20 @@
 use bevy::prelude::*;
-use log::{debug, info};
+use log::{debug, info, warn};
+use std::net::SocketAddr;
 
+use super::{
+    dialog::DialogSpawner,
+    game_options::{GameOptionsSpawner, OptionsPurpose, PreGameOptions},
+    settings_screen::{SettingsFocus, SettingsScreenSpawner},
+};
 use crate::{
-    core::{CreateGameEvent, JoinGameEvent, PlayerInfo, Team, AppConfig},
+    core::{
+        phase::{AppPhase, PhaseChangedEvent, PhaseScoped},
+        replay::{LoadReplayEvent, DEFAULT_REPLAY_PATH},
+        save::{LoadFailedEvent, LoadGameEvent, DEFAULT_SAVE_PATH},
+        settings::Settings,
+        CreateHotseatGameEvent,
+    },
     prelude::*,
 };
 
@@ -17,6 +29,10 @@ pub struct MainMenu;
 pub struct MainMenuButton;
 pub struct StartButton;
 pub struct JoinButton;
+pub struct HotseatButton;
+pub struct LoadButton;
+pub struct ReplayButton;
+pub struct SettingsButton;
 
 
 // ==========================================================================
@@ -25,6 +41,10 @@ pub struct JoinButton;
 struct MainMenuSpawner {
     start_button: MainMenuButtonSpawner,
     join_button: MainMenuButtonSpawner,
+    hotseat_button: MainMenuButtonSpawner,
+    load_button: MainMenuButtonSpawner,
+    replay_button: MainMenuButtonSpawner,
+    settings_button: MainMenuButtonSpawner,
 }
 
 impl SpawnWithCommands for MainMenuSpawner {
@@ -32,10 +52,19 @@ impl SpawnWithCommands for MainMenuSpawner {
         commands
             .spawn(Self::node_components())
             .with(MainMenu)
+            .with(PhaseScoped(&[AppPhase::MainMenu]))
             .with_children(|commands| {
                 self.start_button.spawn_with_child_builder(commands).with(StartButton);
 
                 self.join_button.spawn_with_child_builder(commands).with(JoinButton);
+
+                self.hotseat_button.spawn_with_child_builder(commands).with(HotseatButton);
+
+                self.load_button.spawn_with_child_builder(commands).with(LoadButton);
+
+                self.replay_button.spawn_with_child_builder(commands).with(ReplayButton);
+
+                self.settings_button.spawn_with_child_builder(commands).with(SettingsButton);
             })
     }
 }
@@ -45,13 +74,17 @@ impl MainMenuSpawner {
         Self {
             start_button: MainMenuButtonSpawner::from_materials(materials, "Start"),
             join_button: MainMenuButtonSpawner::from_materials(materials, "Join"),
+            hotseat_button: MainMenuButtonSpawner::from_materials(materials, "Hotseat"),
+            load_button: MainMenuButtonSpawner::from_materials(materials, "Load"),
+            replay_button: MainMenuButtonSpawner::from_materials(materials, "Replay"),
+            settings_button: MainMenuButtonSpawner::from_materials(materials, "Settings"),
         }
     }
 
     fn node_components() -> NodeComponents {
         NodeComponents {
             style: Style {
-                size: Size::new(Val::Percent(50.0), Val::Percent(50.0)),
+                size: Size::new(Val::Percent(50.0), Val::Percent(60.0)),
                 flex_direction: FlexDirection::Column,
                 align_content: AlignContent::Center,
                 justify_content: JustifyContent::Center,
@@ -67,10 +100,11 @@ impl MainMenuSpawner {
 // ==========================================================================
 // MainMenuButton Bundle Spawner
 // ==========================================================================
-struct MainMenuButtonSpawner {
+pub(super) struct MainMenuButtonSpawner {
     material: Handle<ColorMaterial>,
     font: Handle<Font>,
-    text: &'static str,
+    text: String,
+    width: f32,
 }
 
 impl SpawnWithChildBuilder for MainMenuButtonSpawner {
@@ -85,17 +119,22 @@ impl SpawnWithChildBuilder for MainMenuButtonSpawner {
 }
 
 impl MainMenuButtonSpawner {
-    fn from_materials(materials: &Res<MainMenuMaterials>, text: &'static str) -> Self {
+    pub(super) fn from_materials(materials: &Res<MainMenuMaterials>, text: impl Into<String>) -> Self {
         let material = materials.normal.as_weak();
         let font = materials.font.as_weak();
+        let text = text.into();
 
-        Self { material, font, text }
+        Self { material, font, text, width: 200.0 }
+    }
+
+    pub(super) fn with_width(self, width: f32) -> Self {
+        Self { width, ..self }
     }
 
     fn button_components(&self) -> ButtonComponents {
         ButtonComponents {
             style: Style {
-                size: Size::new(Val::Px(200.0), Val::Px(80.0)),
+                size: Size::new(Val::Px(self.width), Val::Px(80.0)),
                 margin: Rect::all(Val::Auto),
                 justify_content: JustifyContent::Center,
                 align_items: AlignItems::Center,
@@ -109,7 +148,7 @@ impl MainMenuButtonSpawner {
     fn text_components(&self) -> TextComponents {
         TextComponents {
             text: Text {
-                value: self.text.into(),
+                value: self.text.clone(),
                 font: self.font.as_weak(),
                 style: TextStyle {
                     font_size: 40.0,
@@ -138,6 +177,19 @@ pub fn handle_create_main_menu_event(
     }
 }
 
+/// Returning to the main menu phase, e.g. after a game, brings the main menu back up.
+pub fn handle_phase_changed_event(
+    mut reader: Local<EventReader<PhaseChangedEvent>>,
+    events: Res<Events<PhaseChangedEvent>>,
+    mut create_main_menu_events: ResMut<Events<CreateMainMenuEvent>>,
+) {
+    for event in reader.iter(&events) {
+        if event.to == AppPhase::MainMenu {
+            create_main_menu_events.send(CreateMainMenuEvent);
+        }
+    }
+}
+
 
 /// Handles button interaction changes & updates the button material
 /// to reflect its interaction state.
@@ -154,10 +206,11 @@ pub fn handle_main_menu_button_interaction(
     }
 }
 
-/// The system listens for when "Start" button is pressed & fires `CreateGameEvent`.
+/// The system listens for when "Start" button is pressed & opens the options of the game to host.
 pub fn handle_start_button_pressed(
     mut commands: Commands,
-    mut create_game_events: ResMut<Events<CreateGameEvent>>,
+    materials: Res<MainMenuMaterials>,
+    options: Res<PreGameOptions>,
     main_menu_query: Query<With<MainMenu, Entity>>,
     interaction_query: Query<With<StartButton, Mutated<Interaction>>>,
 ) {
@@ -176,18 +229,15 @@ pub fn handle_start_button_pressed(
         commands.despawn_recursive(entity);
     }
 
-    create_game_events.send(CreateGameEvent {
-        player_info: PlayerInfo {
-            name: "Player 1".into(),
-            team: Team::White,
-        },
-    });
+    GameOptionsSpawner::new(&materials, &options, OptionsPurpose::Host).spawn_with_commands(&mut commands);
 }
 
+/// The system listens for when "Join" button is pressed & opens the options for joining the last remote host.
 pub fn handle_join_button_pressed(
     mut commands: Commands,
-    app_config: Res<AppConfig>,
-    mut join_game_events: ResMut<Events<JoinGameEvent>>,
+    settings: Res<Settings>,
+    materials: Res<MainMenuMaterials>,
+    options: Res<PreGameOptions>,
     main_menu_query: Query<With<MainMenu, Entity>>,
     interaction_query: Query<With<JoinButton, Mutated<Interaction>>>,
 ) {
@@ -202,29 +252,152 @@ pub fn handle_join_button_pressed(
 
     debug!("handle_join_button_pressed()");
 
-    let server_addr = app_config.remote_addr.clone().unwrap();
+    // Typed on the settings screen, so not necessarily a valid address
+    let server_addr = match settings.remote_addr.as_ref().map(|addr| addr.parse::<SocketAddr>()) {
+        Some(Ok(addr)) => addr,
+        Some(Err(error)) => {
+            warn!("handle_join_button_pressed() - invalid remote address: {}", error);
+            return;
+        }
+        None => {
+            warn!("handle_join_button_pressed() - no remote address configured, use --remote or the settings");
+            return;
+        }
+    };
+
+    for entity in main_menu_query.iter() {
+        commands.despawn_recursive(entity);
+    }
+
+    GameOptionsSpawner::new(&materials, &options, OptionsPurpose::Join(server_addr))
+        .spawn_with_commands(&mut commands);
+}
+
+/// The system listens for when "Hotseat" button is pressed & fires `CreateHotseatGameEvent`.
+pub fn handle_hotseat_button_pressed(
+    mut commands: Commands,
+    mut create_hotseat_game_events: ResMut<Events<CreateHotseatGameEvent>>,
+    main_menu_query: Query<With<MainMenu, Entity>>,
+    interaction_query: Query<With<HotseatButton, Mutated<Interaction>>>,
+) {
+    let clicks = interaction_query
+        .iter()
+        .filter(|interaction| **interaction == Interaction::Clicked)
+        .next();
+
+    if clicks.is_none() {
+        return;
+    }
+
+    debug!("handle_hotseat_button_pressed()");
 
     for entity in main_menu_query.iter() {
         commands.despawn_recursive(entity);
     }
 
-    join_game_events.send(JoinGameEvent {
-        player_info: PlayerInfo {
-            name: "Player2".into(),
-            team: Team::Black,
-        },
-        server_addr: server_addr.parse().expect("Unable to parse socket address"),
+    create_hotseat_game_events.send(CreateHotseatGameEvent {
+        white_name: "Player 1".into(),
+        black_name: "Player 2".into(),
+    });
+}
+
+/// The system listens for when "Load" button is pressed & resumes the game saved at the default path.
+pub fn handle_load_button_pressed(
+    mut load_game_events: ResMut<Events<LoadGameEvent>>,
+    interaction_query: Query<With<LoadButton, Mutated<Interaction>>>,
+) {
+    let clicks = interaction_query
+        .iter()
+        .filter(|interaction| **interaction == Interaction::Clicked)
+        .next();
+
+    if clicks.is_none() {
+        return;
+    }
+
+    debug!("handle_load_button_pressed()");
+
+    // The main menu goes with the phase change, & stays should the save fail to load
+    load_game_events.send(LoadGameEvent {
+        path: DEFAULT_SAVE_PATH.into(),
+    });
+}
+
+/// The system listens for when "Replay" button is pressed & watches the last recorded game.
+pub fn handle_replay_button_pressed(
+    mut load_replay_events: ResMut<Events<LoadReplayEvent>>,
+    interaction_query: Query<With<ReplayButton, Mutated<Interaction>>>,
+) {
+    let clicks = interaction_query
+        .iter()
+        .filter(|interaction| **interaction == Interaction::Clicked)
+        .next();
+
+    if clicks.is_none() {
+        return;
+    }
+
+    debug!("handle_replay_button_pressed()");
+
+    load_replay_events.send(LoadReplayEvent {
+        path: DEFAULT_REPLAY_PATH.into(),
     });
 }
 
+/// The system listens for when "Settings" button is pressed & opens the settings screen.
+pub fn handle_settings_button_pressed(
+    mut commands: Commands,
+    materials: Res<MainMenuMaterials>,
+    settings: Res<Settings>,
+    mut focus: ResMut<SettingsFocus>,
+    main_menu_query: Query<With<MainMenu, Entity>>,
+    interaction_query: Query<With<SettingsButton, Mutated<Interaction>>>,
+) {
+    let clicks = interaction_query
+        .iter()
+        .filter(|interaction| **interaction == Interaction::Clicked)
+        .next();
+
+    if clicks.is_none() {
+        return;
+    }
+
+    debug!("handle_settings_button_pressed()");
+
+    for entity in main_menu_query.iter() {
+        commands.despawn_recursive(entity);
+    }
+
+    *focus = SettingsFocus::default();
+    SettingsScreenSpawner::new(&materials, &settings, *focus).spawn_with_commands(&mut commands);
+}
+
+/// Tells the player why "Load" or "Replay" left them on the main menu.
+pub fn handle_load_failed_event(
+    mut commands: Commands,
+    mut reader: Local<EventReader<LoadFailedEvent>>,
+    events: Res<Events<LoadFailedEvent>>,
+    materials: Res<MainMenuMaterials>,
+) {
+    for event in reader.iter(&events) {
+        let text = format!("Unable to load {}: {}", event.path.display(), event.reason);
+
+        DialogSpawner::new(&materials, text, vec![("OK", None)])
+            .with_scope(PhaseScoped(&[AppPhase::MainMenu]))
+            .spawn_with_commands(&mut commands);
+    }
+}
+
 /// ==========================================================================
 /// Resources
 /// ==========================================================================
 pub struct MainMenuMaterials {
-    normal: Handle<ColorMaterial>,
+    pub(super) normal: Handle<ColorMaterial>,
     hovered: Handle<ColorMaterial>,
-    pressed: Handle<ColorMaterial>,
-    font: Handle<Font>,
+    pub(super) pressed: Handle<ColorMaterial>,
+    /// Background of screens drawn over the board.
+    pub(super) panel: Handle<ColorMaterial>,
+    pub(super) font: Handle<Font>,
 }
 
 impl FromResources for MainMenuMaterials {
@@ -236,6 +409,7 @@ impl FromResources for MainMenuMaterials {
             normal: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
             hovered: materials.add(Color::rgb(0.25, 0.25, 0.25).into()),
             pressed: materials.add(Color::rgb(0.35, 0.75, 0.35).into()),
+            panel: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.7).into()),
             font: asset_server.load("fonts/FiraSans-Bold.ttf"),
         }
     }
@@ -4,8 +4,9 @@ use bevy_prototype_lyon::prelude::*;
 
 
 use crate::core::{
+    ruleset::{legal_targets, Ruleset},
     unit::{Actions, Health, Team, Unit},
-    Map, Tile,
+    GameState, Map, Tile,
 };
 use log::info;
 use std::cmp::Ordering;
@@ -21,6 +22,8 @@ use std::ops::Deref;
 pub fn handle_input_state_change(
     mut previous_state: Local<Option<InputState>>,
     input_state: ChangedRes<InputState>,
+    game_state: Res<GameState>,
+    ruleset: Res<Ruleset>,
     unit_position_map: Res<PositionMap<Unit>>,
     tile_position_map: Res<PositionMap<Tile>>,
     mut tile_query: Query<(&Tile, &mut TileOverlayState)>,
@@ -38,7 +41,16 @@ pub fn handle_input_state_change(
             let actions = action_query.get_component::<Actions>(entity.clone()).unwrap();
             let action = actions.get(0).unwrap();
 
-            for target in action.list_targets(&entity, &unit_position_map, &action_query) {
+            let targets = legal_targets(
+                action,
+                &entity,
+                &unit_position_map,
+                &action_query,
+                &ruleset,
+                &game_state.history,
+            );
+
+            for target in targets {
                 info!("! Target {:?}", target);
 
                 let tile_entity = tile_position_map.get(&target).unwrap();
@@ -51,17 +63,9 @@ pub fn handle_input_state_change(
             }
         }
         InputState::Idle => {
-            if let Some(InputState::UnitSelected(entity)) = *previous_state {
-                let actions = action_query.get_component::<Actions>(entity.clone()).unwrap();
-                let action = actions.get(0).unwrap();
-
-                for target in action.list_targets(&entity, &unit_position_map, &action_query) {
-                    info!("! Target {:?}", target);
-
-                    let tile_entity = tile_position_map.get(&target).unwrap();
-
-                    let (_, mut tile_overlay_state) = tile_query.get_mut(*tile_entity).unwrap();
-
+            // Targets may have changed with the move just made, castling among them, so all shown targets are hidden
+            if let Some(InputState::UnitSelected(_)) = *previous_state {
+                for (_, mut tile_overlay_state) in tile_query.iter_mut() {
                     if *tile_overlay_state != TileOverlayState::Invisible {
                         *tile_overlay_state = TileOverlayState::Invisible;
                     }
@@ -1,11 +1,24 @@
 use bevy::prelude::*;
 
+mod chat_panel;
+mod connection_banner;
+mod dialog;
+mod game_controls;
+mod game_options;
+mod game_over;
 mod info_panel;
 mod input;
+mod lobby;
 mod main_menu;
 mod map;
+mod replay_controls;
+mod settings_screen;
 mod sprite_interaction;
+mod text_input;
+mod waiting_screen;
 
+use chat_panel::ChatPanelPlugin;
+use connection_banner::ConnectionBannerPlugin;
 use info_panel::InfoPanelPlugin;
 use input::InputState;
 
@@ -20,12 +33,54 @@ impl Plugin for UIPlugin {
             .init_resource::<main_menu::MainMenuMaterials>()
             .add_event::<main_menu::CreateMainMenuEvent>()
             .add_system(main_menu::handle_create_main_menu_event.system())
+            .add_system(main_menu::handle_phase_changed_event.system())
             .add_system_to_stage(stage::UPDATE, main_menu::handle_main_menu_button_interaction.system())
             .add_system_to_stage(stage::UPDATE, main_menu::handle_start_button_pressed.system())
             .add_system(main_menu::handle_join_button_pressed.system())
+            .add_system(main_menu::handle_hotseat_button_pressed.system())
+            .add_system(main_menu::handle_load_button_pressed.system())
+            .add_system(main_menu::handle_replay_button_pressed.system())
+            .add_system(main_menu::handle_settings_button_pressed.system())
+            .add_system(main_menu::handle_load_failed_event.system())
+            .init_resource::<game_options::PreGameOptions>()
+            .add_system(game_options::handle_keyboard_input.system())
+            .add_system(game_options::handle_option_button_pressed.system())
+            .add_system(game_options::handle_options_changed.system())
+            .add_system(game_options::handle_settings_changed.system())
+            .add_system(game_options::handle_confirm_button_pressed.system())
+            .add_system(game_options::handle_back_button_pressed.system())
+            .init_resource::<settings_screen::SettingsFocus>()
+            .add_system(settings_screen::handle_keyboard_input.system())
+            .add_system(settings_screen::handle_setting_button_pressed.system())
+            .add_system(settings_screen::update_setting_labels.system())
+            .add_system(settings_screen::handle_back_button_pressed.system())
+            .add_event::<lobby::CreateLobbyEvent>()
+            .add_system(lobby::handle_create_lobby_event.system())
+            .add_system(lobby::handle_lobby_games_received.system())
+            .add_system(lobby::handle_join_rejected_event.system())
+            .add_system(lobby::handle_lobby_game_button_pressed.system())
+            .add_system(lobby::handle_refresh_button_pressed.system())
+            .add_system(lobby::handle_back_button_pressed.system())
+            .add_system(waiting_screen::handle_phase_changed_event.system())
+            .add_system(waiting_screen::handle_cancel_button_pressed.system())
+            .add_system(game_over::handle_phase_changed_event.system())
+            .add_system(game_over::handle_rematch_button_pressed.system())
+            .add_system(game_over::handle_replay_button_pressed.system())
+            .add_system(game_over::handle_leave_button_pressed.system())
+            .add_system(game_controls::handle_phase_changed_event.system())
+            .add_system(game_controls::handle_control_button_pressed.system())
+            .add_system(game_controls::handle_game_state_changed.system())
+            .add_system(dialog::handle_dialog_button_pressed.system())
+            .add_system(replay_controls::handle_phase_changed_event.system())
+            .add_system(replay_controls::handle_replay_button_pressed.system())
+            .add_system(replay_controls::handle_keyboard_input.system())
+            .add_system(replay_controls::handle_timeline_clicked.system())
+            .add_system(replay_controls::handle_replay_changed.system())
             .init_resource::<InputState>()
             .add_startup_system(setup.system())
             .add_plugin(InfoPanelPlugin)
+            .add_plugin(ChatPanelPlugin)
+            .add_plugin(ConnectionBannerPlugin)
             .add_system_to_stage(
                 stage::PRE_UPDATE,
                 sprite_interaction::sprite_interaction_system.system(),
@@ -0,0 +1,383 @@
+use bevy::{
+    input::{keyboard::KeyboardInput, ElementState},
+    prelude::*,
+    ui::FocusPolicy,
+};
+use log::debug;
+
+use super::main_menu::{MainMenuButtonSpawner, MainMenuMaterials};
+use crate::{
+    core::{
+        phase::{AppPhase, PhaseChangedEvent, PhaseScoped, PhaseState},
+        replay::{Replay, ReplayCmd},
+        GameResult,
+    },
+    prelude::*,
+};
+
+
+// ==========================================================================
+// Components
+// ==========================================================================
+pub struct ReplayPanel;
+pub struct ReplayStatusText;
+
+/// Clicking the timeline jumps to the move under the cursor.
+pub struct ReplayTimeline;
+pub struct ReplayTimelineFill;
+
+#[derive(Debug, Clone, Copy)]
+pub enum ReplayButton {
+    First,
+    Back,
+    TogglePlay,
+    Forward,
+    Last,
+    Slower,
+    Faster,
+    Exit,
+}
+
+impl ReplayButton {
+    const ALL: [ReplayButton; 8] = [
+        ReplayButton::First,
+        ReplayButton::Back,
+        ReplayButton::TogglePlay,
+        ReplayButton::Forward,
+        ReplayButton::Last,
+        ReplayButton::Slower,
+        ReplayButton::Faster,
+        ReplayButton::Exit,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            ReplayButton::First => "|<",
+            ReplayButton::Back => "<",
+            ReplayButton::TogglePlay => "Play",
+            ReplayButton::Forward => ">",
+            ReplayButton::Last => ">|",
+            ReplayButton::Slower => "Slower",
+            ReplayButton::Faster => "Faster",
+            ReplayButton::Exit => "Exit",
+        }
+    }
+
+    /// Exit leaves the replay rather than stepping through it.
+    fn cmd(&self) -> Option<ReplayCmd> {
+        match self {
+            ReplayButton::First => Some(ReplayCmd::First),
+            ReplayButton::Back => Some(ReplayCmd::Back),
+            ReplayButton::TogglePlay => Some(ReplayCmd::TogglePlay),
+            ReplayButton::Forward => Some(ReplayCmd::Forward),
+            ReplayButton::Last => Some(ReplayCmd::Last),
+            ReplayButton::Slower => Some(ReplayCmd::Slower),
+            ReplayButton::Faster => Some(ReplayCmd::Faster),
+            ReplayButton::Exit => None,
+        }
+    }
+}
+
+
+// ==========================================================================
+// ReplayPanel Bundle Spawner
+// ==========================================================================
+struct ReplayPanelSpawner {
+    material: Handle<ColorMaterial>,
+    status: TextComponents,
+    timeline_material: Handle<ColorMaterial>,
+    fill_material: Handle<ColorMaterial>,
+    progress: f32,
+    buttons: Vec<(MainMenuButtonSpawner, ReplayButton)>,
+}
+
+impl SpawnWithCommands for ReplayPanelSpawner {
+    fn spawn_with_commands(self, commands: &mut Commands) -> &mut Commands {
+        let ReplayPanelSpawner {
+            material,
+            status,
+            timeline_material,
+            fill_material,
+            progress,
+            buttons,
+        } = self;
+
+        commands
+            .spawn(Self::node_components(material))
+            .with(ReplayPanel)
+            .with(PhaseScoped(&[AppPhase::Replay]))
+            .with_children(|commands| {
+                // Flex column direction is bottom to top, spawn the buttons first to place them at the bottom
+                commands.spawn(Self::row_components()).with_children(|commands| {
+                    for (button, replay_button) in buttons.into_iter() {
+                        button.spawn_with_child_builder(commands).with(replay_button);
+                    }
+                });
+
+                commands
+                    .spawn(Self::timeline_components(timeline_material))
+                    .with(ReplayTimeline)
+                    .with_children(|commands| {
+                        // Clicks go through the fill to the timeline
+                        commands
+                            .spawn(Self::fill_components(fill_material, progress))
+                            .with(ReplayTimelineFill)
+                            .with(FocusPolicy::Pass);
+                    });
+
+                commands.spawn(status).with(ReplayStatusText);
+            })
+    }
+}
+
+impl ReplayPanelSpawner {
+    fn new(materials: &Res<MainMenuMaterials>, replay: &Replay) -> Self {
+        Self {
+            material: materials.panel.as_weak(),
+            status: Self::status_components(materials, status_text(replay)),
+            timeline_material: materials.normal.as_weak(),
+            fill_material: materials.pressed.as_weak(),
+            progress: progress(replay),
+            buttons: ReplayButton::ALL
+                .iter()
+                .map(|&button| {
+                    let spawner = MainMenuButtonSpawner::from_materials(materials, button.label()).with_width(140.0);
+                    (spawner, button)
+                })
+                .collect(),
+        }
+    }
+
+    /// Along the bottom of the window, leaving the board in view.
+    fn node_components(material: Handle<ColorMaterial>) -> NodeComponents {
+        NodeComponents {
+            style: Style {
+                size: Size::new(Val::Percent(80.0), Val::Px(220.0)),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::SpaceAround,
+                margin: Rect {
+                    left: Val::Auto,
+                    right: Val::Auto,
+                    top: Val::Auto,
+                    bottom: Val::Px(0.0),
+                },
+                ..Default::default()
+            },
+            material,
+            ..Default::default()
+        }
+    }
+
+    fn row_components() -> NodeComponents {
+        NodeComponents {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Px(90.0)),
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn timeline_components(material: Handle<ColorMaterial>) -> ButtonComponents {
+        ButtonComponents {
+            style: Style {
+                size: Size::new(Val::Percent(90.0), Val::Px(20.0)),
+                flex_direction: FlexDirection::Row,
+                ..Default::default()
+            },
+            material,
+            ..Default::default()
+        }
+    }
+
+    fn fill_components(material: Handle<ColorMaterial>, progress: f32) -> NodeComponents {
+        NodeComponents {
+            style: Style {
+                size: Size::new(Val::Percent(progress * 100.0), Val::Percent(100.0)),
+                ..Default::default()
+            },
+            material,
+            ..Default::default()
+        }
+    }
+
+    fn status_components(materials: &Res<MainMenuMaterials>, value: String) -> TextComponents {
+        TextComponents {
+            text: Text {
+                value,
+                font: materials.font.as_weak(),
+                style: TextStyle {
+                    font_size: 40.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        }
+    }
+}
+
+
+// ==========================================================================
+// Systems
+// ==========================================================================
+pub fn handle_phase_changed_event(
+    mut commands: Commands,
+    mut reader: Local<EventReader<PhaseChangedEvent>>,
+    events: Res<Events<PhaseChangedEvent>>,
+    materials: Res<MainMenuMaterials>,
+    replay: Res<Replay>,
+) {
+    for event in reader.iter(&events) {
+        if event.to != AppPhase::Replay {
+            continue;
+        }
+
+        debug!("handle_phase_changed_event() - spawn replay controls");
+        ReplayPanelSpawner::new(&materials, &replay).spawn_with_commands(&mut commands);
+    }
+}
+
+pub fn handle_replay_button_pressed(
+    mut replay_cmds: ResMut<Events<ReplayCmd>>,
+    mut phase: ResMut<PhaseState>,
+    interaction_query: Query<(Mutated<Interaction>, &ReplayButton)>,
+) {
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        debug!("handle_replay_button_pressed() - {:?}", button);
+
+        match button.cmd() {
+            Some(cmd) => replay_cmds.send(cmd),
+            None => phase.set_next(AppPhase::MainMenu),
+        }
+    }
+}
+
+/// Arrow keys step, Home & End jump to either end, space plays & pauses, up & down change the speed.
+pub fn handle_keyboard_input(
+    mut reader: Local<EventReader<KeyboardInput>>,
+    events: Res<Events<KeyboardInput>>,
+    mut replay_cmds: ResMut<Events<ReplayCmd>>,
+    mut phase: ResMut<PhaseState>,
+) {
+    for event in reader.iter(&events) {
+        if !phase.is(AppPhase::Replay) || event.state != ElementState::Pressed {
+            continue;
+        }
+
+        let cmd = match event.key_code {
+            Some(KeyCode::Home) => ReplayCmd::First,
+            Some(KeyCode::Left) => ReplayCmd::Back,
+            Some(KeyCode::Space) => ReplayCmd::TogglePlay,
+            Some(KeyCode::Right) => ReplayCmd::Forward,
+            Some(KeyCode::End) => ReplayCmd::Last,
+            Some(KeyCode::Down) => ReplayCmd::Slower,
+            Some(KeyCode::Up) => ReplayCmd::Faster,
+            Some(KeyCode::Escape) => {
+                phase.set_next(AppPhase::MainMenu);
+                continue;
+            }
+            _ => continue,
+        };
+
+        replay_cmds.send(cmd);
+    }
+}
+
+#[derive(Default)]
+pub struct CursorState {
+    reader: EventReader<CursorMoved>,
+    position: Vec2,
+}
+
+/// Jumps to the move at the clicked point of the timeline.
+pub fn handle_timeline_clicked(
+    mut cursor: Local<CursorState>,
+    cursor_moved_events: Res<Events<CursorMoved>>,
+    replay: Res<Replay>,
+    mut replay_cmds: ResMut<Events<ReplayCmd>>,
+    interaction_query: Query<With<ReplayTimeline, (Mutated<Interaction>, &Node, &GlobalTransform)>>,
+) {
+    if let Some(cursor_moved) = cursor.reader.latest(&cursor_moved_events) {
+        cursor.position = cursor_moved.position;
+    }
+
+    for (interaction, node, transform) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked || node.size.x() <= 0.0 {
+            continue;
+        }
+
+        // UI nodes are positioned by their center
+        let left = transform.translation.x() - node.size.x() / 2.0;
+        let fraction = ((cursor.position.x() - left) / node.size.x()).max(0.0).min(1.0);
+        let step = (fraction * replay.len() as f32).round() as usize;
+
+        debug!("handle_timeline_clicked() - jump to move {}", step);
+        replay_cmds.send(ReplayCmd::JumpTo(step));
+    }
+}
+
+pub fn handle_replay_changed(
+    replay: ChangedRes<Replay>,
+    mut text_query: Query<With<ReplayStatusText, &mut Text>>,
+    mut fill_query: Query<With<ReplayTimelineFill, &mut Style>>,
+) {
+    for mut text in text_query.iter_mut() {
+        (*text).value = status_text(&replay);
+    }
+
+    for mut style in fill_query.iter_mut() {
+        style.size.width = Val::Percent(progress(&replay) * 100.0);
+    }
+}
+
+
+// ==========================================================================
+// --- Helpers
+// ==========================================================================
+/// E.g. "Move 12 / 40 - Black to move - Playing 2x".
+fn status_text(replay: &Replay) -> String {
+    let position = match (replay.step() == replay.len(), replay.result()) {
+        (true, Some(GameResult { winner: Some(team), reason })) => format!("{} wins - {}", team, reason),
+        (true, Some(GameResult { winner: None, reason })) => reason.to_string(),
+        _ => {
+            let team = replay.active_team();
+            let name = replay
+                .players()
+                .iter()
+                .find(|player_info| player_info.team == team)
+                .map(|player_info| format!(" ({})", player_info.name))
+                .unwrap_or_default();
+
+            format!("{}{} to move", team, name)
+        }
+    };
+
+    let playback = match replay.is_playing() {
+        true => "Playing",
+        false => "Paused",
+    };
+
+    format!(
+        "Move {} / {} - {} - {} {}x",
+        replay.step(),
+        replay.len(),
+        position,
+        playback,
+        replay.speed()
+    )
+}
+
+fn progress(replay: &Replay) -> f32 {
+    match replay.len() {
+        0 => 1.0,
+        len => replay.step() as f32 / len as f32,
+    }
+}
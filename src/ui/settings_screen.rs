@@ -0,0 +1,294 @@
+use bevy::{input::keyboard::KeyboardInput, prelude::*};
+use log::debug;
+
+use super::{
+    game_options::{next, MAX_PLAYER_NAME_LENGTH},
+    main_menu::{CreateMainMenuEvent, MainMenuButtonSpawner, MainMenuMaterials},
+    text_input::{edit_text, is_shift_pressed, pressed_key},
+};
+use crate::{
+    core::{
+        phase::{AppPhase, PhaseScoped},
+        settings::{SaveSettingsEvent, Settings, Theme},
+    },
+    prelude::*,
+};
+
+const MAX_PORT_LENGTH: usize = 5;
+const MAX_REMOTE_LENGTH: usize = 64;
+
+const VOLUME_STEPS: [f32; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+const SCALE_STEPS: [f32; 6] = [0.5, 0.75, 1.0, 1.25, 1.5, 2.0];
+
+
+/// The text setting which typing edits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SettingsFocus(SettingButton);
+
+impl Default for SettingsFocus {
+    fn default() -> Self {
+        SettingsFocus(SettingButton::Name)
+    }
+}
+
+
+// ==========================================================================
+// Components
+// ==========================================================================
+pub struct SettingsScreen;
+pub struct SettingsBackButton;
+
+/// Text settings are focused by clicking them & edited by typing, the others cycle through their choices.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingButton {
+    Name,
+    Port,
+    Remote,
+    Volume,
+    Theme,
+    Scale,
+}
+
+impl SettingButton {
+    const ALL: [SettingButton; 6] = [
+        SettingButton::Name,
+        SettingButton::Port,
+        SettingButton::Remote,
+        SettingButton::Volume,
+        SettingButton::Theme,
+        SettingButton::Scale,
+    ];
+
+    fn is_text(&self) -> bool {
+        match self {
+            SettingButton::Name | SettingButton::Port | SettingButton::Remote => true,
+            _ => false,
+        }
+    }
+
+    fn label(&self, settings: &Settings, focus: SettingsFocus) -> String {
+        let cursor = if focus.0 == *self { "_" } else { "" };
+
+        match self {
+            SettingButton::Name => format!("Name: {}{}", settings.player_name, cursor),
+            SettingButton::Port => format!("Port: {}{}", settings.port, cursor),
+            SettingButton::Remote => {
+                let remote = settings.remote_addr.as_deref().unwrap_or_default();
+                format!("Remote: {}{}", remote, cursor)
+            }
+            SettingButton::Volume => format!("Volume: {}%", (settings.volume * 100.0).round()),
+            SettingButton::Theme => format!("Theme: {}", settings.theme),
+            SettingButton::Scale => format!("Scale: {}x", settings.scale),
+        }
+    }
+}
+
+
+// ==========================================================================
+// SettingsScreen Bundle Spawner
+// ==========================================================================
+pub(super) struct SettingsScreenSpawner {
+    hint: TextComponents,
+    setting_buttons: Vec<(MainMenuButtonSpawner, SettingButton)>,
+    back_button: MainMenuButtonSpawner,
+}
+
+impl SpawnWithCommands for SettingsScreenSpawner {
+    fn spawn_with_commands(self, commands: &mut Commands) -> &mut Commands {
+        let SettingsScreenSpawner {
+            hint,
+            setting_buttons,
+            back_button,
+        } = self;
+
+        commands
+            .spawn(Self::node_components())
+            .with(SettingsScreen)
+            .with(PhaseScoped(&[AppPhase::MainMenu]))
+            .with_children(|commands| {
+                // Flex column direction is bottom to top, spawn the hint last to place it on top
+                back_button.spawn_with_child_builder(commands).with(SettingsBackButton);
+
+                for (button, setting) in setting_buttons.into_iter().rev() {
+                    button.spawn_with_child_builder(commands).with(setting);
+                }
+
+                commands.spawn(hint);
+            })
+    }
+}
+
+impl SettingsScreenSpawner {
+    pub(super) fn new(materials: &Res<MainMenuMaterials>, settings: &Settings, focus: SettingsFocus) -> Self {
+        Self {
+            hint: Self::hint_components(materials),
+            setting_buttons: SettingButton::ALL
+                .iter()
+                .map(|&setting| {
+                    let button = MainMenuButtonSpawner::from_materials(materials, setting.label(settings, focus));
+                    (button.with_width(640.0), setting)
+                })
+                .collect(),
+            back_button: MainMenuButtonSpawner::from_materials(materials, "Back"),
+        }
+    }
+
+    fn node_components() -> NodeComponents {
+        NodeComponents {
+            style: Style {
+                size: Size::new(Val::Percent(50.0), Val::Percent(90.0)),
+                flex_direction: FlexDirection::Column,
+                align_content: AlignContent::Center,
+                justify_content: JustifyContent::Center,
+                margin: Rect::all(Val::Auto),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn hint_components(materials: &Res<MainMenuMaterials>) -> TextComponents {
+        TextComponents {
+            style: Style {
+                margin: Rect::all(Val::Auto),
+                ..Default::default()
+            },
+            text: Text {
+                value: "Enter or Back saves text, port & scale apply on the next launch".into(),
+                font: materials.font.as_weak(),
+                style: TextStyle {
+                    font_size: 30.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        }
+    }
+}
+
+
+// ==========================================================================
+// Systems
+// ==========================================================================
+/// Typing edits the focused text setting while the settings screen is open, enter commits it.
+pub fn handle_keyboard_input(
+    mut reader: Local<EventReader<KeyboardInput>>,
+    events: Res<Events<KeyboardInput>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    focus: Res<SettingsFocus>,
+    mut settings: ResMut<Settings>,
+    mut save_settings_events: ResMut<Events<SaveSettingsEvent>>,
+    screen_query: Query<With<SettingsScreen, Entity>>,
+) {
+    let is_open = screen_query.iter().next().is_some();
+
+    for event in reader.iter(&events) {
+        let key_code = match pressed_key(event) {
+            Some(key_code) if is_open => key_code,
+            _ => continue,
+        };
+
+        if key_code == KeyCode::Return {
+            save_settings_events.send(SaveSettingsEvent);
+            continue;
+        }
+
+        let shift = is_shift_pressed(&keyboard_input);
+
+        // Edited on a copy, so keys which change nothing do not mark the settings changed
+        match focus.0 {
+            SettingButton::Name => {
+                let mut name = settings.player_name.clone();
+                if edit_text(&mut name, key_code, shift, MAX_PLAYER_NAME_LENGTH) {
+                    settings.player_name = name;
+                }
+            }
+            SettingButton::Port => {
+                let mut port = settings.port.clone();
+                if edit_text(&mut port, key_code, shift, MAX_PORT_LENGTH) && port.chars().all(|c| c.is_ascii_digit()) {
+                    settings.port = port;
+                }
+            }
+            SettingButton::Remote => {
+                let mut remote = settings.remote_addr.clone().unwrap_or_default();
+                if edit_text(&mut remote, key_code, shift, MAX_REMOTE_LENGTH) {
+                    settings.remote_addr = Some(remote).filter(|remote| !remote.is_empty());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Text settings take the focus, committing the one which had it, the others cycle through their choices.
+pub fn handle_setting_button_pressed(
+    mut focus: ResMut<SettingsFocus>,
+    mut settings: ResMut<Settings>,
+    mut save_settings_events: ResMut<Events<SaveSettingsEvent>>,
+    interaction_query: Query<(Mutated<Interaction>, &SettingButton)>,
+) {
+    for (interaction, setting) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        debug!("handle_setting_button_pressed() - {:?}", setting);
+
+        match setting {
+            setting if setting.is_text() => focus.0 = *setting,
+            SettingButton::Volume => settings.volume = next(&VOLUME_STEPS, settings.volume),
+            SettingButton::Theme => settings.theme = next(&Theme::ALL, settings.theme),
+            SettingButton::Scale => settings.scale = next(&SCALE_STEPS, settings.scale),
+            _ => {}
+        }
+
+        save_settings_events.send(SaveSettingsEvent);
+    }
+}
+
+/// Keeps the labels of the setting buttons in sync with the settings & the focus.
+pub fn update_setting_labels(
+    settings: Res<Settings>,
+    focus: Res<SettingsFocus>,
+    button_query: Query<(&SettingButton, &Children)>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (setting, children) in button_query.iter() {
+        let label = setting.label(&settings, *focus);
+
+        for child in children.iter() {
+            // Only written when the label changes, this runs every frame
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                if text.value != label {
+                    (*text).value = label.clone();
+                }
+            }
+        }
+    }
+}
+
+/// Leaving the screen commits the focused text setting.
+pub fn handle_back_button_pressed(
+    mut commands: Commands,
+    mut save_settings_events: ResMut<Events<SaveSettingsEvent>>,
+    mut create_main_menu_events: ResMut<Events<CreateMainMenuEvent>>,
+    screen_query: Query<With<SettingsScreen, Entity>>,
+    interaction_query: Query<With<SettingsBackButton, Mutated<Interaction>>>,
+) {
+    let clicks = interaction_query
+        .iter()
+        .filter(|interaction| **interaction == Interaction::Clicked)
+        .next();
+
+    if clicks.is_none() {
+        return;
+    }
+
+    for entity in screen_query.iter() {
+        commands.despawn_recursive(entity);
+    }
+
+    save_settings_events.send(SaveSettingsEvent);
+    create_main_menu_events.send(CreateMainMenuEvent);
+}
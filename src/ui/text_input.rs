@@ -0,0 +1,103 @@
+use bevy::{input::keyboard::KeyboardInput, input::ElementState, prelude::*};
+
+
+/// Applies a pressed key to a text buffer, returns true if the buffer changed.
+///
+/// Bevy does not expose received characters yet, so keys are mapped assuming a US layout.
+pub fn edit_text(buffer: &mut String, key_code: KeyCode, shift: bool, max_length: usize) -> bool {
+    if key_code == KeyCode::Back {
+        return buffer.pop().is_some();
+    }
+
+    match key_code_to_char(key_code, shift) {
+        Some(c) if buffer.chars().count() < max_length => {
+            buffer.push(c);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// The key of a key press, `None` for releases & keys bevy has no `KeyCode` for.
+pub fn pressed_key(event: &KeyboardInput) -> Option<KeyCode> {
+    match (&event.state, event.key_code) {
+        (ElementState::Pressed, Some(key_code)) => Some(key_code),
+        _ => None,
+    }
+}
+
+pub fn is_shift_pressed(keyboard_input: &Input<KeyCode>) -> bool {
+    keyboard_input.pressed(KeyCode::LShift) || keyboard_input.pressed(KeyCode::RShift)
+}
+
+fn key_code_to_char(key_code: KeyCode, shift: bool) -> Option<char> {
+    let c = match key_code {
+        KeyCode::A => 'a',
+        KeyCode::B => 'b',
+        KeyCode::C => 'c',
+        KeyCode::D => 'd',
+        KeyCode::E => 'e',
+        KeyCode::F => 'f',
+        KeyCode::G => 'g',
+        KeyCode::H => 'h',
+        KeyCode::I => 'i',
+        KeyCode::J => 'j',
+        KeyCode::K => 'k',
+        KeyCode::L => 'l',
+        KeyCode::M => 'm',
+        KeyCode::N => 'n',
+        KeyCode::O => 'o',
+        KeyCode::P => 'p',
+        KeyCode::Q => 'q',
+        KeyCode::R => 'r',
+        KeyCode::S => 's',
+        KeyCode::T => 't',
+        KeyCode::U => 'u',
+        KeyCode::V => 'v',
+        KeyCode::W => 'w',
+        KeyCode::X => 'x',
+        KeyCode::Y => 'y',
+        KeyCode::Z => 'z',
+        KeyCode::Key1 if shift => '!',
+        KeyCode::Key1 => '1',
+        KeyCode::Key2 if shift => '@',
+        KeyCode::Key2 => '2',
+        KeyCode::Key3 if shift => '#',
+        KeyCode::Key3 => '3',
+        KeyCode::Key4 if shift => '$',
+        KeyCode::Key4 => '4',
+        KeyCode::Key5 if shift => '%',
+        KeyCode::Key5 => '5',
+        KeyCode::Key6 if shift => '^',
+        KeyCode::Key6 => '6',
+        KeyCode::Key7 if shift => '&',
+        KeyCode::Key7 => '7',
+        KeyCode::Key8 if shift => '*',
+        KeyCode::Key8 => '8',
+        KeyCode::Key9 if shift => '(',
+        KeyCode::Key9 => '9',
+        KeyCode::Key0 if shift => ')',
+        KeyCode::Key0 => '0',
+        KeyCode::Space => ' ',
+        KeyCode::Minus if shift => '_',
+        KeyCode::Minus => '-',
+        KeyCode::Equals if shift => '+',
+        KeyCode::Equals => '=',
+        KeyCode::Comma if shift => '<',
+        KeyCode::Comma => ',',
+        KeyCode::Period if shift => '>',
+        KeyCode::Period => '.',
+        KeyCode::Slash if shift => '?',
+        KeyCode::Slash => '/',
+        KeyCode::Semicolon if shift => ':',
+        KeyCode::Semicolon => ';',
+        KeyCode::Apostrophe if shift => '"',
+        KeyCode::Apostrophe => '\'',
+        _ => return None,
+    };
+
+    match shift {
+        true => Some(c.to_ascii_uppercase()),
+        false => Some(c),
+    }
+}
@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+use log::{debug, info};
+
+use super::main_menu::{MainMenuButtonSpawner, MainMenuMaterials};
+use crate::{
+    core::{
+        phase::{AppPhase, PhaseChangedEvent, PhaseScoped, PhaseState},
+        GameState,
+    },
+    prelude::*,
+};
+
+
+// ==========================================================================
+// Components
+// ==========================================================================
+pub struct WaitingScreen;
+pub struct CancelButton;
+
+
+// ==========================================================================
+// WaitingScreen Bundle Spawner
+// ==========================================================================
+struct WaitingScreenSpawner {
+    status: TextComponents,
+    cancel_button: MainMenuButtonSpawner,
+}
+
+impl SpawnWithCommands for WaitingScreenSpawner {
+    fn spawn_with_commands(self, commands: &mut Commands) -> &mut Commands {
+        let WaitingScreenSpawner { status, cancel_button } = self;
+
+        commands
+            .spawn(Self::node_components())
+            .with(WaitingScreen)
+            .with(PhaseScoped(&[AppPhase::WaitingForOpponent]))
+            .with_children(|commands| {
+                // Flex column direction is bottom to top, spawn the status text last to place it on top
+                cancel_button.spawn_with_child_builder(commands).with(CancelButton);
+                commands.spawn(status);
+            })
+    }
+}
+
+impl WaitingScreenSpawner {
+    fn new(materials: &Res<MainMenuMaterials>, state: &GameState) -> Self {
+        let options = &state.options;
+        let status = format!(
+            "Waiting for an opponent...\n{}, {}, {}\n{}",
+            options.host_team, options.time_control, options.variant, options.ruleset
+        );
+
+        Self {
+            status: Self::status_components(materials, status),
+            cancel_button: MainMenuButtonSpawner::from_materials(materials, "Cancel"),
+        }
+    }
+
+    fn node_components() -> NodeComponents {
+        NodeComponents {
+            style: Style {
+                size: Size::new(Val::Percent(50.0), Val::Percent(50.0)),
+                flex_direction: FlexDirection::Column,
+                align_content: AlignContent::Center,
+                justify_content: JustifyContent::Center,
+                margin: Rect::all(Val::Auto),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn status_components(materials: &Res<MainMenuMaterials>, value: String) -> TextComponents {
+        TextComponents {
+            style: Style {
+                margin: Rect::all(Val::Auto),
+                ..Default::default()
+            },
+            text: Text {
+                value,
+                font: materials.font.as_weak(),
+                style: TextStyle {
+                    font_size: 40.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        }
+    }
+}
+
+
+// ==========================================================================
+// Systems
+// ==========================================================================
+pub fn handle_phase_changed_event(
+    mut commands: Commands,
+    mut reader: Local<EventReader<PhaseChangedEvent>>,
+    events: Res<Events<PhaseChangedEvent>>,
+    materials: Res<MainMenuMaterials>,
+    state: Res<GameState>,
+) {
+    for event in reader.iter(&events) {
+        if event.to != AppPhase::WaitingForOpponent {
+            continue;
+        }
+
+        debug!("handle_phase_changed_event() - spawn waiting screen");
+        WaitingScreenSpawner::new(&materials, &state).spawn_with_commands(&mut commands);
+    }
+}
+
+/// Stops hosting, leaving the waiting phase closes the game in the lobby.
+pub fn handle_cancel_button_pressed(
+    mut phase: ResMut<PhaseState>,
+    interaction_query: Query<With<CancelButton, Mutated<Interaction>>>,
+) {
+    let clicks = interaction_query
+        .iter()
+        .filter(|interaction| **interaction == Interaction::Clicked)
+        .next();
+
+    if clicks.is_none() {
+        return;
+    }
+
+    info!("handle_cancel_button_pressed()");
+    phase.set_next(AppPhase::MainMenu);
+}
@@ -6,7 +6,7 @@ use bevy::prelude::*;
 
 use std::{ops::Add, vec};
 
-use super::utils::{list_targets_step, move_unit};
+use super::utils::{list_targets_step, move_unit, occupant_team, Occupant, DIAGONAL_STEPS};
 
 
 pub fn bishop() -> UnitComponents {
@@ -21,6 +21,14 @@ pub fn bishop() -> UnitComponents {
 }
 
 
+pub fn bishop_targets(position: &Position, team: &Team, occupant: Occupant) -> Vec<Position> {
+    DIAGONAL_STEPS
+        .iter()
+        .flat_map(|step| list_targets_step(position, team, *step, occupant))
+        .collect()
+}
+
+
 pub struct BishopMoveAction;
 
 impl Action for BishopMoveAction {
@@ -33,17 +41,7 @@ impl Action for BishopMoveAction {
         let position = query.get_component::<Position>(*entity).unwrap();
         let team = query.get_component::<Team>(*entity).unwrap();
 
-        let steps: Vec<Position> = vec![
-            Position::new(1, -1),
-            Position::new(1, 1),
-            Position::new(-1, -1),
-            Position::new(-1, 1),
-        ];
-
-        let results = steps
-            .into_iter()
-            .flat_map(|step| list_targets_step(position, team, step, store, query))
-            .collect::<Vec<Position>>();
+        let results = bishop_targets(position, team, &|next| occupant_team(next, store, query));
 
         Box::new(results.into_iter())
     }
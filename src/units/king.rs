@@ -6,7 +6,9 @@ use bevy::prelude::*;
 
 use std::{ops::Add, vec};
 
-use super::utils::{list_targets_step, move_unit};
+use super::utils::{
+    list_targets_single_step, move_unit, occupant_team, Occupant, DIAGONAL_STEPS, ORTHOGONAL_STEPS,
+};
 
 
 pub fn king() -> UnitComponents {
@@ -21,9 +23,29 @@ pub fn king() -> UnitComponents {
 }
 
 
+pub fn king_targets(position: &Position, team: &Team, occupant: Occupant) -> Vec<Position> {
+    let steps: Vec<Position> = ORTHOGONAL_STEPS.iter().chain(DIAGONAL_STEPS.iter()).copied().collect();
+    list_targets_single_step(position, team, &steps, occupant)
+}
+
+/// Castling moves the king two squares towards a rook, which jumps to the square the king passed. Returns where the
+/// rook moves from & to.
+pub fn castling_rook(king_from: &Position, king_to: &Position) -> (Position, Position) {
+    let rook_x = if king_to.x > king_from.x { 7 } else { 0 };
+    let passed_x = (king_from.x + king_to.x) / 2;
+
+    (Position::new(rook_x, king_from.y), Position::new(passed_x, king_from.y))
+}
+
+pub fn is_castling(king_from: &Position, king_to: &Position) -> bool {
+    king_from.y == king_to.y && (king_to.x - king_from.x).abs() == 2
+}
+
+
 pub struct KingMoveAction;
 
 impl Action for KingMoveAction {
+    /// Castling depends on the ruleset & on the game so far, see `core::ruleset::legal_targets()`.
     fn list_targets(
         &self,
         entity: &Entity,
@@ -33,34 +55,7 @@ impl Action for KingMoveAction {
         let position = query.get_component::<Position>(*entity).unwrap();
         let team = query.get_component::<Team>(*entity).unwrap();
 
-        let steps: Vec<Position> = vec![
-            Position::new(0, 1),
-            Position::new(0, -1),
-            Position::new(1, 0),
-            Position::new(-1, 0),
-            Position::new(1, -1),
-            Position::new(1, 1),
-            Position::new(-1, -1),
-            Position::new(-1, 1),
-        ];
-
-        let results = steps
-            .into_iter()
-            .filter(|step| {
-                let next = position.add(*step);
-
-                if next.x < 0 || next.y < 0 || next.x > 7 || next.y > 7 {
-                    return false;
-                }
-
-                if let Some(unit_entity) = store.get(&next) {
-                    let target_team = query.get_component::<Team>(*unit_entity).unwrap();
-                    return target_team != team;
-                }
-
-                true
-            })
-            .collect::<Vec<Position>>();
+        let results = king_targets(position, team, &|next| occupant_team(next, store, query));
 
         Box::new(results.into_iter())
     }
@@ -72,6 +67,17 @@ impl Action for KingMoveAction {
         store: &Res<PositionMap<Unit>>,
         query: &Query<(&Unit, &Position, &Team, &Health, &Actions)>,
     ) -> Box<dyn Iterator<Item = ActionResult>> {
-        Box::new(move_unit(entity, target, store, query))
+        let position = query.get_component::<Position>(*entity).unwrap();
+        let mut results: Vec<ActionResult> = move_unit(entity, target, store, query).collect();
+
+        if is_castling(position, target) {
+            let (rook_from, rook_to) = castling_rook(position, target);
+
+            if let Some(rook_entity) = store.get(&rook_from) {
+                results.push(ActionResult::SetPosition(*rook_entity, rook_to));
+            }
+        }
+
+        Box::new(results.into_iter())
     }
 }
@@ -6,7 +6,7 @@ use bevy::prelude::*;
 
 use std::{ops::Add, vec};
 
-use super::utils::{list_targets_step, move_unit};
+use super::utils::{list_targets_single_step, move_unit, occupant_team, Occupant};
 
 
 pub fn knight() -> UnitComponents {
@@ -21,6 +21,22 @@ pub fn knight() -> UnitComponents {
 }
 
 
+pub const KNIGHT_STEPS: [Position; 8] = [
+    Position { x: -2, y: 1 },
+    Position { x: -1, y: 2 },
+    Position { x: 1, y: 2 },
+    Position { x: 2, y: 1 },
+    Position { x: 2, y: -1 },
+    Position { x: 1, y: -2 },
+    Position { x: -1, y: -2 },
+    Position { x: -2, y: -1 },
+];
+
+pub fn knight_targets(position: &Position, team: &Team, occupant: Occupant) -> Vec<Position> {
+    list_targets_single_step(position, team, &KNIGHT_STEPS, occupant)
+}
+
+
 pub struct KnightMoveAction;
 
 impl Action for KnightMoveAction {
@@ -33,34 +49,7 @@ impl Action for KnightMoveAction {
         let position = query.get_component::<Position>(*entity).unwrap();
         let team = query.get_component::<Team>(*entity).unwrap();
 
-        let steps: Vec<Position> = vec![
-            Position::new(-2, 1),
-            Position::new(-1, 2),
-            Position::new(1, 2),
-            Position::new(2, 1),
-            Position::new(2, -1),
-            Position::new(1, -2),
-            Position::new(-1, -2),
-            Position::new(-2, -1),
-        ];
-
-        let results = steps
-            .into_iter()
-            .filter(|step| {
-                let next = position.add(*step);
-
-                if next.x < 0 || next.y < 0 || next.x > 7 || next.y > 7 {
-                    return false;
-                }
-
-                if let Some(unit_entity) = store.get(&next) {
-                    let target_team = query.get_component::<Team>(*unit_entity).unwrap();
-                    return target_team != team;
-                }
-
-                true
-            })
-            .collect::<Vec<Position>>();
+        let results = knight_targets(position, team, &|next| occupant_team(next, store, query));
 
         Box::new(results.into_iter())
     }
@@ -13,3 +13,21 @@ pub use queen::*;
 pub use rook::*;
 
 pub mod utils;
+
+use crate::{
+    core::unit::{Team, Unit},
+    prelude::*,
+};
+use utils::Occupant;
+
+/// The squares a unit could move to, castling aside & regardless of what it leaves its king open to.
+pub fn unit_targets(unit: Unit, position: &Position, team: &Team, occupant: Occupant) -> Vec<Position> {
+    match unit {
+        Unit::Pawn => pawn_targets(position, team, occupant),
+        Unit::Bishop => bishop_targets(position, team, occupant),
+        Unit::Knight => knight_targets(position, team, occupant),
+        Unit::Rook => rook_targets(position, team, occupant),
+        Unit::King => king_targets(position, team, occupant),
+        Unit::Queen => queen_targets(position, team, occupant),
+    }
+}
@@ -6,7 +6,7 @@ use bevy::prelude::*;
 
 use std::{ops::Add, vec};
 
-use super::utils::move_unit;
+use super::utils::{is_on_board, move_unit, occupant_team, Occupant};
 
 
 pub fn pawn() -> UnitComponents {
@@ -21,60 +21,77 @@ pub fn pawn() -> UnitComponents {
 }
 
 
-pub struct PawnMoveAction;
+/// The direction pawns of the team move in.
+pub fn pawn_step(team: &Team) -> i32 {
+    match team {
+        Team::White => 1,
+        Team::Black => -1,
+    }
+}
 
-impl Action for PawnMoveAction {
-    fn list_targets(
-        &self,
-        entity: &Entity,
-        store: &Res<PositionMap<Unit>>,
-        query: &Query<(&Unit, &Position, &Team, &Health, &Actions)>,
-    ) -> Box<dyn Iterator<Item = Position>> {
-        let position = query.get_component::<Position>(*entity).unwrap();
-        let team = query.get_component::<Team>(*entity).unwrap();
+/// The row pawns of the team promote on.
+pub fn promotion_row(team: &Team) -> i32 {
+    match team {
+        Team::White => 7,
+        Team::Black => 0,
+    }
+}
 
-        let (step, home_row) = match team {
-            Team::White => (1, 1),
-            Team::Black => (-1, 6),
-        };
+pub fn pawn_targets(position: &Position, team: &Team, occupant: Occupant) -> Vec<Position> {
+    let step = pawn_step(team);
+    let home_row = match team {
+        Team::White => 1,
+        Team::Black => 6,
+    };
 
-        let mut results: Vec<Position> = vec![];
+    let mut results: Vec<Position> = vec![];
 
-        let mut next = position.add(Position::new(0, step));
+    let mut next = position.add(Position::new(0, step));
 
-        if store.get(&next).is_none() {
-            results.push(next.clone());
+    if is_on_board(&next) && occupant(&next).is_none() {
+        results.push(next.clone());
 
-            if position.y == home_row {
-                next = next.add(Position::new(0, step));
+        if position.y == home_row {
+            next = next.add(Position::new(0, step));
 
-                if store.get(&next).is_none() {
-                    results.push(next.clone());
-                }
+            if occupant(&next).is_none() {
+                results.push(next.clone());
             }
         }
+    }
 
-        if position.x > 0 {
-            let attack_left_position = position.add(Position::new(-1, step));
+    // Pawns only move diagonally to attack
+    for &x in [-1, 1].iter() {
+        let attack_position = position.add(Position::new(x, step));
 
-            if let Some(unit) = store.get(&attack_left_position) {
-                let target_team = query.get_component::<Team>(*unit).unwrap();
-                if target_team != team {
-                    results.push(attack_left_position);
-                }
-            }
+        if !is_on_board(&attack_position) {
+            continue;
         }
 
-        if position.x < 7 {
-            let attack_right_position = position.add(Position::new(1, step));
-
-            if let Some(unit) = store.get(&attack_right_position) {
-                let target_team = query.get_component::<Team>(*unit).unwrap();
-                if target_team != team {
-                    results.push(attack_right_position);
-                }
+        if let Some(target_team) = occupant(&attack_position) {
+            if target_team != *team {
+                results.push(attack_position);
             }
         }
+    }
+
+    results
+}
+
+
+pub struct PawnMoveAction;
+
+impl Action for PawnMoveAction {
+    fn list_targets(
+        &self,
+        entity: &Entity,
+        store: &Res<PositionMap<Unit>>,
+        query: &Query<(&Unit, &Position, &Team, &Health, &Actions)>,
+    ) -> Box<dyn Iterator<Item = Position>> {
+        let position = query.get_component::<Position>(*entity).unwrap();
+        let team = query.get_component::<Team>(*entity).unwrap();
+
+        let results = pawn_targets(position, team, &|next| occupant_team(next, store, query));
 
         Box::new(results.into_iter())
     }
@@ -86,6 +103,19 @@ impl Action for PawnMoveAction {
         store: &Res<PositionMap<Unit>>,
         query: &Query<(&Unit, &Position, &Team, &Health, &Actions)>,
     ) -> Box<dyn Iterator<Item = ActionResult>> {
-        Box::new(move_unit(entity, target, store, query))
+        let team = query.get_component::<Team>(*entity).unwrap();
+        let mut results: Vec<ActionResult> = move_unit(entity, target, store, query).collect();
+
+        // Pawns reaching the far rank become queens, unless they only hit a unit & stay where they are
+        let is_moved = results.iter().any(|result| match result {
+            ActionResult::SetPosition(moved, _) => moved == entity,
+            _ => false,
+        });
+
+        if is_moved && target.y == promotion_row(team) {
+            results.push(ActionResult::Promote(*entity, Unit::Queen));
+        }
+
+        Box::new(results.into_iter())
     }
 }
@@ -6,7 +6,7 @@ use bevy::prelude::*;
 
 use std::{ops::Add, vec};
 
-use super::utils::{list_targets_step, move_unit};
+use super::utils::{list_targets_step, move_unit, occupant_team, Occupant, ORTHOGONAL_STEPS};
 
 
 pub fn rook() -> UnitComponents {
@@ -20,6 +20,15 @@ pub fn rook() -> UnitComponents {
     }
 }
 
+
+pub fn rook_targets(position: &Position, team: &Team, occupant: Occupant) -> Vec<Position> {
+    ORTHOGONAL_STEPS
+        .iter()
+        .flat_map(|step| list_targets_step(position, team, *step, occupant))
+        .collect()
+}
+
+
 pub struct RookMoveAction;
 
 impl Action for RookMoveAction {
@@ -32,17 +41,7 @@ impl Action for RookMoveAction {
         let position = query.get_component::<Position>(*entity).unwrap();
         let team = query.get_component::<Team>(*entity).unwrap();
 
-        let steps: Vec<Position> = vec![
-            Position::new(0, 1),
-            Position::new(0, -1),
-            Position::new(1, 0),
-            Position::new(-1, 0),
-        ];
-
-        let results = steps
-            .into_iter()
-            .flat_map(|step| list_targets_step(position, team, step, store, query))
-            .collect::<Vec<Position>>();
+        let results = rook_targets(position, team, &|next| occupant_team(next, store, query));
 
         Box::new(results.into_iter())
     }
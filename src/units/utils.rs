@@ -5,23 +5,54 @@ use crate::core::unit::{Action, ActionResult, Actions, Health, Team, Unit, UnitC
 
 use std::{ops::Add, vec};
 
+pub const ORTHOGONAL_STEPS: [Position; 4] = [
+    Position { x: 0, y: 1 },
+    Position { x: 0, y: -1 },
+    Position { x: 1, y: 0 },
+    Position { x: -1, y: 0 },
+];
+
+pub const DIAGONAL_STEPS: [Position; 4] = [
+    Position { x: 1, y: -1 },
+    Position { x: 1, y: 1 },
+    Position { x: -1, y: -1 },
+    Position { x: -1, y: 1 },
+];
+
+/// The team of the unit standing on a square. Targets are listed against this rather than the board itself, so the
+/// same rules apply to the board in play & to boards imagined while looking for check, see `core::ruleset::Board`.
+pub type Occupant<'a> = &'a dyn Fn(&Position) -> Option<Team>;
+
+pub fn is_on_board(position: &Position) -> bool {
+    position.x >= 0 && position.y >= 0 && position.x <= 7 && position.y <= 7
+}
+
+/// The occupant of a square of the board in play.
+pub fn occupant_team(
+    position: &Position,
+    store: &Res<PositionMap<Unit>>,
+    query: &Query<(&Unit, &Position, &Team, &Health, &Actions)>,
+) -> Option<Team> {
+    let unit_entity = store.get(position)?;
+    query.get_component::<Team>(*unit_entity).ok().copied()
+}
+
+/// Every square along `step` up to the first unit, which is included if it is an enemy.
 pub fn list_targets_step(
     starting_position: &Position,
     team: &Team,
     step: Position,
-    store: &Res<PositionMap<Unit>>,
-    query: &Query<(&Unit, &Position, &Team, &Health, &Actions)>,
+    occupant: Occupant,
 ) -> vec::IntoIter<Position> {
     let mut results: Vec<Position> = vec![];
 
     let mut next_pos = Some(starting_position.add(step));
 
     while let Some(next) = next_pos {
-        if next.x < 0 || next.y < 0 || next.x > 7 || next.y > 7 {
+        if !is_on_board(&next) {
             next_pos = None;
-        } else if let Some(unit_entity) = store.get(&next) {
-            let target_team = query.get_component::<Team>(*unit_entity).unwrap();
-            if target_team != team {
+        } else if let Some(target_team) = occupant(&next) {
+            if target_team != *team {
                 results.push(next.clone());
             }
 
@@ -36,7 +67,23 @@ pub fn list_targets_step(
     return results.into_iter();
 }
 
+/// Every square one `step` away which is empty or holds an enemy.
+pub fn list_targets_single_step(
+    starting_position: &Position,
+    team: &Team,
+    steps: &[Position],
+    occupant: Occupant,
+) -> Vec<Position> {
+    steps
+        .iter()
+        .map(|step| starting_position.add(*step))
+        .filter(|next| is_on_board(next) && occupant(next).map_or(true, |target_team| target_team != *team))
+        .collect()
+}
+
 
+/// Moves the unit onto the target, taking a point of health from an enemy standing there. Units only leave the board
+/// once out of health, until then the attacker stays where it is.
 pub fn move_unit(
     entity: &Entity,
     target: &Position,
@@ -48,8 +95,11 @@ pub fn move_unit(
     if let Some(target_unit) = store.get(target) {
         let team = query.get_component::<Team>(*entity).unwrap();
         let target_team = query.get_component::<Team>(*target_unit).unwrap();
+        let target_health = query.get_component::<Health>(*target_unit).unwrap();
 
-        if target_team != team {
+        if target_team != team && target_health.0 > 1 {
+            commands = vec![ActionResult::SetHealth(*target_unit, Health(target_health.0 - 1))];
+        } else if target_team != team {
             commands.push(ActionResult::SetHealth(*target_unit, Health(0)));
         }
     }